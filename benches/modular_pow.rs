@@ -0,0 +1,62 @@
+//! Compares the Montgomery exponentiation with the square-and-multiply
+//! implementation it replaced. Run with `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use num_bigint::{BigInt, RandBigInt};
+use num_traits::One;
+use rsa::algorithms::modular_pow;
+use rsa::montgomery::MontgomeryContext;
+
+/// The previous implementation of `algorithms::modular_pow`.
+fn square_and_multiply(base: &BigInt, exponent: &BigInt, modulus: &BigInt) -> BigInt {
+    let mut exponent = exponent.clone();
+    let mut base = base % modulus;
+    let mut result = BigInt::one();
+
+    while exponent > 0.into() {
+        if &exponent % 2 == BigInt::one() {
+            result = (result * &base) % modulus;
+        }
+        exponent >>= 1;
+        base = (&base * &base) % modulus;
+    }
+
+    result
+}
+
+fn bench_modular_pow(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    let mut group = c.benchmark_group("modular_pow");
+    group.sample_size(10);
+
+    for bits in [2048u64, 3072, 4096] {
+        // Any odd modulus of the right size behaves like an RSA modulus here.
+        let mut modulus = rng.gen_bigint(bits);
+        modulus.set_bit(bits - 1, true);
+        modulus.set_bit(0, true);
+        let modulus = modulus.magnitude().clone().into();
+        let base = rng.gen_bigint_range(&BigInt::from(2), &modulus);
+        let exponent = rng.gen_bigint_range(&BigInt::from(2), &modulus);
+        let context = MontgomeryContext::new(&modulus).unwrap();
+
+        group.bench_with_input(
+            BenchmarkId::new("square_and_multiply", bits),
+            &bits,
+            |b, _| b.iter(|| square_and_multiply(&base, &exponent, &modulus)),
+        );
+        group.bench_with_input(BenchmarkId::new("modular_pow", bits), &bits, |b, _| {
+            b.iter(|| modular_pow(&base, &exponent, &modulus))
+        });
+        group.bench_with_input(BenchmarkId::new("context", bits), &bits, |b, _| {
+            b.iter(|| context.pow(&base, &exponent))
+        });
+        group.bench_with_input(BenchmarkId::new("constant_time", bits), &bits, |b, _| {
+            b.iter(|| context.pow_secret(&base, &exponent))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_modular_pow);
+criterion_main!(benches);
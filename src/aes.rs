@@ -0,0 +1,531 @@
+//! The AES block cipher (FIPS 197), the CBC mode of operation (NIST SP 800-38A)
+//! with the padding of RFC 8018, section 6.1.1, the CTR mode and the GCM
+//! authenticated mode (NIST SP 800-38D).
+//!
+//! The S-box is looked up by reading the whole table and the multiplications
+//! in GF(2^8) and GF(2^128) do not branch, so that the timing does not depend on
+//! the key or the data.
+
+/// Length of a block in bytes.
+pub const BLOCK_LEN: usize = 16;
+/// Length of a GCM nonce in bytes. Other lengths are not supported.
+pub const GCM_NONCE_LEN: usize = 12;
+/// Length of a GCM authentication tag in bytes.
+pub const GCM_TAG_LEN: usize = 16;
+
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+const INV_SBOX: [u8; 256] = [
+    0x52, 0x09, 0x6a, 0xd5, 0x30, 0x36, 0xa5, 0x38, 0xbf, 0x40, 0xa3, 0x9e, 0x81, 0xf3, 0xd7, 0xfb,
+    0x7c, 0xe3, 0x39, 0x82, 0x9b, 0x2f, 0xff, 0x87, 0x34, 0x8e, 0x43, 0x44, 0xc4, 0xde, 0xe9, 0xcb,
+    0x54, 0x7b, 0x94, 0x32, 0xa6, 0xc2, 0x23, 0x3d, 0xee, 0x4c, 0x95, 0x0b, 0x42, 0xfa, 0xc3, 0x4e,
+    0x08, 0x2e, 0xa1, 0x66, 0x28, 0xd9, 0x24, 0xb2, 0x76, 0x5b, 0xa2, 0x49, 0x6d, 0x8b, 0xd1, 0x25,
+    0x72, 0xf8, 0xf6, 0x64, 0x86, 0x68, 0x98, 0x16, 0xd4, 0xa4, 0x5c, 0xcc, 0x5d, 0x65, 0xb6, 0x92,
+    0x6c, 0x70, 0x48, 0x50, 0xfd, 0xed, 0xb9, 0xda, 0x5e, 0x15, 0x46, 0x57, 0xa7, 0x8d, 0x9d, 0x84,
+    0x90, 0xd8, 0xab, 0x00, 0x8c, 0xbc, 0xd3, 0x0a, 0xf7, 0xe4, 0x58, 0x05, 0xb8, 0xb3, 0x45, 0x06,
+    0xd0, 0x2c, 0x1e, 0x8f, 0xca, 0x3f, 0x0f, 0x02, 0xc1, 0xaf, 0xbd, 0x03, 0x01, 0x13, 0x8a, 0x6b,
+    0x3a, 0x91, 0x11, 0x41, 0x4f, 0x67, 0xdc, 0xea, 0x97, 0xf2, 0xcf, 0xce, 0xf0, 0xb4, 0xe6, 0x73,
+    0x96, 0xac, 0x74, 0x22, 0xe7, 0xad, 0x35, 0x85, 0xe2, 0xf9, 0x37, 0xe8, 0x1c, 0x75, 0xdf, 0x6e,
+    0x47, 0xf1, 0x1a, 0x71, 0x1d, 0x29, 0xc5, 0x89, 0x6f, 0xb7, 0x62, 0x0e, 0xaa, 0x18, 0xbe, 0x1b,
+    0xfc, 0x56, 0x3e, 0x4b, 0xc6, 0xd2, 0x79, 0x20, 0x9a, 0xdb, 0xc0, 0xfe, 0x78, 0xcd, 0x5a, 0xf4,
+    0x1f, 0xdd, 0xa8, 0x33, 0x88, 0x07, 0xc7, 0x31, 0xb1, 0x12, 0x10, 0x59, 0x27, 0x80, 0xec, 0x5f,
+    0x60, 0x51, 0x7f, 0xa9, 0x19, 0xb5, 0x4a, 0x0d, 0x2d, 0xe5, 0x7a, 0x9f, 0x93, 0xc9, 0x9c, 0xef,
+    0xa0, 0xe0, 0x3b, 0x4d, 0xae, 0x2a, 0xf5, 0xb0, 0xc8, 0xeb, 0xbb, 0x3c, 0x83, 0x53, 0x99, 0x61,
+    0x17, 0x2b, 0x04, 0x7e, 0xba, 0x77, 0xd6, 0x26, 0xe1, 0x69, 0x14, 0x63, 0x55, 0x21, 0x0c, 0x7d,
+];
+
+/// An expanded AES-128, AES-192 or AES-256 key.
+pub struct Aes {
+    round_keys: Vec<[u8; BLOCK_LEN]>,
+}
+
+impl Aes {
+    /// Expands a key of 16, 24 or 32 bytes. Returns `None` for other lengths.
+    pub fn new(key: &[u8]) -> Option<Aes> {
+        if !matches!(key.len(), 16 | 24 | 32) {
+            return None;
+        }
+        let key_words = key.len() / 4;
+        let rounds = key_words + 6;
+
+        let mut words: Vec<[u8; 4]> = key
+            .chunks_exact(4)
+            .map(|word| word.try_into().unwrap())
+            .collect();
+        let mut rcon = 1u8;
+        for i in key_words..4 * (rounds + 1) {
+            let mut temp = words[i - 1];
+            if i % key_words == 0 {
+                temp.rotate_left(1);
+                temp = temp.map(|byte| lookup(&SBOX, byte));
+                temp[0] ^= rcon;
+                rcon = xtime(rcon);
+            } else if key_words > 6 && i % key_words == 4 {
+                temp = temp.map(|byte| lookup(&SBOX, byte));
+            }
+            let previous = words[i - key_words];
+            words.push(core::array::from_fn(|j| previous[j] ^ temp[j]));
+        }
+
+        let round_keys = words
+            .chunks_exact(4)
+            .map(|round| core::array::from_fn(|i| round[i / 4][i % 4]))
+            .collect();
+        Some(Aes { round_keys })
+    }
+
+    /// Encrypts a single block in place.
+    pub fn encrypt_block(&self, block: &mut [u8; BLOCK_LEN]) {
+        let rounds = self.round_keys.len() - 1;
+        add_round_key(block, &self.round_keys[0]);
+        for round_key in &self.round_keys[1..rounds] {
+            sub_bytes(block, &SBOX);
+            shift_rows(block);
+            mix_columns(block);
+            add_round_key(block, round_key);
+        }
+        sub_bytes(block, &SBOX);
+        shift_rows(block);
+        add_round_key(block, &self.round_keys[rounds]);
+    }
+
+    /// Decrypts a single block in place.
+    pub fn decrypt_block(&self, block: &mut [u8; BLOCK_LEN]) {
+        let rounds = self.round_keys.len() - 1;
+        add_round_key(block, &self.round_keys[rounds]);
+        for round_key in self.round_keys[1..rounds].iter().rev() {
+            inv_shift_rows(block);
+            sub_bytes(block, &INV_SBOX);
+            add_round_key(block, round_key);
+            inv_mix_columns(block);
+        }
+        inv_shift_rows(block);
+        sub_bytes(block, &INV_SBOX);
+        add_round_key(block, &self.round_keys[0]);
+    }
+}
+
+/// Returns the table entry at the index. Every entry is read, so that the
+/// memory access pattern does not depend on the index.
+fn lookup(table: &[u8; 256], index: u8) -> u8 {
+    let mut result = 0;
+    for (i, value) in table.iter().enumerate() {
+        // 0xff if i == index, otherwise 0.
+        let mask = ((((i as u8) ^ index) as u32).wrapping_sub(1) >> 8) as u8;
+        result |= value & mask;
+    }
+    result
+}
+
+/// Multiplies by x in GF(2^8).
+fn xtime(byte: u8) -> u8 {
+    (byte << 1) ^ (0x1b & 0u8.wrapping_sub(byte >> 7))
+}
+
+/// Multiplies two elements of GF(2^8). The second one is a public constant.
+fn multiply(mut a: u8, mut b: u8) -> u8 {
+    let mut result = 0;
+    while b != 0 {
+        result ^= a & 0u8.wrapping_sub(b & 1);
+        a = xtime(a);
+        b >>= 1;
+    }
+    result
+}
+
+fn add_round_key(block: &mut [u8; BLOCK_LEN], round_key: &[u8; BLOCK_LEN]) {
+    for (byte, key_byte) in block.iter_mut().zip(round_key) {
+        *byte ^= key_byte;
+    }
+}
+
+fn sub_bytes(block: &mut [u8; BLOCK_LEN], table: &[u8; 256]) {
+    for byte in block.iter_mut() {
+        *byte = lookup(table, *byte);
+    }
+}
+
+// The block is stored column by column, so row r of column c is at 4 * c + r.
+fn shift_rows(block: &mut [u8; BLOCK_LEN]) {
+    let state = *block;
+    for (i, byte) in block.iter_mut().enumerate() {
+        let (column, row) = (i / 4, i % 4);
+        *byte = state[4 * ((column + row) % 4) + row];
+    }
+}
+
+fn inv_shift_rows(block: &mut [u8; BLOCK_LEN]) {
+    let state = *block;
+    for (i, byte) in block.iter_mut().enumerate() {
+        let (column, row) = (i / 4, i % 4);
+        *byte = state[4 * ((column + 4 - row) % 4) + row];
+    }
+}
+
+fn mix_columns(block: &mut [u8; BLOCK_LEN]) {
+    for column in block.chunks_exact_mut(4) {
+        let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+        let all = a0 ^ a1 ^ a2 ^ a3;
+        column[0] ^= all ^ xtime(a0 ^ a1);
+        column[1] ^= all ^ xtime(a1 ^ a2);
+        column[2] ^= all ^ xtime(a2 ^ a3);
+        column[3] ^= all ^ xtime(a3 ^ a0);
+    }
+}
+
+fn inv_mix_columns(block: &mut [u8; BLOCK_LEN]) {
+    for column in block.chunks_exact_mut(4) {
+        let a = [column[0], column[1], column[2], column[3]];
+        for (row, byte) in column.iter_mut().enumerate() {
+            *byte = multiply(a[row], 14)
+                ^ multiply(a[(row + 1) % 4], 11)
+                ^ multiply(a[(row + 2) % 4], 13)
+                ^ multiply(a[(row + 3) % 4], 9);
+        }
+    }
+}
+
+/// Pads the data to a multiple of the block length and encrypts it in CBC mode.
+pub fn cbc_encrypt(cipher: &Aes, iv: &[u8; BLOCK_LEN], data: &[u8]) -> Vec<u8> {
+    let padding = BLOCK_LEN - data.len() % BLOCK_LEN;
+    let mut padded = data.to_vec();
+    padded.resize(data.len() + padding, padding as u8);
+
+    let mut previous = *iv;
+    for chunk in padded.chunks_exact_mut(BLOCK_LEN) {
+        let mut block: [u8; BLOCK_LEN] = core::array::from_fn(|i| chunk[i] ^ previous[i]);
+        cipher.encrypt_block(&mut block);
+        chunk.copy_from_slice(&block);
+        previous = block;
+    }
+    padded
+}
+
+/// Decrypts data encrypted by `cbc_encrypt`. Returns `None` if the length is not
+/// a multiple of the block length or the padding is invalid.
+pub fn cbc_decrypt(cipher: &Aes, iv: &[u8; BLOCK_LEN], data: &[u8]) -> Option<Vec<u8>> {
+    if data.is_empty() || !data.len().is_multiple_of(BLOCK_LEN) {
+        return None;
+    }
+
+    let mut result = Vec::with_capacity(data.len());
+    let mut previous = *iv;
+    for chunk in data.chunks_exact(BLOCK_LEN) {
+        let mut block: [u8; BLOCK_LEN] = chunk.try_into().unwrap();
+        cipher.decrypt_block(&mut block);
+        result.extend(block.iter().zip(previous).map(|(byte, prev)| byte ^ prev));
+        previous = chunk.try_into().unwrap();
+    }
+
+    let padding = *result.last().unwrap() as usize;
+    if padding == 0 || padding > BLOCK_LEN {
+        return None;
+    }
+    let start = result.len() - padding;
+    if result[start..].iter().any(|&byte| byte as usize != padding) {
+        return None;
+    }
+    result.truncate(start);
+    Some(result)
+}
+
+/// Encrypts or decrypts the data in place in CTR mode. The whole initial
+/// counter block is incremented as a big-endian number, as OpenSSH does.
+pub fn ctr_apply(cipher: &Aes, iv: &[u8; BLOCK_LEN], data: &mut [u8]) {
+    let mut counter = u128::from_be_bytes(*iv);
+    for chunk in data.chunks_mut(BLOCK_LEN) {
+        let mut keystream = counter.to_be_bytes();
+        cipher.encrypt_block(&mut keystream);
+        for (byte, key_byte) in chunk.iter_mut().zip(keystream) {
+            *byte ^= key_byte;
+        }
+        counter = counter.wrapping_add(1);
+    }
+}
+
+/// Multiplies two elements of GF(2^128) with the bit order of GCM, where the
+/// most significant bit of the number is the coefficient of x^0.
+fn gf_multiply(x: u128, y: u128) -> u128 {
+    const R: u128 = 0xe1 << 120;
+    let mut result = 0;
+    let mut v = y;
+    for i in (0..128).rev() {
+        result ^= v & 0u128.wrapping_sub((x >> i) & 1);
+        v = (v >> 1) ^ (R & 0u128.wrapping_sub(v & 1));
+    }
+    result
+}
+
+/// The GHASH function over the additional data and the ciphertext, each padded
+/// to whole blocks, and their lengths in bits.
+fn ghash(h: u128, aad: &[u8], ciphertext: &[u8]) -> u128 {
+    let mut y = 0;
+    for data in [aad, ciphertext] {
+        for chunk in data.chunks(BLOCK_LEN) {
+            let mut block = [0u8; BLOCK_LEN];
+            block[..chunk.len()].copy_from_slice(chunk);
+            y = gf_multiply(y ^ u128::from_be_bytes(block), h);
+        }
+    }
+    let lengths = ((aad.len() as u128 * 8) << 64) | (ciphertext.len() as u128 * 8);
+    gf_multiply(y ^ lengths, h)
+}
+
+/// Encrypts or decrypts the data in place with the counter blocks following the
+/// initial block, where only the last 32 bits are incremented.
+fn gcm_ctr(cipher: &Aes, initial: u128, data: &mut [u8]) {
+    for (i, chunk) in data.chunks_mut(BLOCK_LEN).enumerate() {
+        let counter = (initial as u32).wrapping_add(i as u32 + 1);
+        let mut keystream = ((initial & !0xffff_ffff) | counter as u128).to_be_bytes();
+        cipher.encrypt_block(&mut keystream);
+        for (byte, key_byte) in chunk.iter_mut().zip(keystream) {
+            *byte ^= key_byte;
+        }
+    }
+}
+
+fn gcm_tag(cipher: &Aes, initial: u128, aad: &[u8], ciphertext: &[u8]) -> [u8; GCM_TAG_LEN] {
+    let mut h = [0u8; BLOCK_LEN];
+    cipher.encrypt_block(&mut h);
+    let mut tag = initial.to_be_bytes();
+    cipher.encrypt_block(&mut tag);
+    (u128::from_be_bytes(tag) ^ ghash(u128::from_be_bytes(h), aad, ciphertext)).to_be_bytes()
+}
+
+fn gcm_initial_block(nonce: &[u8; GCM_NONCE_LEN]) -> u128 {
+    let mut block = [0u8; BLOCK_LEN];
+    block[..GCM_NONCE_LEN].copy_from_slice(nonce);
+    block[BLOCK_LEN - 1] = 1;
+    u128::from_be_bytes(block)
+}
+
+/// Encrypts the data in GCM mode and returns the ciphertext followed by the tag,
+/// which also authenticates the additional data.
+pub fn gcm_encrypt(cipher: &Aes, nonce: &[u8; GCM_NONCE_LEN], aad: &[u8], data: &[u8]) -> Vec<u8> {
+    let initial = gcm_initial_block(nonce);
+    let mut result = data.to_vec();
+    gcm_ctr(cipher, initial, &mut result);
+    let tag = gcm_tag(cipher, initial, aad, &result);
+    result.extend_from_slice(&tag);
+    result
+}
+
+/// Decrypts data encrypted by `gcm_encrypt`. Returns `None` if the tag does not
+/// match, that is if the key is wrong or the data was modified.
+pub fn gcm_decrypt(
+    cipher: &Aes,
+    nonce: &[u8; GCM_NONCE_LEN],
+    aad: &[u8],
+    data: &[u8],
+) -> Option<Vec<u8>> {
+    if data.len() < GCM_TAG_LEN {
+        return None;
+    }
+    let (ciphertext, tag) = data.split_at(data.len() - GCM_TAG_LEN);
+    let initial = gcm_initial_block(nonce);
+    let expected = gcm_tag(cipher, initial, aad, ciphertext);
+    // The tag is compared without an early exit.
+    if expected
+        .iter()
+        .zip(tag)
+        .fold(0, |acc, (a, b)| acc | (a ^ b))
+        != 0
+    {
+        return None;
+    }
+    let mut result = ciphertext.to_vec();
+    gcm_ctr(cipher, initial, &mut result);
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        let hex: String = hex.split_whitespace().collect();
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // test vectors from FIPS 197, appendix C
+    #[test]
+    fn aes_encrypts_and_decrypts_block() {
+        let plaintext: [u8; 16] = from_hex("00112233445566778899aabbccddeeff")
+            .try_into()
+            .unwrap();
+        let vectors = [
+            (16, "69c4e0d86a7b0430d8cdb78070b4c55a"),
+            (24, "dda97ca4864cdfe06eaf70a0ec0d7191"),
+            (32, "8ea2b7ca516745bfeafc49904b496089"),
+        ];
+
+        for (key_len, expected) in vectors {
+            let key: Vec<u8> = (0..key_len as u8).collect();
+            let cipher = Aes::new(&key).unwrap();
+            let mut block = plaintext;
+
+            cipher.encrypt_block(&mut block);
+            assert_eq!(from_hex(expected), block);
+            cipher.decrypt_block(&mut block);
+            assert_eq!(plaintext, block);
+        }
+    }
+
+    #[test]
+    fn aes_rejects_invalid_key_length() {
+        assert!(Aes::new(&[0u8; 20]).is_none());
+    }
+
+    // test vector from NIST SP 800-38A, F.2.5
+    #[test]
+    fn cbc_encrypt_returns_correct_ciphertext() {
+        let key = from_hex("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4");
+        let iv: [u8; 16] = core::array::from_fn(|i| i as u8);
+        let plaintext = from_hex(
+            "6bc1bee22e409f96e93d7e117393172a ae2d8a571e03ac9c9eb76fac45af8e51
+             30c81c46a35ce411e5fbc1191a0a52ef f69f2445df4f9b17ad2b417be66c3710",
+        );
+        let cipher = Aes::new(&key).unwrap();
+
+        let ciphertext = cbc_encrypt(&cipher, &iv, &plaintext);
+
+        assert_eq!(
+            from_hex(
+                "f58c4c04d6e5f1ba779eabfb5f7bfbd6 9cfc4e967edb808d679f777bc6702c7d
+                 39f23369a9d9bacfa530e26304231461 b2eb05e2c39be9fcda6c19078c6a9d1b"
+            ),
+            ciphertext[..64]
+        );
+        // A full block of padding is added to input that is a multiple of the block length.
+        assert_eq!(80, ciphertext.len());
+        assert_eq!(Some(plaintext), cbc_decrypt(&cipher, &iv, &ciphertext));
+    }
+
+    #[test]
+    fn cbc_decrypt_rejects_invalid_padding() {
+        let cipher = Aes::new(&[7u8; 32]).unwrap();
+        let iv = [1u8; 16];
+        let mut ciphertext = cbc_encrypt(&cipher, &iv, b"FooBarBaz");
+
+        assert_eq!(
+            Some(b"FooBarBaz".to_vec()),
+            cbc_decrypt(&cipher, &iv, &ciphertext)
+        );
+        assert_eq!(None, cbc_decrypt(&cipher, &iv, &ciphertext[..15]));
+
+        let last = ciphertext.len() - 1;
+        ciphertext[last] ^= 1;
+        assert_eq!(None, cbc_decrypt(&cipher, &iv, &ciphertext));
+    }
+
+    // test vector from NIST SP 800-38A, F.5.5
+    #[test]
+    fn ctr_apply_returns_correct_ciphertext() {
+        let key = from_hex("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4");
+        let iv: [u8; 16] = from_hex("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff")
+            .try_into()
+            .unwrap();
+        let plaintext =
+            from_hex("6bc1bee22e409f96e93d7e117393172a ae2d8a571e03ac9c9eb76fac45af8e51");
+        let cipher = Aes::new(&key).unwrap();
+
+        let mut data = plaintext.clone();
+        ctr_apply(&cipher, &iv, &mut data);
+        assert_eq!(
+            from_hex("601ec313775789a5b7a7f504bbf3d228 f443e3ca4d62b59aca84e990cacaf5c5"),
+            data
+        );
+        ctr_apply(&cipher, &iv, &mut data);
+        assert_eq!(plaintext, data);
+    }
+
+    #[test]
+    fn ctr_apply_carries_into_whole_counter() {
+        let key = from_hex("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4");
+        let cipher = Aes::new(&key).unwrap();
+        let mut data = [0u8; 32];
+
+        ctr_apply(&cipher, &[0xff; 16], &mut data);
+
+        let mut expected = [0u8; 16];
+        cipher.encrypt_block(&mut expected);
+        assert_eq!(expected, data[16..]);
+    }
+
+    // test cases 1, 2 and 16 from the GCM specification of McGrew and Viega
+    #[test]
+    fn gcm_encrypt_returns_correct_ciphertext() {
+        let zero = Aes::new(&[0u8; 16]).unwrap();
+        assert_eq!(
+            from_hex("58e2fccefa7e3061367f1d57a4e7455a"),
+            gcm_encrypt(&zero, &[0; 12], b"", b"")
+        );
+        assert_eq!(
+            from_hex("0388dace60b6a392f328c2b971b2fe78 ab6e47d42cec13bdf53a67b21257bddf"),
+            gcm_encrypt(&zero, &[0; 12], b"", &[0; 16])
+        );
+
+        let key = from_hex("feffe9928665731c6d6a8f9467308308 feffe9928665731c6d6a8f9467308308");
+        let nonce: [u8; 12] = from_hex("cafebabefacedbaddecaf888").try_into().unwrap();
+        let aad = from_hex("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+        let plaintext = from_hex(
+            "d9313225f88406e5a55909c5aff5269a 86a7a9531534f7da2e4c303d8a318a72
+             1c3c0c95956809532fcf0e2449a6b525 b16aedf5aa0de657ba637b39",
+        );
+        let cipher = Aes::new(&key).unwrap();
+
+        let ciphertext = gcm_encrypt(&cipher, &nonce, &aad, &plaintext);
+
+        assert_eq!(
+            from_hex(
+                "522dc1f099567d07f47f37a32a84427d 643a8cdcbfe5c0c97598a2bd2555d1aa
+                 8cb08e48590dbb3da7b08b1056828838 c5f61e6393ba7a0abcc9f662
+                 76fc6ece0f4e1768cddf8853bb2d551b"
+            ),
+            ciphertext
+        );
+        assert_eq!(
+            Some(plaintext),
+            gcm_decrypt(&cipher, &nonce, &aad, &ciphertext)
+        );
+    }
+
+    #[test]
+    fn gcm_decrypt_rejects_modified_data() {
+        let cipher = Aes::new(&[7u8; 32]).unwrap();
+        let nonce = [1u8; 12];
+        let ciphertext = gcm_encrypt(&cipher, &nonce, b"aad", b"FooBarBaz");
+
+        for i in 0..ciphertext.len() {
+            let mut modified = ciphertext.clone();
+            modified[i] ^= 1;
+            assert_eq!(None, gcm_decrypt(&cipher, &nonce, b"aad", &modified));
+        }
+        assert_eq!(None, gcm_decrypt(&cipher, &nonce, b"aab", &ciphertext));
+        assert_eq!(None, gcm_decrypt(&cipher, &nonce, b"aad", &ciphertext[1..]));
+        assert_eq!(
+            None,
+            gcm_decrypt(&cipher, &nonce, b"aad", &ciphertext[..15])
+        );
+    }
+}
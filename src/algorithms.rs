@@ -1,4 +1,6 @@
 use num_bigint::{BigInt, RandBigInt};
+
+use crate::montgomery::MontgomeryContext;
 #[allow(unused_imports)]
 use num_traits::identities::{One, Zero};
 
@@ -7,6 +9,13 @@ pub fn miller_rabin(n: &BigInt, k: isize) -> bool {
     if *n <= BigInt::from(2) {
         return false;
     }
+    // The witnesses are drawn from [2, n - 2), which is empty for 3 and 4.
+    if *n == BigInt::from(3) {
+        return true;
+    }
+    if n % 2 == BigInt::zero() {
+        return false;
+    }
 
     // Find s & d so that 2^s * d = n - 1
     let mut s: BigInt = BigInt::zero();
@@ -16,14 +25,18 @@ pub fn miller_rabin(n: &BigInt, k: isize) -> bool {
         s += 1;
     }
 
+    let context = MontgomeryContext::new(n);
     for _ in 0..k {
         let a = rand::thread_rng().gen_bigint_range(&BigInt::from(2), &(n - 2));
-        let mut x = modular_pow(&a, &d, n);
+        let mut x = match &context {
+            Some(context) => context.pow(&a, &d),
+            None => modular_pow(&a, &d, n),
+        };
         let mut y = BigInt::zero();
 
         let mut m: BigInt = s.clone();
         while m > BigInt::zero() {
-            y = modular_pow(&x, &BigInt::from(2), n);
+            y = (&x * &x) % n;
             if y == BigInt::one() && x != BigInt::one() && x != n - 1 {
                 return false;
             }
@@ -40,8 +53,13 @@ pub fn miller_rabin(n: &BigInt, k: isize) -> bool {
     true
 }
 
-/// Returns result for base^exponent % modulus
+/// Returns result for base^exponent % modulus. Odd moduli use Montgomery
+/// multiplication; create a `MontgomeryContext` instead when the same modulus
+/// is used many times.
 pub fn modular_pow(base: &BigInt, exponent: &BigInt, modulus: &BigInt) -> BigInt {
+    if let Some(context) = MontgomeryContext::new(modulus) {
+        return context.pow(base, exponent);
+    }
     if *modulus == BigInt::one() {
         return BigInt::zero();
     }
@@ -87,6 +105,62 @@ pub fn least_common_multiple(a: &BigInt, b: &BigInt) -> BigInt {
     a * b / qcd
 }
 
+/// Recovers the prime factors of the modulus from the public and private exponents,
+/// using the probabilistic method from NIST SP 800-56B, appendix C.
+pub fn recover_primes(n: &BigInt, e: &BigInt, d: &BigInt) -> Option<(BigInt, BigInt)> {
+    // k = d * e - 1 is a multiple of lambda(n), write it as 2^t * r with r odd.
+    let k: BigInt = d * e - 1;
+    if k <= BigInt::zero() || n <= &BigInt::from(3) {
+        return None;
+    }
+    let mut r = k.clone();
+    let mut t = 0;
+    while &r % 2 == BigInt::zero() {
+        r /= 2;
+        t += 1;
+    }
+
+    for _ in 0..100 {
+        let g = rand::thread_rng().gen_bigint_range(&BigInt::from(2), &(n - 1));
+        let mut y = modular_pow(&g, &r, n);
+        if y == BigInt::one() || y == n - 1 {
+            continue;
+        }
+
+        for _ in 0..t {
+            let x = modular_pow(&y, &BigInt::from(2), n);
+            if x == BigInt::one() {
+                // y is a nontrivial square root of 1, so gcd(y - 1, n) is a factor.
+                let (p, _, _) = extended_eucledian(&(&y - 1), n);
+                let q = n / &p;
+                return Some((p, q));
+            }
+            if x == n - 1 {
+                break;
+            }
+            y = x;
+        }
+    }
+
+    None
+}
+
+/// Returns the primes below the limit with the sieve of Eratosthenes.
+pub fn small_primes(limit: u32) -> Vec<u32> {
+    let limit = limit as usize;
+    let mut is_prime = vec![true; limit];
+    let mut primes = Vec::new();
+    for i in 2..limit {
+        if is_prime[i] {
+            primes.push(i as u32);
+            for multiple in (i * i..limit).step_by(i) {
+                is_prime[multiple] = false;
+            }
+        }
+    }
+    primes
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -103,6 +177,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn miller_rabin_handles_small_numbers() {
+        assert!(miller_rabin(&BigInt::from(3), 4));
+        assert!(!miller_rabin(&BigInt::from(4), 4));
+        assert!(miller_rabin(&BigInt::from(5), 4));
+    }
+
     #[test]
     fn miller_rabin_returns_true_for_prime() {
         let prime = BigInt::from(7919); // 1000th prime
@@ -143,6 +224,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn small_primes_returns_primes_below_limit() {
+        assert_eq!(vec![2, 3, 5, 7, 11, 13, 17, 19], small_primes(20));
+        assert_eq!(1000, small_primes(7920).len());
+        assert_eq!(Some(&7919), small_primes(7920).last());
+    }
+
     #[test]
     fn modular_pow_returns_correct_remainder() {
         let result = modular_pow(&BigInt::from(3), &BigInt::from(2), &BigInt::from(5));
@@ -200,6 +288,24 @@ mod tests {
         ).unwrap()), result);
     }
 
+    #[test]
+    fn recover_primes_returns_factors() {
+        // example from https://en.wikipedia.org/wiki/RSA_(cryptosystem)
+        let (p, q) =
+            recover_primes(&BigInt::from(3233), &BigInt::from(17), &BigInt::from(413)).unwrap();
+
+        assert_eq!(BigInt::from(3233), &p * &q);
+        assert!(p == BigInt::from(61) || p == BigInt::from(53));
+    }
+
+    #[test]
+    fn recover_primes_returns_none_for_wrong_exponent() {
+        assert_eq!(
+            None,
+            recover_primes(&BigInt::from(3233), &BigInt::from(17), &BigInt::from(414))
+        );
+    }
+
     #[test]
     fn least_common_multiple_returns_correctly() {
         let result = least_common_multiple(&BigInt::from(4), &BigInt::from(6));
@@ -0,0 +1,284 @@
+//! ASCII armor for encrypted messages and signatures, in the same format as PEM:
+//! the data is encoded as base64 in lines of 64 characters between
+//! `-----BEGIN <label>-----` and `-----END <label>-----` lines.
+//!
+//! Unlike `pem`, the armor is written and read as a stream, so that large
+//! encrypted files do not have to fit into memory.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use crate::encoding::{base64_decode, base64_encode};
+use crate::keys::read_block;
+use crate::pem::{self, PemError};
+
+/// Label of armored encrypted messages.
+pub const MESSAGE_LABEL: &str = "RSA MESSAGE";
+/// Label of armored signatures.
+pub const SIGNATURE_LABEL: &str = "RSA SIGNATURE";
+
+/// Number of bytes encoded on one line of 64 base64 characters.
+const LINE_BYTES: usize = 48;
+/// How much of the input is examined for a BEGIN line.
+const DETECT_LEN: usize = 64;
+
+fn invalid_data(err: PemError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// A writer that armors everything written to it. `finish` must be called
+/// after the last write to write the END line.
+pub struct ArmorWriter<W: Write> {
+    inner: W,
+    label: String,
+    line: Vec<u8>,
+}
+
+impl<W: Write> ArmorWriter<W> {
+    /// Writes the BEGIN line to the inner writer.
+    pub fn new(mut inner: W, label: &str) -> io::Result<ArmorWriter<W>> {
+        writeln!(inner, "-----BEGIN {}-----", label)?;
+        Ok(ArmorWriter {
+            inner,
+            label: label.to_string(),
+            line: Vec::with_capacity(LINE_BYTES),
+        })
+    }
+
+    /// Writes the last line and the END line, and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.line.is_empty() {
+            writeln!(self.inner, "{}", base64_encode(&self.line))?;
+        }
+        writeln!(self.inner, "-----END {}-----", self.label)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ArmorWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let take = buf.len().min(LINE_BYTES - self.line.len());
+        self.line.extend_from_slice(&buf[..take]);
+        if self.line.len() == LINE_BYTES {
+            writeln!(self.inner, "{}", base64_encode(&self.line))?;
+            self.line.clear();
+        }
+        Ok(take)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A reader that removes the armor from the data read from the inner reader.
+/// Reading fails with `io::ErrorKind::InvalidData` and a `PemError` if the
+/// armor is invalid, has another label or ends without an END line.
+pub struct ArmorReader<R: BufRead> {
+    inner: R,
+    label: String,
+    began: bool,
+    ended: bool,
+    /// Base64 characters that do not yet form a whole group of four.
+    pending: String,
+    /// Decoded bytes that have not been read yet.
+    decoded: Vec<u8>,
+    position: usize,
+}
+
+impl<R: BufRead> ArmorReader<R> {
+    pub fn new(inner: R, label: &str) -> ArmorReader<R> {
+        ArmorReader {
+            inner,
+            label: label.to_string(),
+            began: false,
+            ended: false,
+            pending: String::new(),
+            decoded: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Reads lines until some data has been decoded or the END line is reached.
+    fn read_line(&mut self) -> io::Result<()> {
+        let mut line = String::new();
+        if self.inner.read_line(&mut line)? == 0 {
+            let err = match self.began {
+                true => PemError::MissingEnd,
+                false => PemError::MissingBegin,
+            };
+            return Err(invalid_data(err));
+        }
+        let line = line.trim();
+
+        if !self.began {
+            if line == format!("-----BEGIN {}-----", self.label) {
+                self.began = true;
+            } else if !line.is_empty() {
+                return Err(invalid_data(PemError::MissingBegin));
+            }
+            return Ok(());
+        }
+
+        if line.starts_with("-----END ") {
+            if line != format!("-----END {}-----", self.label) {
+                return Err(invalid_data(PemError::MissingEnd));
+            }
+            self.ended = true;
+            self.decode(self.pending.len())?;
+            return Ok(());
+        }
+
+        self.pending.push_str(line);
+        let whole_groups = self.pending.len() / 4 * 4;
+        self.decode(whole_groups)
+    }
+
+    /// Decodes the first characters of the pending base64.
+    fn decode(&mut self, len: usize) -> io::Result<()> {
+        let rest = self.pending.split_off(len);
+        self.decoded =
+            base64_decode(&self.pending).ok_or_else(|| invalid_data(PemError::InvalidBase64))?;
+        self.position = 0;
+        self.pending = rest;
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for ArmorReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.decoded.len() {
+            if self.ended {
+                return Ok(0);
+            }
+            self.read_line()?;
+        }
+
+        let take = buf.len().min(self.decoded.len() - self.position);
+        buf[..take].copy_from_slice(&self.decoded[self.position..self.position + take]);
+        self.position += take;
+        Ok(take)
+    }
+}
+
+/// Returns a reader of the data in the input, which is dearmored if the input
+/// starts with a BEGIN line and returned as it is otherwise.
+pub fn detect<'a>(input: Box<dyn Read + 'a>, label: &str) -> io::Result<Box<dyn Read + 'a>> {
+    read_armored(input, label, false)
+}
+
+/// Like `detect`, but fails with `PemError::MissingBegin` if the input is not armored.
+pub fn require<'a>(input: Box<dyn Read + 'a>, label: &str) -> io::Result<Box<dyn Read + 'a>> {
+    read_armored(input, label, true)
+}
+
+fn read_armored<'a>(
+    mut input: Box<dyn Read + 'a>,
+    label: &str,
+    required: bool,
+) -> io::Result<Box<dyn Read + 'a>> {
+    let mut start = vec![0u8; DETECT_LEN];
+    let len = read_block(&mut input, &mut start)?;
+    start.truncate(len);
+
+    let armored = pem::is_pem(&start);
+    if required && !armored {
+        return Err(invalid_data(PemError::MissingBegin));
+    }
+    let input = io::Cursor::new(start).chain(input);
+    Ok(match armored {
+        true => Box::new(ArmorReader::new(BufReader::new(input), label)),
+        false => Box::new(input),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn armor(data: &[u8]) -> Vec<u8> {
+        let mut writer = ArmorWriter::new(Vec::new(), MESSAGE_LABEL).unwrap();
+        // Write in pieces that do not line up with the lines.
+        for chunk in data.chunks(7) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    fn read_detected(data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut result = Vec::new();
+        detect(Box::new(data), MESSAGE_LABEL)?.read_to_end(&mut result)?;
+        Ok(result)
+    }
+
+    #[test]
+    fn armor_writer_matches_pem() {
+        for len in [0, 1, 47, 48, 49, 200] {
+            let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+
+            assert_eq!(pem::encode(MESSAGE_LABEL, &data).into_bytes(), armor(&data));
+        }
+    }
+
+    #[test]
+    fn detect_dearmors_armored_input() {
+        let data: Vec<u8> = (0..1000).map(|i| (i * 7) as u8).collect();
+
+        assert_eq!(data, read_detected(&armor(&data)).unwrap());
+    }
+
+    #[test]
+    fn detect_accepts_other_line_lengths() {
+        let data: Vec<u8> = (0..100).collect();
+        let encoded = base64_encode(&data);
+        let lines: Vec<&str> = encoded
+            .as_bytes()
+            .chunks(30)
+            .map(|line| std::str::from_utf8(line).unwrap())
+            .collect();
+        let text = format!(
+            "\n-----BEGIN RSA MESSAGE-----\n{}\n-----END RSA MESSAGE-----\n",
+            lines.join("\r\n")
+        );
+
+        assert_eq!(data, read_detected(text.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn detect_passes_binary_input_through() {
+        let data: Vec<u8> = (0..=255).collect();
+
+        assert_eq!(data, read_detected(&data).unwrap());
+        assert_eq!(b"foo".to_vec(), read_detected(b"foo").unwrap());
+    }
+
+    #[test]
+    fn require_rejects_binary_input() {
+        let mut result = Vec::new();
+        require(Box::new(&armor(b"FooBarBaz")[..]), MESSAGE_LABEL)
+            .unwrap()
+            .read_to_end(&mut result)
+            .unwrap();
+        assert_eq!(b"FooBarBaz".to_vec(), result);
+
+        let err = require(Box::new(&b"FooBarBaz"[..]), MESSAGE_LABEL)
+            .err()
+            .unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+
+    #[test]
+    fn armor_reader_rejects_invalid_armor() {
+        let armored = String::from_utf8(armor(b"FooBarBaz")).unwrap();
+
+        for text in [
+            armored.replace("RSA MESSAGE", "RSA SIGNATURE"),
+            armored.replace("-----END RSA MESSAGE-----\n", ""),
+            armored.replace("Rm9v", "Rm9!"),
+        ] {
+            let err = read_detected(text.as_bytes()).unwrap_err();
+
+            assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        }
+    }
+}
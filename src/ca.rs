@@ -0,0 +1,195 @@
+//! The state of a local certificate authority: the serial number of the next
+//! certificate and the certificates issued so far, kept in a JSON file like
+//!
+//! ```text
+//! {"next_serial":"5f3a...","issued":[{"serial":"5f39...","subject":"CN=example.com","not_after":1767225600}]}
+//! ```
+//!
+//! Serial numbers are hexadecimal, `not_after` is in seconds since the Unix epoch.
+
+use std::io;
+use std::path::Path;
+
+use num_bigint::{BigInt, Sign};
+
+use crate::error::{self, Error};
+use crate::json::{self, Value};
+use crate::keys;
+use crate::x509::{self, Certificate};
+
+/// A certificate recorded in the state file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IssuedCertificate {
+    pub serial: BigInt,
+    pub subject: String,
+    pub not_after: i64,
+}
+
+/// The serial number of the next certificate and the certificates issued so far.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaState {
+    next_serial: BigInt,
+    issued: Vec<IssuedCertificate>,
+}
+
+fn invalid_state() -> Error {
+    Error::Parse("Invalid CA state file".to_string())
+}
+
+fn parse_serial(value: Option<&Value>) -> error::Result<BigInt> {
+    value
+        .and_then(Value::as_str)
+        .and_then(|hex| BigInt::parse_bytes(hex.as_bytes(), 16))
+        .filter(|serial| serial.sign() == Sign::Plus)
+        .ok_or_else(invalid_state)
+}
+
+impl CaState {
+    /// A new CA, whose serial numbers start at a random value, so that they do
+    /// not repeat those of an earlier CA with the same name.
+    pub fn new() -> CaState {
+        CaState {
+            next_serial: x509::random_serial(),
+            issued: Vec::new(),
+        }
+    }
+
+    /// Reads the state from the file, or returns a new state if the file does
+    /// not exist.
+    pub fn load(path: &Path) -> error::Result<CaState> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(CaState::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let state = json::parse(&text)?;
+        let issued = state
+            .get("issued")
+            .and_then(Value::as_array)
+            .ok_or_else(invalid_state)?
+            .iter()
+            .map(|issued| {
+                let not_after = match issued.get("not_after") {
+                    Some(Value::Number(number)) => number.parse().map_err(|_| invalid_state())?,
+                    _ => return Err(invalid_state()),
+                };
+                Ok(IssuedCertificate {
+                    serial: parse_serial(issued.get("serial"))?,
+                    subject: issued
+                        .get("subject")
+                        .and_then(Value::as_str)
+                        .ok_or_else(invalid_state)?
+                        .to_string(),
+                    not_after,
+                })
+            })
+            .collect::<error::Result<Vec<IssuedCertificate>>>()?;
+        Ok(CaState {
+            next_serial: parse_serial(state.get("next_serial"))?,
+            issued,
+        })
+    }
+
+    pub fn next_serial(&self) -> &BigInt {
+        &self.next_serial
+    }
+
+    pub fn issued(&self) -> &[IssuedCertificate] {
+        &self.issued
+    }
+
+    /// Records an issued certificate and moves the next serial number past it.
+    pub fn record(&mut self, certificate: &Certificate) {
+        if *certificate.serial() >= self.next_serial {
+            self.next_serial = certificate.serial() + 1;
+        }
+        self.issued.push(IssuedCertificate {
+            serial: certificate.serial().clone(),
+            subject: certificate.subject().to_string(),
+            not_after: certificate.validity().not_after,
+        });
+    }
+
+    /// Writes the state to the file, which is replaced atomically.
+    pub fn save(&self, path: &Path) -> error::Result<()> {
+        let issued = self
+            .issued
+            .iter()
+            .map(|issued| {
+                Value::object(vec![
+                    ("serial", Value::String(issued.serial.to_str_radix(16))),
+                    ("subject", Value::string(&issued.subject)),
+                    ("not_after", Value::Number(issued.not_after.to_string())),
+                ])
+            })
+            .collect();
+        let state = Value::object(vec![
+            (
+                "next_serial",
+                Value::String(self.next_serial.to_str_radix(16)),
+            ),
+            ("issued", Value::Array(issued)),
+        ]);
+        keys::write_file_atomically(path, format!("{}\n", state).as_bytes(), 0o644, true)?;
+        Ok(())
+    }
+}
+
+impl Default for CaState {
+    fn default() -> Self {
+        CaState::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::test_crt_key_pair;
+    use crate::x509::{CertificateParams, Validity};
+
+    #[test]
+    fn new_state_starts_at_random_serial() {
+        let state = CaState::load(Path::new("/nonexistent/ca.state")).unwrap();
+
+        assert!(state.next_serial().bits() > 64);
+        assert!(state.issued().is_empty());
+        assert_ne!(state.next_serial(), CaState::new().next_serial());
+    }
+
+    #[test]
+    fn record_advances_serial_and_save_keeps_it() {
+        let params = CertificateParams {
+            subject: "CN=Test".parse().unwrap(),
+            ..CertificateParams::default()
+        };
+        let der = x509::self_signed(
+            test_crt_key_pair().private(),
+            &params,
+            Validity::days_from_now(1),
+        )
+        .unwrap();
+        let certificate = Certificate::from_der(&der).unwrap();
+        let mut state = CaState::new();
+        state.next_serial = certificate.serial().clone();
+        state.record(&certificate);
+
+        assert_eq!(&(certificate.serial() + 1), state.next_serial());
+        assert_eq!("CN=Test", state.issued()[0].subject);
+
+        let path = std::env::temp_dir().join(format!("rsa-test-{}.state", std::process::id()));
+        state.save(&path).unwrap();
+        let loaded = CaState::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(state, loaded.unwrap());
+    }
+
+    #[test]
+    fn load_rejects_invalid_state() {
+        let path = std::env::temp_dir().join(format!("rsa-test-{}-bad.state", std::process::id()));
+        std::fs::write(&path, "{\"next_serial\":\"-1\",\"issued\":[]}").unwrap();
+        let loaded = CaState::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(loaded, Err(Error::Parse(_))));
+    }
+}
@@ -0,0 +1,382 @@
+/// Length of the key in bytes.
+pub const KEY_LEN: usize = 32;
+/// Length of the nonce in bytes.
+pub const NONCE_LEN: usize = 12;
+/// Length of the authentication tag in bytes.
+pub const TAG_LEN: usize = 16;
+
+/// Error returned when a ciphertext does not match its authentication tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticationError;
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+/// The ChaCha20 block function from RFC 8439, section 2.3.
+fn chacha20_block(key: &[u8; KEY_LEN], counter: u32, nonce: &[u8; NONCE_LEN]) -> [u8; 64] {
+    let mut state = [0u32; 16];
+    state[..4].copy_from_slice(&[0x61707865, 0x3320646e, 0x79622d32, 0x6b206574]);
+    for (i, word) in key.chunks_exact(4).enumerate() {
+        state[4 + i] = u32::from_le_bytes(word.try_into().unwrap());
+    }
+    state[12] = counter;
+    for (i, word) in nonce.chunks_exact(4).enumerate() {
+        state[13 + i] = u32::from_le_bytes(word.try_into().unwrap());
+    }
+
+    let mut working = state;
+    for _ in 0..10 {
+        quarter_round(&mut working, 0, 4, 8, 12);
+        quarter_round(&mut working, 1, 5, 9, 13);
+        quarter_round(&mut working, 2, 6, 10, 14);
+        quarter_round(&mut working, 3, 7, 11, 15);
+        quarter_round(&mut working, 0, 5, 10, 15);
+        quarter_round(&mut working, 1, 6, 11, 12);
+        quarter_round(&mut working, 2, 7, 8, 13);
+        quarter_round(&mut working, 3, 4, 9, 14);
+    }
+
+    let mut block = [0u8; 64];
+    for (i, chunk) in block.chunks_exact_mut(4).enumerate() {
+        chunk.copy_from_slice(&working[i].wrapping_add(state[i]).to_le_bytes());
+    }
+    block
+}
+
+/// Encrypts or decrypts the data in place with ChaCha20, starting from the given block counter.
+pub fn chacha20_xor(key: &[u8; KEY_LEN], counter: u32, nonce: &[u8; NONCE_LEN], data: &mut [u8]) {
+    for (i, chunk) in data.chunks_mut(64).enumerate() {
+        let keystream = chacha20_block(key, counter.wrapping_add(i as u32), nonce);
+        for (byte, key_byte) in chunk.iter_mut().zip(keystream) {
+            *byte ^= key_byte;
+        }
+    }
+}
+
+/// The Poly1305 one-time authenticator from RFC 8439, section 2.5,
+/// using 26-bit limbs so that products fit into 64 bits.
+struct Poly1305 {
+    r: [u32; 5],
+    h: [u32; 5],
+    pad: [u32; 4],
+}
+
+impl Poly1305 {
+    fn new(key: &[u8; 32]) -> Poly1305 {
+        let word = |i: usize| u32::from_le_bytes(key[i..i + 4].try_into().unwrap());
+
+        // Clamp r as required by the specification.
+        let r = [
+            word(0) & 0x3ffffff,
+            (word(3) >> 2) & 0x3ffff03,
+            (word(6) >> 4) & 0x3ffc0ff,
+            (word(9) >> 6) & 0x3f03fff,
+            (word(12) >> 8) & 0x00fffff,
+        ];
+
+        Poly1305 {
+            r,
+            h: [0u32; 5],
+            pad: [word(16), word(20), word(24), word(28)],
+        }
+    }
+
+    /// Processes one block of at most 16 bytes.
+    fn block(&mut self, data: &[u8]) {
+        let mut block = [0u8; 17];
+        block[..data.len()].copy_from_slice(data);
+        block[data.len()] = 1;
+        let word = |i: usize| u32::from_le_bytes(block[i..i + 4].try_into().unwrap());
+
+        let [r0, r1, r2, r3, r4] = self.r.map(u64::from);
+        let (s1, s2, s3, s4) = (r1 * 5, r2 * 5, r3 * 5, r4 * 5);
+
+        let h0 = u64::from(self.h[0] + (word(0) & 0x3ffffff));
+        let h1 = u64::from(self.h[1] + ((word(3) >> 2) & 0x3ffffff));
+        let h2 = u64::from(self.h[2] + ((word(6) >> 4) & 0x3ffffff));
+        let h3 = u64::from(self.h[3] + ((word(9) >> 6) & 0x3ffffff));
+        let h4 = u64::from(self.h[4] + ((word(12) >> 8) | (u32::from(block[16]) << 24)));
+
+        let d0 = h0 * r0 + h1 * s4 + h2 * s3 + h3 * s2 + h4 * s1;
+        let mut d1 = h0 * r1 + h1 * r0 + h2 * s4 + h3 * s3 + h4 * s2;
+        let mut d2 = h0 * r2 + h1 * r1 + h2 * r0 + h3 * s4 + h4 * s3;
+        let mut d3 = h0 * r3 + h1 * r2 + h2 * r1 + h3 * r0 + h4 * s4;
+        let mut d4 = h0 * r4 + h1 * r3 + h2 * r2 + h3 * r1 + h4 * r0;
+
+        let mut carry = d0 >> 26;
+        let mut h0 = d0 & 0x3ffffff;
+        d1 += carry;
+        carry = d1 >> 26;
+        let h1 = d1 & 0x3ffffff;
+        d2 += carry;
+        carry = d2 >> 26;
+        let h2 = d2 & 0x3ffffff;
+        d3 += carry;
+        carry = d3 >> 26;
+        let h3 = d3 & 0x3ffffff;
+        d4 += carry;
+        carry = d4 >> 26;
+        let h4 = d4 & 0x3ffffff;
+        h0 += carry * 5;
+        let h1 = h1 + (h0 >> 26);
+        h0 &= 0x3ffffff;
+
+        self.h = [h0 as u32, h1 as u32, h2 as u32, h3 as u32, h4 as u32];
+    }
+
+    fn finalize(mut self) -> [u8; TAG_LEN] {
+        let h = &mut self.h;
+
+        // Fully carry h.
+        let mut carry = h[1] >> 26;
+        h[1] &= 0x3ffffff;
+        for limb in h[2..].iter_mut() {
+            *limb += carry;
+            carry = *limb >> 26;
+            *limb &= 0x3ffffff;
+        }
+        h[0] += carry * 5;
+        carry = h[0] >> 26;
+        h[0] &= 0x3ffffff;
+        h[1] += carry;
+
+        // Compute h - p and select it if h >= p, without branching.
+        let mut g = [0u32; 5];
+        let mut carry = 5u32;
+        for i in 0..4 {
+            g[i] = h[i] + carry;
+            carry = g[i] >> 26;
+            g[i] &= 0x3ffffff;
+        }
+        g[4] = (h[4] + carry).wrapping_sub(1 << 26);
+        let mask = (g[4] >> 31).wrapping_sub(1);
+        for i in 0..5 {
+            h[i] = (h[i] & !mask) | (g[i] & mask);
+        }
+
+        // h = (h + pad) % 2^128
+        let words = [
+            h[0] | (h[1] << 26),
+            (h[1] >> 6) | (h[2] << 20),
+            (h[2] >> 12) | (h[3] << 14),
+            (h[3] >> 18) | (h[4] << 8),
+        ];
+        let mut tag = [0u8; TAG_LEN];
+        let mut carry = 0u64;
+        for i in 0..4 {
+            let sum = u64::from(words[i]) + u64::from(self.pad[i]) + carry;
+            tag[4 * i..4 * i + 4].copy_from_slice(&(sum as u32).to_le_bytes());
+            carry = sum >> 32;
+        }
+        tag
+    }
+}
+
+/// Computes the Poly1305 tag of the message with a one-time key.
+fn poly1305(key: &[u8; 32], message: &[u8]) -> [u8; TAG_LEN] {
+    let mut mac = Poly1305::new(key);
+    for block in message.chunks(16) {
+        mac.block(block);
+    }
+    mac.finalize()
+}
+
+/// Computes the AEAD tag over the additional data and the ciphertext.
+fn compute_tag(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    ciphertext: &[u8],
+) -> [u8; TAG_LEN] {
+    let mut otk = [0u8; 32];
+    otk.copy_from_slice(&chacha20_block(key, 0, nonce)[..32]);
+
+    let mut mac_data = Vec::with_capacity(aad.len() + ciphertext.len() + 48);
+    for part in [aad, ciphertext] {
+        mac_data.extend_from_slice(part);
+        mac_data.resize(mac_data.len().div_ceil(16) * 16, 0);
+    }
+    mac_data.extend_from_slice(&(aad.len() as u64).to_le_bytes());
+    mac_data.extend_from_slice(&(ciphertext.len() as u64).to_le_bytes());
+
+    poly1305(&otk, &mac_data)
+}
+
+/// Encrypts the data in place with ChaCha20-Poly1305 (RFC 8439, section 2.8)
+/// and returns the authentication tag.
+pub fn seal(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    data: &mut [u8],
+) -> [u8; TAG_LEN] {
+    chacha20_xor(key, 1, nonce, data);
+    compute_tag(key, nonce, aad, data)
+}
+
+/// Checks the authentication tag and decrypts the data in place.
+/// The data is left untouched if the tag does not match.
+pub fn open(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    data: &mut [u8],
+    tag: &[u8],
+) -> Result<(), AuthenticationError> {
+    let expected = compute_tag(key, nonce, aad, data);
+    let difference = expected
+        .iter()
+        .zip(tag)
+        .fold((tag.len() != TAG_LEN) as u8, |acc, (a, b)| acc | (a ^ b));
+    if difference != 0 {
+        return Err(AuthenticationError);
+    }
+
+    chacha20_xor(key, 1, nonce, data);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        let hex: String = hex.split_whitespace().collect();
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    const SUNSCREEN: &[u8] = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+
+    // test vectors from RFC 8439
+    #[test]
+    fn chacha20_block_returns_correct_keystream() {
+        let key: [u8; 32] = core::array::from_fn(|i| i as u8);
+        let nonce: [u8; 12] = from_hex("000000090000004a00000000").try_into().unwrap();
+
+        assert_eq!(
+            from_hex(
+                "10f1e7e4d13b5915500fdd1fa32071c4c7d1f4c733c068030422aa9ac3d46c4e
+                 d2826446079faa0914c2d705d98b02a2b5129cd1de164eb9cbd083e8a2503c4e"
+            ),
+            chacha20_block(&key, 1, &nonce)
+        );
+    }
+
+    #[test]
+    fn chacha20_xor_encrypts_correctly() {
+        let key: [u8; 32] = core::array::from_fn(|i| i as u8);
+        let nonce: [u8; 12] = from_hex("000000000000004a00000000").try_into().unwrap();
+        let mut data = SUNSCREEN.to_vec();
+
+        chacha20_xor(&key, 1, &nonce, &mut data);
+
+        assert_eq!(
+            from_hex(
+                "6e2e359a2568f98041ba0728dd0d6981e97e7aec1d4360c20a27afccfd9fae0b
+                 f91b65c5524733ab8f593dabcd62b3571639d624e65152ab8f530c359f0861d8
+                 07ca0dbf500d6a6156a38e088a22b65e52bc514d16ccf806818ce91ab7793736
+                 5af90bbf74a35be6b40b8eedf2785e42874d"
+            ),
+            data
+        );
+    }
+
+    #[test]
+    fn poly1305_returns_correct_tag() {
+        let key: [u8; 32] =
+            from_hex("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b")
+                .try_into()
+                .unwrap();
+
+        assert_eq!(
+            from_hex("a8061dc1305136c6c22b8baf0c0127a9"),
+            poly1305(&key, b"Cryptographic Forum Research Group")
+        );
+    }
+
+    #[test]
+    fn poly1305_handles_wraparound() {
+        // (2^129 - 1) * 2 = 2^130 - 2, which only reduces to 3 after the final subtraction of p
+        let mut key = [0u8; 32];
+        key[0] = 2;
+        let message = [0xffu8; 16];
+
+        assert_eq!(
+            from_hex("03000000000000000000000000000000"),
+            poly1305(&key, &message)
+        );
+    }
+
+    #[test]
+    fn seal_returns_correct_ciphertext_and_tag() {
+        let key: [u8; 32] = core::array::from_fn(|i| 0x80 + i as u8);
+        let nonce: [u8; 12] = from_hex("070000004041424344454647").try_into().unwrap();
+        let aad = from_hex("50515253c0c1c2c3c4c5c6c7");
+        let mut data = SUNSCREEN.to_vec();
+
+        let tag = seal(&key, &nonce, &aad, &mut data);
+
+        assert_eq!(
+            from_hex(
+                "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6
+                 3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36
+                 92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc
+                 3ff4def08e4b7a9de576d26586cec64b6116"
+            ),
+            data
+        );
+        assert_eq!(from_hex("1ae10b594f09e26a7e902ecbd0600691"), tag);
+    }
+
+    #[test]
+    fn open_returns_original_data() {
+        let key = [9u8; 32];
+        let nonce = [1u8; 12];
+        let mut data = SUNSCREEN.to_vec();
+
+        let tag = seal(&key, &nonce, b"header", &mut data);
+        open(&key, &nonce, b"header", &mut data, &tag).unwrap();
+
+        assert_eq!(SUNSCREEN, data);
+    }
+
+    #[test]
+    fn open_rejects_modified_ciphertext() {
+        let key = [9u8; 32];
+        let nonce = [1u8; 12];
+        let mut data = SUNSCREEN.to_vec();
+
+        let tag = seal(&key, &nonce, b"header", &mut data);
+        data[3] ^= 1;
+
+        assert_eq!(
+            Err(AuthenticationError),
+            open(&key, &nonce, b"header", &mut data, &tag)
+        );
+    }
+
+    #[test]
+    fn open_rejects_modified_additional_data() {
+        let key = [9u8; 32];
+        let nonce = [1u8; 12];
+        let mut data = SUNSCREEN.to_vec();
+
+        let tag = seal(&key, &nonce, b"header", &mut data);
+
+        assert_eq!(
+            Err(AuthenticationError),
+            open(&key, &nonce, b"headex", &mut data, &tag)
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! The container format of data encrypted block by block with RSA, which records
+//! how the data was encrypted and for which key.
+//!
+//! The output starts with a header:
+//!
+//! | field        | size | value                                                |
+//! |--------------|------|------------------------------------------------------|
+//! | magic        | 4    | `RSAC`                                               |
+//! | version      | 1    | `1`                                                  |
+//! | padding      | 1    | `0` = raw, `1` = OAEP, `2` = PKCS #1 v1.5            |
+//! | hash         | 1    | hash id for OAEP, `0` otherwise                      |
+//! | block length | 2    | big-endian length of an encrypted block              |
+//! | fingerprint  | 32   | SHA-256 fingerprint of the key, zero if it is unknown |
+//!
+//! The encrypted blocks follow the header, in the same format as written by
+//! `Key::encrypt_with`. The OAEP label is not stored, so it has to be given when
+//! decrypting. The header is not authenticated, but changing it only makes the
+//! blocks fail to decrypt.
+
+use std::fmt;
+use std::io::{Read, Write};
+
+use crate::error::{Error, Result};
+use crate::hash::HashAlgorithm;
+use crate::keys::{read_block, Key};
+use crate::padding::Padding;
+
+const MAGIC: &[u8; 4] = b"RSAC";
+const VERSION: u8 = 1;
+const FINGERPRINT_LEN: usize = 32;
+const HEADER_LEN: usize = 9 + FINGERPRINT_LEN;
+
+/// Errors produced while reading a container.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContainerError {
+    /// The input does not start with a valid header.
+    InvalidHeader,
+    /// The header has a version this program does not know.
+    UnsupportedVersion(u8),
+    /// The header names an algorithm this program does not know.
+    UnsupportedAlgorithm,
+    /// The data was encrypted for another key.
+    WrongKey,
+}
+
+impl fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContainerError::InvalidHeader => write!(
+                f,
+                "Invalid header, data encrypted without a header needs --raw"
+            ),
+            ContainerError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported container version: {}", version)
+            }
+            ContainerError::UnsupportedAlgorithm => write!(f, "Unsupported container algorithm"),
+            ContainerError::WrongKey => write!(f, "The data was encrypted for another key"),
+        }
+    }
+}
+
+impl std::error::Error for ContainerError {}
+
+#[derive(Debug, PartialEq)]
+struct Header {
+    padding: Padding,
+    block_len: u16,
+    fingerprint: [u8; FINGERPRINT_LEN],
+}
+
+impl Header {
+    /// Fails with `Error::Size` if the block length of the key does not fit into
+    /// the header.
+    fn new(key: &Key, padding: &Padding) -> Result<Header> {
+        let block_len = u16::try_from(key.size_in_bytes()).map_err(|_| {
+            Error::Size("The key is too large for the encrypted file header".to_string())
+        })?;
+        let mut fingerprint = [0u8; FINGERPRINT_LEN];
+        if let Some(key_fingerprint) = key.fingerprint() {
+            fingerprint.copy_from_slice(&key_fingerprint);
+        }
+        Ok(Header {
+            padding: padding.clone(),
+            block_len,
+            fingerprint,
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let (padding_id, hash_id) = match &self.padding {
+            Padding::Raw => (0, 0),
+            Padding::Oaep { hash, .. } => (1, hash.id()),
+            Padding::Pkcs1v15 => (2, 0),
+        };
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[VERSION, padding_id, hash_id]);
+        bytes.extend_from_slice(&self.block_len.to_be_bytes());
+        bytes.extend_from_slice(&self.fingerprint);
+        bytes
+    }
+
+    fn read(input: &mut dyn Read, label: &[u8]) -> Result<Header> {
+        let mut bytes = [0u8; HEADER_LEN];
+        if read_block(input, &mut bytes)? < bytes.len() || bytes[..4] != MAGIC[..] {
+            return Err(ContainerError::InvalidHeader.into());
+        }
+        if bytes[4] != VERSION {
+            return Err(ContainerError::UnsupportedVersion(bytes[4]).into());
+        }
+
+        let padding = match (bytes[5], HashAlgorithm::from_id(bytes[6])) {
+            (0, _) => Padding::Raw,
+            (1, Some(hash)) => Padding::Oaep {
+                hash,
+                label: label.to_vec(),
+            },
+            (2, _) => Padding::Pkcs1v15,
+            _ => return Err(ContainerError::UnsupportedAlgorithm.into()),
+        };
+
+        Ok(Header {
+            padding,
+            block_len: u16::from_be_bytes([bytes[7], bytes[8]]),
+            fingerprint: bytes[9..].try_into().unwrap(),
+        })
+    }
+}
+
+/// Writes the header and encrypts the input block by block with the key.
+pub(crate) fn seal(
+    key: &Key,
+    padding: &Padding,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+) -> Result<()> {
+    output.write_all(&Header::new(key, padding)?.to_bytes())?;
+    key.encrypt_with(padding, input, output)
+}
+
+/// Checks the header written by `seal` against the key and decrypts the blocks
+/// with the padding named in it.
+pub(crate) fn open(
+    key: &Key,
+    label: &[u8],
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+) -> Result<()> {
+    let header = Header::read(input, label)?;
+    let expected = Header::new(key, &header.padding)?;
+
+    // Keys read from old decimal files have no known fingerprint, so only the
+    // modulus length can be compared for them.
+    let unknown = [0u8; FINGERPRINT_LEN];
+    let fingerprints_differ = header.fingerprint != unknown
+        && expected.fingerprint != unknown
+        && header.fingerprint != expected.fingerprint;
+    if fingerprints_differ || header.block_len != expected.block_len {
+        return Err(ContainerError::WrongKey.into());
+    }
+
+    key.decrypt_with(&header.padding, input, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::{test_crt_key_pair, test_key_pair, KeyPair};
+
+    fn seal_to_vec(keys: &KeyPair, padding: &Padding, data: &[u8]) -> Vec<u8> {
+        let mut sealed = Vec::new();
+        seal(keys.public(), padding, &mut &data[..], &mut sealed).unwrap();
+        sealed
+    }
+
+    fn open_error(keys: &KeyPair, sealed: &[u8]) -> ContainerError {
+        match open(keys.private(), b"", &mut &sealed[..], &mut Vec::new()) {
+            Err(Error::Container(err)) => err,
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn header_to_bytes_starts_with_magic_and_version() {
+        let header = Header {
+            padding: Padding::Pkcs1v15,
+            block_len: 256,
+            fingerprint: [7u8; FINGERPRINT_LEN],
+        };
+
+        let bytes = header.to_bytes();
+
+        assert_eq!(b"RSAC\x01\x02\x00\x01\x00".to_vec(), bytes[..9].to_vec());
+        assert_eq!([7u8; FINGERPRINT_LEN], bytes[9..]);
+    }
+
+    #[test]
+    fn open_uses_padding_from_header() {
+        let keys = test_crt_key_pair();
+
+        for padding in [
+            Padding::oaep(),
+            Padding::Oaep {
+                hash: HashAlgorithm::Sha512,
+                label: Vec::new(),
+            },
+            Padding::Pkcs1v15,
+            Padding::Raw,
+        ] {
+            let sealed = seal_to_vec(&keys, &padding, b"FooBarBaz");
+            let mut opened = Vec::new();
+            open(keys.private(), b"", &mut &sealed[..], &mut opened).unwrap();
+
+            assert_eq!(b"FooBarBaz".to_vec(), opened);
+        }
+    }
+
+    #[test]
+    fn open_rejects_data_without_header() {
+        let keys = test_crt_key_pair();
+        let mut encrypted = Vec::new();
+        keys.public()
+            .encrypt_with(&Padding::oaep(), &mut &b"FooBarBaz"[..], &mut encrypted)
+            .unwrap();
+
+        assert_eq!(ContainerError::InvalidHeader, open_error(&keys, &encrypted));
+        assert_eq!(ContainerError::InvalidHeader, open_error(&keys, b"RSAC"));
+    }
+
+    #[test]
+    fn open_rejects_unknown_version_and_algorithm() {
+        let keys = test_crt_key_pair();
+        let sealed = seal_to_vec(&keys, &Padding::oaep(), b"FooBarBaz");
+
+        let mut modified = sealed.clone();
+        modified[4] = 2;
+        assert_eq!(
+            ContainerError::UnsupportedVersion(2),
+            open_error(&keys, &modified)
+        );
+
+        let mut modified = sealed.clone();
+        modified[6] = 9;
+        assert_eq!(
+            ContainerError::UnsupportedAlgorithm,
+            open_error(&keys, &modified)
+        );
+    }
+
+    #[test]
+    fn open_rejects_other_key() {
+        let keys = test_crt_key_pair();
+        let sealed = seal_to_vec(&keys, &Padding::oaep(), b"FooBarBaz");
+
+        let mut modified = sealed.clone();
+        modified[20] ^= 1;
+        assert_eq!(ContainerError::WrongKey, open_error(&keys, &modified));
+
+        let mut modified = sealed.clone();
+        modified[8] ^= 1;
+        assert_eq!(ContainerError::WrongKey, open_error(&keys, &modified));
+    }
+
+    #[test]
+    fn open_accepts_key_without_fingerprint() {
+        let keys = test_key_pair();
+        let sealed = seal_to_vec(&keys, &Padding::oaep(), b"FooBarBaz");
+        let mut opened = Vec::new();
+        open(keys.private(), b"", &mut &sealed[..], &mut opened).unwrap();
+
+        assert_eq!(b"FooBarBaz".to_vec(), opened);
+    }
+}
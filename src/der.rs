@@ -0,0 +1,458 @@
+//! A minimal encoder and decoder for the subset of ASN.1 DER used by the key formats
+//! and certificates. Only single byte tags and definite lengths are supported.
+
+use std::fmt;
+
+use num_bigint::{BigInt, Sign};
+
+pub const BOOLEAN: u8 = 0x01;
+pub const INTEGER: u8 = 0x02;
+pub const BIT_STRING: u8 = 0x03;
+pub const OCTET_STRING: u8 = 0x04;
+pub const NULL: u8 = 0x05;
+pub const OBJECT_IDENTIFIER: u8 = 0x06;
+pub const UTF8_STRING: u8 = 0x0c;
+pub const PRINTABLE_STRING: u8 = 0x13;
+pub const IA5_STRING: u8 = 0x16;
+pub const UTC_TIME: u8 = 0x17;
+pub const GENERALIZED_TIME: u8 = 0x18;
+pub const BMP_STRING: u8 = 0x1e;
+pub const SEQUENCE: u8 = 0x30;
+pub const SET: u8 = 0x31;
+
+/// The tag of a context-specific element, constructed if it contains other elements.
+pub const fn context(number: u8, constructed: bool) -> u8 {
+    0x80 | if constructed { 0x20 } else { 0 } | number
+}
+
+/// Errors produced while decoding DER.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DerError {
+    /// The input ended in the middle of an element.
+    UnexpectedEnd,
+    /// An element had another tag than expected.
+    UnexpectedTag { expected: u8, found: u8 },
+    /// A length was not encoded in the shortest form or was too large.
+    InvalidLength,
+    /// The content of an element was not valid for its type.
+    InvalidValue,
+    /// There was data left after the last expected element.
+    TrailingData,
+}
+
+impl fmt::Display for DerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DerError::UnexpectedEnd => write!(f, "Unexpected end of DER data"),
+            DerError::UnexpectedTag { expected, found } => write!(
+                f,
+                "Unexpected DER tag: expected 0x{:02x}, found 0x{:02x}",
+                expected, found
+            ),
+            DerError::InvalidLength => write!(f, "Invalid DER length"),
+            DerError::InvalidValue => write!(f, "Invalid DER value"),
+            DerError::TrailingData => write!(f, "Trailing data after DER element"),
+        }
+    }
+}
+
+impl std::error::Error for DerError {}
+
+/// Encodes an element from its tag and content.
+pub fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut result = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        result.push(len as u8);
+    } else {
+        let len_bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|byte| *byte == 0)
+            .collect();
+        result.push(0x80 | len_bytes.len() as u8);
+        result.extend_from_slice(&len_bytes);
+    }
+    result.extend_from_slice(content);
+    result
+}
+
+/// Encodes a sequence of already encoded elements.
+pub fn sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    tlv(SEQUENCE, &items.concat())
+}
+
+/// Encodes a set of already encoded elements, sorted as DER requires for SET OF.
+pub fn set(items: &[Vec<u8>]) -> Vec<u8> {
+    let mut items = items.to_vec();
+    items.sort();
+    tlv(SET, &items.concat())
+}
+
+pub fn boolean(value: bool) -> Vec<u8> {
+    tlv(BOOLEAN, &[if value { 0xff } else { 0 }])
+}
+
+/// Encodes an integer in the shortest two's complement form.
+pub fn integer(value: &BigInt) -> Vec<u8> {
+    tlv(INTEGER, &value.to_signed_bytes_be())
+}
+
+pub fn null() -> Vec<u8> {
+    tlv(NULL, &[])
+}
+
+/// Encodes a bit string with no unused bits.
+pub fn bit_string(bytes: &[u8]) -> Vec<u8> {
+    let mut content = vec![0u8];
+    content.extend_from_slice(bytes);
+    tlv(BIT_STRING, &content)
+}
+
+/// Encodes a bit string from the numbers of the bits that are set, where bit 0
+/// is the most significant bit of the first byte, without trailing zero bits.
+pub fn named_bits(bits: &[usize]) -> Vec<u8> {
+    let Some(&last) = bits.iter().max() else {
+        return tlv(BIT_STRING, &[0]);
+    };
+    let mut content = vec![0u8; last / 8 + 2];
+    content[0] = 7 - (last % 8) as u8;
+    for bit in bits {
+        content[1 + bit / 8] |= 0x80 >> (bit % 8);
+    }
+    tlv(BIT_STRING, &content)
+}
+
+pub fn octet_string(bytes: &[u8]) -> Vec<u8> {
+    tlv(OCTET_STRING, bytes)
+}
+
+/// Encodes an object identifier from its arcs.
+pub fn oid(arcs: &[u64]) -> Vec<u8> {
+    let mut content = Vec::new();
+    let mut values = vec![arcs[0] * 40 + arcs[1]];
+    values.extend_from_slice(&arcs[2..]);
+    for value in values {
+        let mut groups = vec![(value & 0x7f) as u8];
+        let mut rest = value >> 7;
+        while rest > 0 {
+            groups.push(0x80 | (rest & 0x7f) as u8);
+            rest >>= 7;
+        }
+        content.extend(groups.iter().rev());
+    }
+    tlv(OBJECT_IDENTIFIER, &content)
+}
+
+/// Reads consecutive DER elements from a byte slice.
+#[derive(Debug, Clone)]
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the tag of the next element without consuming it.
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+
+    /// Reads the next element and returns its tag and content.
+    pub fn read_any(&mut self) -> Result<(u8, &'a [u8]), DerError> {
+        let (&tag, rest) = self.data.split_first().ok_or(DerError::UnexpectedEnd)?;
+        let (&first, mut rest) = rest.split_first().ok_or(DerError::UnexpectedEnd)?;
+
+        let len = if first < 0x80 {
+            first as usize
+        } else {
+            let count = (first & 0x7f) as usize;
+            if count == 0 || count > std::mem::size_of::<usize>() || rest.len() < count {
+                return Err(DerError::InvalidLength);
+            }
+            let (len_bytes, after) = rest.split_at(count);
+            rest = after;
+            if len_bytes[0] == 0 {
+                return Err(DerError::InvalidLength);
+            }
+            let len = len_bytes
+                .iter()
+                .fold(0usize, |acc, byte| (acc << 8) | *byte as usize);
+            if len < 0x80 {
+                return Err(DerError::InvalidLength);
+            }
+            len
+        };
+
+        if rest.len() < len {
+            return Err(DerError::UnexpectedEnd);
+        }
+        let (content, after) = rest.split_at(len);
+        self.data = after;
+        Ok((tag, content))
+    }
+
+    /// Reads the next element, which must have the given tag, and returns its content.
+    pub fn read(&mut self, expected: u8) -> Result<&'a [u8], DerError> {
+        let (tag, content) = self.read_any()?;
+        if tag != expected {
+            return Err(DerError::UnexpectedTag {
+                expected,
+                found: tag,
+            });
+        }
+        Ok(content)
+    }
+
+    /// Reads a sequence and returns a reader over its elements.
+    pub fn read_sequence(&mut self) -> Result<Reader<'a>, DerError> {
+        Ok(Reader::new(self.read(SEQUENCE)?))
+    }
+
+    pub fn read_integer(&mut self) -> Result<BigInt, DerError> {
+        let content = self.read(INTEGER)?;
+        if content.is_empty()
+            || (content.len() > 1
+                && ((content[0] == 0 && content[1] < 0x80)
+                    || (content[0] == 0xff && content[1] >= 0x80)))
+        {
+            return Err(DerError::InvalidValue);
+        }
+        Ok(BigInt::from_signed_bytes_be(content))
+    }
+
+    /// Reads an integer that must not be negative.
+    pub fn read_unsigned(&mut self) -> Result<BigInt, DerError> {
+        let value = self.read_integer()?;
+        if value.sign() == Sign::Minus {
+            return Err(DerError::InvalidValue);
+        }
+        Ok(value)
+    }
+
+    pub fn read_boolean(&mut self) -> Result<bool, DerError> {
+        match self.read(BOOLEAN)? {
+            [0] => Ok(false),
+            [0xff] => Ok(true),
+            _ => Err(DerError::InvalidValue),
+        }
+    }
+
+    pub fn read_null(&mut self) -> Result<(), DerError> {
+        if !self.read(NULL)?.is_empty() {
+            return Err(DerError::InvalidValue);
+        }
+        Ok(())
+    }
+
+    /// Reads a bit string, which must not have unused bits.
+    pub fn read_bit_string(&mut self) -> Result<&'a [u8], DerError> {
+        match self.read(BIT_STRING)?.split_first() {
+            Some((0, bytes)) => Ok(bytes),
+            _ => Err(DerError::InvalidValue),
+        }
+    }
+
+    /// Reads a bit string and returns the numbers of the bits that are set, where
+    /// bit 0 is the most significant bit of the first byte.
+    pub fn read_named_bits(&mut self) -> Result<Vec<usize>, DerError> {
+        let (unused, bytes) = self
+            .read(BIT_STRING)?
+            .split_first()
+            .ok_or(DerError::InvalidValue)?;
+        if *unused > 7 || (bytes.is_empty() && *unused != 0) {
+            return Err(DerError::InvalidValue);
+        }
+        Ok((0..bytes.len() * 8 - *unused as usize)
+            .filter(|bit| bytes[bit / 8] & (0x80 >> (bit % 8)) != 0)
+            .collect())
+    }
+
+    pub fn read_octet_string(&mut self) -> Result<&'a [u8], DerError> {
+        self.read(OCTET_STRING)
+    }
+
+    pub fn read_oid(&mut self) -> Result<Vec<u64>, DerError> {
+        let content = self.read(OBJECT_IDENTIFIER)?;
+        if content.is_empty() || content[content.len() - 1] & 0x80 != 0 {
+            return Err(DerError::InvalidValue);
+        }
+
+        let mut values = Vec::new();
+        let mut value: u64 = 0;
+        for (i, byte) in content.iter().enumerate() {
+            let starts_value = i == 0 || content[i - 1] & 0x80 == 0;
+            if (starts_value && *byte == 0x80) || value >> 57 != 0 {
+                return Err(DerError::InvalidValue);
+            }
+            value = (value << 7) | (byte & 0x7f) as u64;
+            if byte & 0x80 == 0 {
+                values.push(value);
+                value = 0;
+            }
+        }
+
+        let first = values[0];
+        let mut arcs = if first < 80 {
+            vec![first / 40, first % 40]
+        } else {
+            vec![2, first - 80]
+        };
+        arcs.extend_from_slice(&values[1..]);
+        Ok(arcs)
+    }
+
+    /// Checks that all elements have been read.
+    pub fn finish(&self) -> Result<(), DerError> {
+        if !self.data.is_empty() {
+            return Err(DerError::TrailingData);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_uses_shortest_form() {
+        assert_eq!(vec![0x02, 0x01, 0x00], integer(&BigInt::from(0)));
+        assert_eq!(vec![0x02, 0x01, 0x7f], integer(&BigInt::from(127)));
+        assert_eq!(vec![0x02, 0x02, 0x00, 0x80], integer(&BigInt::from(128)));
+        assert_eq!(vec![0x02, 0x01, 0x80], integer(&BigInt::from(-128)));
+        assert_eq!(
+            vec![0x02, 0x03, 0x01, 0x00, 0x01],
+            integer(&BigInt::from(65537))
+        );
+    }
+
+    #[test]
+    fn tlv_uses_long_form_length() {
+        let encoded = tlv(OCTET_STRING, &[0u8; 300]);
+
+        assert_eq!(vec![0x04, 0x82, 0x01, 0x2c], encoded[..4]);
+        assert_eq!(304, encoded.len());
+    }
+
+    #[test]
+    fn oid_encodes_rsa_encryption() {
+        assert_eq!(
+            vec![0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01],
+            oid(&[1, 2, 840, 113549, 1, 1, 1])
+        );
+    }
+
+    #[test]
+    fn set_sorts_elements() {
+        assert_eq!(
+            vec![0x31, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02],
+            set(&[integer(&BigInt::from(2)), integer(&BigInt::from(1))])
+        );
+    }
+
+    #[test]
+    fn named_bits_drops_trailing_zero_bits() {
+        assert_eq!(vec![0x03, 0x02, 0x05, 0xa0], named_bits(&[0, 2]));
+        assert_eq!(vec![0x03, 0x03, 0x07, 0x00, 0x80], named_bits(&[8]));
+        assert_eq!(vec![0x03, 0x01, 0x00], named_bits(&[]));
+    }
+
+    #[test]
+    fn reader_returns_named_bits() {
+        for bits in [vec![], vec![0, 2], vec![5, 6], vec![8]] {
+            assert_eq!(
+                bits,
+                Reader::new(&named_bits(&bits)).read_named_bits().unwrap()
+            );
+        }
+        assert_eq!(
+            Err(DerError::InvalidValue),
+            Reader::new(&[0x03, 0x01, 0x01]).read_named_bits()
+        );
+    }
+
+    #[test]
+    fn reader_returns_encoded_values() {
+        let encoded = sequence(&[
+            integer(&BigInt::from(-5)),
+            oid(&[1, 2, 840, 113549, 1, 1, 1]),
+            null(),
+            boolean(true),
+            bit_string(b"bits"),
+            octet_string(&[1u8; 200]),
+        ]);
+
+        let mut reader = Reader::new(&encoded);
+        let mut sequence = reader.read_sequence().unwrap();
+        reader.finish().unwrap();
+
+        assert_eq!(BigInt::from(-5), sequence.read_integer().unwrap());
+        assert_eq!(
+            vec![1, 2, 840, 113549, 1, 1, 1],
+            sequence.read_oid().unwrap()
+        );
+        sequence.read_null().unwrap();
+        assert!(sequence.read_boolean().unwrap());
+        assert_eq!(b"bits", sequence.read_bit_string().unwrap());
+        assert_eq!(vec![1u8; 200], sequence.read_octet_string().unwrap());
+        sequence.finish().unwrap();
+    }
+
+    #[test]
+    fn reader_rejects_wrong_tag() {
+        let encoded = null();
+
+        assert_eq!(
+            Err(DerError::UnexpectedTag {
+                expected: INTEGER,
+                found: NULL
+            }),
+            Reader::new(&encoded).read_integer()
+        );
+    }
+
+    #[test]
+    fn reader_rejects_truncated_element() {
+        assert_eq!(
+            Err(DerError::UnexpectedEnd),
+            Reader::new(&[0x04, 0x05, 0x01]).read_octet_string()
+        );
+    }
+
+    #[test]
+    fn reader_rejects_non_minimal_encodings() {
+        assert_eq!(
+            Err(DerError::InvalidLength),
+            Reader::new(&[0x04, 0x81, 0x01, 0x00]).read_octet_string()
+        );
+        assert_eq!(
+            Err(DerError::InvalidValue),
+            Reader::new(&[0x02, 0x02, 0x00, 0x01]).read_integer()
+        );
+    }
+
+    #[test]
+    fn reader_rejects_negative_unsigned() {
+        assert_eq!(
+            Err(DerError::InvalidValue),
+            Reader::new(&integer(&BigInt::from(-1))).read_unsigned()
+        );
+    }
+
+    #[test]
+    fn finish_rejects_trailing_data() {
+        let mut encoded = null();
+        encoded.push(0);
+        let mut reader = Reader::new(&encoded);
+        reader.read_null().unwrap();
+
+        assert_eq!(Err(DerError::TrailingData), reader.finish());
+    }
+}
@@ -0,0 +1,174 @@
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+const BASE64URL_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Encodes the bytes as base64 with padding, as specified in RFC 4648, section 4.
+pub fn base64_encode(bytes: &[u8]) -> String {
+    encode(bytes, BASE64_ALPHABET, true)
+}
+
+/// Decodes base64 with or without padding. Whitespace is ignored.
+pub fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    decode(text, BASE64_ALPHABET)
+}
+
+/// Encodes the bytes as base64url without padding, as used by JSON Web Keys
+/// (RFC 4648, section 5 and RFC 7515, section 2).
+pub fn base64url_encode(bytes: &[u8]) -> String {
+    encode(bytes, BASE64URL_ALPHABET, false)
+}
+
+/// Decodes base64url with or without padding. Whitespace is ignored.
+pub fn base64url_decode(text: &str) -> Option<Vec<u8>> {
+    decode(text, BASE64URL_ALPHABET)
+}
+
+fn encode(bytes: &[u8], alphabet: &[u8; 64], pad: bool) -> String {
+    let mut result = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let mut group = [0u8; 3];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let value = u32::from_be_bytes([0, group[0], group[1], group[2]]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (value >> (18 - 6 * i)) & 0x3f;
+                result.push(alphabet[index as usize] as char);
+            } else if pad {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+fn decode(text: &str, alphabet: &[u8; 64]) -> Option<Vec<u8>> {
+    let mut values = Vec::with_capacity(text.len());
+    let mut padding = 0;
+    for byte in text.bytes() {
+        if byte.is_ascii_whitespace() {
+            continue;
+        }
+        if byte == b'=' {
+            padding += 1;
+            continue;
+        }
+        if padding > 0 {
+            return None;
+        }
+        let value = alphabet.iter().position(|c| *c == byte)?;
+        values.push(value as u32);
+    }
+    if padding > 2 || (padding > 0 && (values.len() + padding) % 4 != 0) || values.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut result = Vec::with_capacity(values.len() * 3 / 4);
+    for chunk in values.chunks(4) {
+        let value = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, v)| acc | (v << (18 - 6 * i)));
+        let bytes = value.to_be_bytes();
+        result.extend_from_slice(&bytes[1..chunk.len()]);
+    }
+    Some(result)
+}
+
+/// Encodes the bytes as lowercase hexadecimal.
+pub fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decodes hexadecimal in upper or lower case. Whitespace is ignored.
+pub fn hex_decode(text: &str) -> Option<Vec<u8>> {
+    let digits = text
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace())
+        .map(|c| c.to_digit(16).map(|digit| digit as u8))
+        .collect::<Option<Vec<u8>>>()?;
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    Some(
+        digits
+            .chunks(2)
+            .map(|pair| pair[0] << 4 | pair[1])
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // test vectors from RFC 4648, section 10
+    const VECTORS: [(&str, &str); 7] = [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ];
+
+    #[test]
+    fn base64_encode_returns_correct_string() {
+        for (plain, encoded) in VECTORS {
+            assert_eq!(encoded, base64_encode(plain.as_bytes()));
+        }
+    }
+
+    #[test]
+    fn base64_decode_returns_original_bytes() {
+        for (plain, encoded) in VECTORS {
+            assert_eq!(Some(plain.as_bytes().to_vec()), base64_decode(encoded));
+        }
+    }
+
+    #[test]
+    fn base64_decode_ignores_whitespace_and_missing_padding() {
+        assert_eq!(Some(b"foobar".to_vec()), base64_decode("Zm9v\nYmFy\n"));
+        assert_eq!(Some(b"foob".to_vec()), base64_decode("Zm9vYg"));
+    }
+
+    #[test]
+    fn base64_decode_rejects_invalid_input() {
+        assert_eq!(None, base64_decode("Zm9v!"));
+        assert_eq!(None, base64_decode("Zm=9v"));
+        assert_eq!(None, base64_decode("Z"));
+    }
+
+    #[test]
+    fn base64_decode_handles_binary_data() {
+        let data: Vec<u8> = (0..=255).collect();
+
+        assert_eq!(Some(data.clone()), base64_decode(&base64_encode(&data)));
+    }
+
+    #[test]
+    fn base64url_uses_url_safe_alphabet_without_padding() {
+        assert_eq!("-_8", base64url_encode(&[0xfb, 0xff]));
+        assert_eq!("Zm9vYg", base64url_encode(b"foob"));
+        assert_eq!(Some(vec![0xfb, 0xff]), base64url_decode("-_8"));
+        assert_eq!(None, base64url_decode("+/8"));
+    }
+
+    #[test]
+    fn hex_decode_returns_original_bytes() {
+        let data: Vec<u8> = (0..=255).collect();
+
+        assert_eq!("00017f80ff", hex_encode(&[0, 1, 127, 128, 255]));
+        assert_eq!(Some(data.clone()), hex_decode(&hex_encode(&data)));
+        assert_eq!(Some(vec![0xab, 0xcd]), hex_decode("AB cd\n"));
+    }
+
+    #[test]
+    fn hex_decode_rejects_invalid_input() {
+        assert_eq!(None, hex_decode("abc"));
+        assert_eq!(None, hex_decode("zz"));
+    }
+}
@@ -0,0 +1,371 @@
+//! Hybrid encryption: the data is encrypted with ChaCha20-Poly1305 under a random
+//! content key, and only the content key is encrypted with RSA.
+//!
+//! The output starts with a header:
+//!
+//! | field             | size | value                                      |
+//! |-------------------|------|--------------------------------------------|
+//! | magic             | 4    | `RSAH`                                     |
+//! | version           | 1    | `1`                                        |
+//! | key wrap padding  | 1    | `1` = OAEP, `2` = PKCS #1 v1.5             |
+//! | key wrap hash     | 1    | hash id for OAEP, `0` otherwise            |
+//! | cipher            | 1    | `1` = ChaCha20-Poly1305                    |
+//! | chunk size        | 4    | big-endian length of a plaintext chunk     |
+//! | wrapped key size  | 2    | big-endian length of the wrapped key       |
+//! | wrapped key       | n    | the content key encrypted with RSA         |
+//!
+//! The plaintext is then split into chunks of the given size, each of which is
+//! written out encrypted and followed by its 16 byte authentication tag. The last
+//! chunk is always shorter than the chunk size, even if that makes it empty. The
+//! nonce of a chunk is its index with a flag marking the last chunk, so that
+//! chunks can not be reordered and truncation is detected. The whole header is
+//! authenticated as additional data of every chunk.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::chacha20poly1305::{self, KEY_LEN, NONCE_LEN, TAG_LEN};
+use crate::error::Result;
+use crate::hash::HashAlgorithm;
+use crate::keys::{read_block, Key};
+use crate::padding::Padding;
+use rand::RngCore;
+
+const MAGIC: &[u8; 4] = b"RSAH";
+const VERSION: u8 = 1;
+const CIPHER_CHACHA20_POLY1305: u8 = 1;
+const CHUNK_SIZE: u32 = 64 * 1024;
+/// Upper bound for the chunk size accepted from a header, to bound memory use.
+const MAX_CHUNK_SIZE: u32 = 16 * 1024 * 1024;
+
+/// Errors produced while reading an envelope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvelopeError {
+    /// The input does not start with a valid header.
+    InvalidHeader,
+    /// The header has a version this program does not know.
+    UnsupportedVersion(u8),
+    /// The header names an algorithm this program does not know.
+    UnsupportedAlgorithm,
+    /// The padding can not be used to wrap the content key.
+    UnsupportedPadding,
+    /// A chunk has been modified or the wrong key was used.
+    Authentication,
+    /// The input ended before the last chunk.
+    Truncated,
+}
+
+impl fmt::Display for EnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvelopeError::InvalidHeader => write!(f, "Invalid envelope header"),
+            EnvelopeError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported envelope version: {}", version)
+            }
+            EnvelopeError::UnsupportedAlgorithm => write!(f, "Unsupported envelope algorithm"),
+            EnvelopeError::UnsupportedPadding => {
+                write!(f, "Raw padding can not be used for hybrid encryption")
+            }
+            EnvelopeError::Authentication => write!(f, "Authentication failed"),
+            EnvelopeError::Truncated => write!(f, "Envelope is truncated"),
+        }
+    }
+}
+
+impl std::error::Error for EnvelopeError {}
+
+impl From<EnvelopeError> for io::Error {
+    fn from(err: EnvelopeError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Header {
+    padding: Padding,
+    chunk_size: u32,
+    wrapped_key: Vec<u8>,
+}
+
+impl Header {
+    fn to_bytes(&self) -> Vec<u8> {
+        let (padding_id, hash_id) = match &self.padding {
+            Padding::Oaep { hash, .. } => (1, hash.id()),
+            Padding::Pkcs1v15 => (2, 0),
+            Padding::Raw => unreachable!("checked when sealing"),
+        };
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[VERSION, padding_id, hash_id, CIPHER_CHACHA20_POLY1305]);
+        bytes.extend_from_slice(&self.chunk_size.to_be_bytes());
+        bytes.extend_from_slice(&(self.wrapped_key.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&self.wrapped_key);
+        bytes
+    }
+
+    /// Reads the header and returns it together with its bytes.
+    fn read(input: &mut dyn Read, label: &[u8]) -> Result<(Header, Vec<u8>)> {
+        let mut bytes = vec![0u8; 14];
+        if read_block(input, &mut bytes)? < bytes.len() || bytes[..4] != MAGIC[..] {
+            return Err(EnvelopeError::InvalidHeader.into());
+        }
+        if bytes[4] != VERSION {
+            return Err(EnvelopeError::UnsupportedVersion(bytes[4]).into());
+        }
+
+        let padding = match (bytes[5], HashAlgorithm::from_id(bytes[6])) {
+            (1, Some(hash)) => Padding::Oaep {
+                hash,
+                label: label.to_vec(),
+            },
+            (2, _) => Padding::Pkcs1v15,
+            _ => return Err(EnvelopeError::UnsupportedAlgorithm.into()),
+        };
+        if bytes[7] != CIPHER_CHACHA20_POLY1305 {
+            return Err(EnvelopeError::UnsupportedAlgorithm.into());
+        }
+
+        let chunk_size = u32::from_be_bytes(bytes[8..12].try_into().unwrap());
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(EnvelopeError::InvalidHeader.into());
+        }
+
+        let wrapped_len = u16::from_be_bytes([bytes[12], bytes[13]]) as usize;
+        let mut wrapped_key = vec![0u8; wrapped_len];
+        if read_block(input, &mut wrapped_key)? < wrapped_len {
+            return Err(EnvelopeError::InvalidHeader.into());
+        }
+        bytes.extend_from_slice(&wrapped_key);
+
+        Ok((
+            Header {
+                padding,
+                chunk_size,
+                wrapped_key,
+            },
+            bytes,
+        ))
+    }
+}
+
+fn chunk_nonce(index: u64, last: bool) -> [u8; NONCE_LEN] {
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce[3..11].copy_from_slice(&index.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+/// Encrypts the input into an envelope whose content key is wrapped with the key.
+pub(crate) fn seal(
+    key: &Key,
+    padding: &Padding,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+) -> Result<()> {
+    if *padding == Padding::Raw {
+        return Err(EnvelopeError::UnsupportedPadding.into());
+    }
+
+    let mut content_key = [0u8; KEY_LEN];
+    rand::thread_rng().fill_bytes(&mut content_key);
+
+    let header = Header {
+        padding: padding.clone(),
+        chunk_size: CHUNK_SIZE,
+        wrapped_key: key.encrypt_block(padding, &content_key)?,
+    }
+    .to_bytes();
+    output.write_all(&header)?;
+
+    let mut chunk = vec![0u8; CHUNK_SIZE as usize];
+    let mut index = 0u64;
+    loop {
+        let amount_of_bytes_read = read_block(input, &mut chunk)?;
+        let last = amount_of_bytes_read < chunk.len();
+
+        let data = &mut chunk[..amount_of_bytes_read];
+        let tag = chacha20poly1305::seal(&content_key, &chunk_nonce(index, last), &header, data);
+        output.write_all(data)?;
+        output.write_all(&tag)?;
+
+        if last {
+            break;
+        }
+        index += 1;
+    }
+
+    Ok(())
+}
+
+/// Decrypts an envelope written by `seal`.
+pub(crate) fn open(
+    key: &Key,
+    label: &[u8],
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+) -> Result<()> {
+    let (header, header_bytes) = Header::read(input, label)?;
+
+    let content_key: [u8; KEY_LEN] = key
+        .decrypt_block(&header.padding, &header.wrapped_key)?
+        .try_into()
+        .map_err(|_| EnvelopeError::Authentication)?;
+
+    let segment_size = header.chunk_size as usize + TAG_LEN;
+    let mut segment = vec![0u8; segment_size];
+    let mut index = 0u64;
+    loop {
+        let amount_of_bytes_read = read_block(input, &mut segment)?;
+        if amount_of_bytes_read < TAG_LEN {
+            return Err(EnvelopeError::Truncated.into());
+        }
+        let last = amount_of_bytes_read < segment_size;
+
+        let (data, tag) =
+            segment[..amount_of_bytes_read].split_at_mut(amount_of_bytes_read - TAG_LEN);
+        chacha20poly1305::open(
+            &content_key,
+            &chunk_nonce(index, last),
+            &header_bytes,
+            data,
+            tag,
+        )
+        .map_err(|_| EnvelopeError::Authentication)?;
+        output.write_all(data)?;
+
+        if last {
+            break;
+        }
+        index += 1;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::keys::{test_key_pair, KeyPair};
+
+    fn get_test_keys() -> KeyPair {
+        test_key_pair()
+    }
+
+    fn seal_to_vec(keys: &KeyPair, data: &[u8]) -> Vec<u8> {
+        let mut sealed = Vec::new();
+        seal(keys.public(), &Padding::oaep(), &mut &data[..], &mut sealed).unwrap();
+        sealed
+    }
+
+    fn open_error(keys: &KeyPair, sealed: &[u8]) -> EnvelopeError {
+        match open(keys.private(), b"", &mut &sealed[..], &mut Vec::new()) {
+            Err(Error::Envelope(err)) => err,
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn header_to_bytes_starts_with_magic_and_version() {
+        let header = Header {
+            padding: Padding::oaep(),
+            chunk_size: CHUNK_SIZE,
+            wrapped_key: vec![1, 2, 3],
+        };
+
+        let bytes = header.to_bytes();
+
+        assert_eq!(
+            b"RSAH\x01\x01\x01\x01\x00\x01\x00\x00\x00\x03\x01\x02\x03".to_vec(),
+            bytes
+        );
+    }
+
+    #[test]
+    fn open_returns_original_data_for_exact_chunk_multiple() {
+        let keys = get_test_keys();
+        let data = vec![5u8; 2 * CHUNK_SIZE as usize];
+
+        let sealed = seal_to_vec(&keys, &data);
+        let mut opened = Vec::new();
+        open(keys.private(), b"", &mut &sealed[..], &mut opened).unwrap();
+
+        assert_eq!(data, opened);
+    }
+
+    #[test]
+    fn open_returns_empty_data() {
+        let keys = get_test_keys();
+
+        let sealed = seal_to_vec(&keys, b"");
+        let mut opened = Vec::new();
+        open(keys.private(), b"", &mut &sealed[..], &mut opened).unwrap();
+
+        assert!(opened.is_empty());
+    }
+
+    #[test]
+    fn open_rejects_modified_chunk() {
+        let keys = get_test_keys();
+        let mut sealed = seal_to_vec(&keys, b"FooBarBaz");
+        let last = sealed.len() - 20;
+        sealed[last] ^= 1;
+
+        assert_eq!(EnvelopeError::Authentication, open_error(&keys, &sealed));
+    }
+
+    #[test]
+    fn open_rejects_truncated_envelope() {
+        let keys = get_test_keys();
+        let data = vec![5u8; CHUNK_SIZE as usize];
+        let mut sealed = seal_to_vec(&keys, &data);
+        sealed.truncate(sealed.len() - TAG_LEN);
+
+        assert_eq!(EnvelopeError::Truncated, open_error(&keys, &sealed));
+    }
+
+    #[test]
+    fn open_rejects_dropped_last_chunk() {
+        let keys = get_test_keys();
+        let data = vec![5u8; CHUNK_SIZE as usize + 10];
+        let mut sealed = seal_to_vec(&keys, &data);
+        sealed.truncate(sealed.len() - 10 - TAG_LEN);
+
+        assert_eq!(EnvelopeError::Truncated, open_error(&keys, &sealed));
+    }
+
+    #[test]
+    fn open_rejects_unknown_version() {
+        let keys = get_test_keys();
+        let mut sealed = seal_to_vec(&keys, b"FooBarBaz");
+        sealed[4] = 9;
+
+        assert_eq!(
+            EnvelopeError::UnsupportedVersion(9),
+            open_error(&keys, &sealed)
+        );
+    }
+
+    #[test]
+    fn open_rejects_other_input() {
+        let keys = get_test_keys();
+
+        assert_eq!(
+            EnvelopeError::InvalidHeader,
+            open_error(&keys, b"FooBarBaz")
+        );
+    }
+
+    #[test]
+    fn seal_rejects_raw_padding() {
+        let keys = get_test_keys();
+
+        assert!(seal(
+            keys.public(),
+            &Padding::Raw,
+            &mut &b"FooBarBaz"[..],
+            &mut Vec::new()
+        )
+        .is_err());
+    }
+}
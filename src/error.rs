@@ -0,0 +1,167 @@
+//! The error type returned by the public functions of the library.
+
+use std::fmt;
+use std::io;
+
+use crate::container::ContainerError;
+use crate::der::DerError;
+use crate::envelope::EnvelopeError;
+use crate::json::JsonError;
+use crate::jwt::JwtError;
+use crate::padding::PaddingError;
+use crate::pem::PemError;
+use crate::x509::CertificateError;
+
+/// Errors returned by the library, grouped by their cause.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file or stream failed.
+    Io(io::Error),
+    /// A key file or other input could not be parsed.
+    Parse(String),
+    /// The key can not be used, for example because its values are inconsistent.
+    InvalidKey(String),
+    /// A block could not be decrypted because its padding is invalid.
+    Padding(PaddingError),
+    /// A hybrid encrypted file could not be decrypted.
+    Envelope(EnvelopeError),
+    /// The header of an encrypted file is invalid or names another key.
+    Container(ContainerError),
+    /// The key or the message has an unsupported size.
+    Size(String),
+    /// An encrypted private key could not be decrypted with the passphrase.
+    Passphrase(String),
+    /// A JSON Web Token is malformed, has an invalid signature or was rejected
+    /// because of its claims.
+    Jwt(JwtError),
+    /// A certificate or a certificate signing request was rejected.
+    Certificate(CertificateError),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "I/O error: {}", err),
+            Error::Parse(msg) => write!(f, "Parse error: {}", msg),
+            Error::InvalidKey(msg) => write!(f, "Invalid key: {}", msg),
+            Error::Padding(err) => write!(f, "{}", err),
+            Error::Envelope(err) => write!(f, "{}", err),
+            Error::Container(err) => write!(f, "{}", err),
+            Error::Size(msg) => write!(f, "{}", msg),
+            Error::Passphrase(msg) => write!(f, "{}", msg),
+            Error::Jwt(err) => write!(f, "{}", err),
+            Error::Certificate(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Padding(err) => Some(err),
+            Error::Envelope(err) => Some(err),
+            Error::Container(err) => Some(err),
+            Error::Jwt(err) => Some(err),
+            Error::Certificate(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    /// Invalid armor is reported by `armor::ArmorReader` as an I/O error, but is
+    /// a parse error.
+    fn from(err: io::Error) -> Self {
+        if err.get_ref().is_some_and(|inner| inner.is::<PemError>()) {
+            return Error::Parse(err.to_string());
+        }
+        Error::Io(err)
+    }
+}
+
+impl From<PaddingError> for Error {
+    fn from(err: PaddingError) -> Self {
+        match err {
+            PaddingError::Decryption | PaddingError::Truncated => Error::Padding(err),
+            PaddingError::MessageTooLong | PaddingError::KeyTooSmall => {
+                Error::Size(err.to_string())
+            }
+        }
+    }
+}
+
+impl From<EnvelopeError> for Error {
+    fn from(err: EnvelopeError) -> Self {
+        Error::Envelope(err)
+    }
+}
+
+impl From<ContainerError> for Error {
+    fn from(err: ContainerError) -> Self {
+        Error::Container(err)
+    }
+}
+
+impl From<JwtError> for Error {
+    fn from(err: JwtError) -> Self {
+        Error::Jwt(err)
+    }
+}
+
+impl From<CertificateError> for Error {
+    fn from(err: CertificateError) -> Self {
+        Error::Certificate(err)
+    }
+}
+
+impl From<DerError> for Error {
+    fn from(err: DerError) -> Self {
+        Error::Parse(err.to_string())
+    }
+}
+
+impl From<JsonError> for Error {
+    fn from(err: JsonError) -> Self {
+        Error::Parse(err.to_string())
+    }
+}
+
+impl From<PemError> for Error {
+    fn from(err: PemError) -> Self {
+        Error::Parse(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn padding_size_errors_are_size_errors() {
+        assert!(matches!(
+            Error::from(PaddingError::MessageTooLong),
+            Error::Size(_)
+        ));
+        assert!(matches!(
+            Error::from(PaddingError::Decryption),
+            Error::Padding(PaddingError::Decryption)
+        ));
+    }
+
+    #[test]
+    fn pem_errors_in_io_errors_are_parse_errors() {
+        let err = io::Error::new(io::ErrorKind::InvalidData, PemError::MissingEnd);
+
+        assert!(matches!(Error::from(err), Error::Parse(_)));
+    }
+
+    #[test]
+    fn display_includes_cause() {
+        let err = Error::from(io::Error::new(io::ErrorKind::NotFound, "key.public"));
+
+        assert_eq!("I/O error: key.public", err.to_string());
+    }
+}
@@ -0,0 +1,701 @@
+use std::fmt;
+use std::io::{self, Read};
+use std::str::FromStr;
+
+/// Common interface for the hash functions implemented in this module.
+pub trait Digest {
+    /// Feeds more data into the hash.
+    fn update(&mut self, data: &[u8]);
+
+    /// Returns the hash of all data fed so far and resets the state.
+    fn finalize(&mut self) -> Vec<u8>;
+
+    /// Length of the hash output in bytes.
+    fn output_size(&self) -> usize;
+
+    /// Returns a copy of the current state, so that a common prefix only has to
+    /// be hashed once.
+    fn box_clone(&self) -> Box<dyn Digest>;
+}
+
+/// Hash functions that can be selected at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    /// Returns a new hasher for the algorithm.
+    pub fn hasher(&self) -> Box<dyn Digest> {
+        match self {
+            HashAlgorithm::Sha256 => Box::new(Sha256::new()),
+            HashAlgorithm::Sha384 => Box::new(Sha384::new()),
+            HashAlgorithm::Sha512 => Box::new(Sha512::new()),
+        }
+    }
+
+    /// Hashes the given data in one go.
+    pub fn digest(&self, data: &[u8]) -> Vec<u8> {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        hasher.finalize()
+    }
+
+    /// Length of the hash output in bytes.
+    pub fn output_size(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha256 => 32,
+            HashAlgorithm::Sha384 => 48,
+            HashAlgorithm::Sha512 => 64,
+        }
+    }
+
+    /// Length of the blocks the hash function processes, in bytes.
+    pub fn block_size(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha256 => 64,
+            HashAlgorithm::Sha384 | HashAlgorithm::Sha512 => 128,
+        }
+    }
+
+    /// Identifier used for the algorithm in the binary file formats of this crate.
+    pub(crate) fn id(&self) -> u8 {
+        match self {
+            HashAlgorithm::Sha256 => 1,
+            HashAlgorithm::Sha384 => 2,
+            HashAlgorithm::Sha512 => 3,
+        }
+    }
+
+    /// Returns the algorithm for an identifier returned by `id`.
+    pub(crate) fn from_id(id: u8) -> Option<HashAlgorithm> {
+        match id {
+            1 => Some(HashAlgorithm::Sha256),
+            2 => Some(HashAlgorithm::Sha384),
+            3 => Some(HashAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    /// Hashes everything read from the input.
+    pub fn digest_reader(&self, input: &mut dyn Read) -> io::Result<Vec<u8>> {
+        let mut hasher = self.hasher();
+        let mut buf = vec![0u8; 8192];
+        loop {
+            match input.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => hasher.update(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(hasher.finalize())
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashAlgorithm::Sha256 => write!(f, "sha256"),
+            HashAlgorithm::Sha384 => write!(f, "sha384"),
+            HashAlgorithm::Sha512 => write!(f, "sha512"),
+        }
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sha256" | "sha-256" => Ok(HashAlgorithm::Sha256),
+            "sha384" | "sha-384" => Ok(HashAlgorithm::Sha384),
+            "sha512" | "sha-512" => Ok(HashAlgorithm::Sha512),
+            _ => Err(format!("Unknown hash algorithm: {}", s)),
+        }
+    }
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const SHA256_H: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// SHA-256 as specified in FIPS 180-4.
+#[derive(Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    buffer: [u8; 64],
+    buffer_len: usize,
+    length: u64,
+}
+
+impl Sha256 {
+    pub fn new() -> Sha256 {
+        Sha256 {
+            state: SHA256_H,
+            buffer: [0u8; 64],
+            buffer_len: 0,
+            length: 0,
+        }
+    }
+
+    fn compress(&mut self, block: &[u8]) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(SHA256_K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Sha256::new()
+    }
+}
+
+impl Digest for Sha256 {
+    fn update(&mut self, mut data: &[u8]) {
+        self.length += data.len() as u64;
+
+        if self.buffer_len > 0 {
+            let take = data.len().min(64 - self.buffer_len);
+            self.buffer[self.buffer_len..self.buffer_len + take].copy_from_slice(&data[..take]);
+            self.buffer_len += take;
+            data = &data[take..];
+
+            if self.buffer_len < 64 {
+                return;
+            }
+            let block = self.buffer;
+            self.compress(&block);
+            self.buffer_len = 0;
+        }
+
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            self.compress(block);
+        }
+        let rest = blocks.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffer_len = rest.len();
+    }
+
+    fn finalize(&mut self) -> Vec<u8> {
+        let bit_length = self.length.wrapping_mul(8);
+
+        // Append the 1 bit, then zeros until 8 bytes are left in the block for the length.
+        let mut padding = vec![0x80u8];
+        let padded_len = (self.buffer_len + 1) % 64;
+        let zeros = if padded_len <= 56 {
+            56 - padded_len
+        } else {
+            120 - padded_len
+        };
+        padding.resize(1 + zeros, 0);
+        padding.extend_from_slice(&bit_length.to_be_bytes());
+        self.update(&padding);
+
+        let result = self
+            .state
+            .iter()
+            .flat_map(|word| word.to_be_bytes())
+            .collect();
+        *self = Sha256::new();
+        result
+    }
+
+    fn output_size(&self) -> usize {
+        32
+    }
+
+    fn box_clone(&self) -> Box<dyn Digest> {
+        Box::new(self.clone())
+    }
+}
+
+const SHA512_K: [u64; 80] = [
+    0x428a2f98d728ae22,
+    0x7137449123ef65cd,
+    0xb5c0fbcfec4d3b2f,
+    0xe9b5dba58189dbbc,
+    0x3956c25bf348b538,
+    0x59f111f1b605d019,
+    0x923f82a4af194f9b,
+    0xab1c5ed5da6d8118,
+    0xd807aa98a3030242,
+    0x12835b0145706fbe,
+    0x243185be4ee4b28c,
+    0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f,
+    0x80deb1fe3b1696b1,
+    0x9bdc06a725c71235,
+    0xc19bf174cf692694,
+    0xe49b69c19ef14ad2,
+    0xefbe4786384f25e3,
+    0x0fc19dc68b8cd5b5,
+    0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275,
+    0x4a7484aa6ea6e483,
+    0x5cb0a9dcbd41fbd4,
+    0x76f988da831153b5,
+    0x983e5152ee66dfab,
+    0xa831c66d2db43210,
+    0xb00327c898fb213f,
+    0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2,
+    0xd5a79147930aa725,
+    0x06ca6351e003826f,
+    0x142929670a0e6e70,
+    0x27b70a8546d22ffc,
+    0x2e1b21385c26c926,
+    0x4d2c6dfc5ac42aed,
+    0x53380d139d95b3df,
+    0x650a73548baf63de,
+    0x766a0abb3c77b2a8,
+    0x81c2c92e47edaee6,
+    0x92722c851482353b,
+    0xa2bfe8a14cf10364,
+    0xa81a664bbc423001,
+    0xc24b8b70d0f89791,
+    0xc76c51a30654be30,
+    0xd192e819d6ef5218,
+    0xd69906245565a910,
+    0xf40e35855771202a,
+    0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8,
+    0x1e376c085141ab53,
+    0x2748774cdf8eeb99,
+    0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63,
+    0x4ed8aa4ae3418acb,
+    0x5b9cca4f7763e373,
+    0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc,
+    0x78a5636f43172f60,
+    0x84c87814a1f0ab72,
+    0x8cc702081a6439ec,
+    0x90befffa23631e28,
+    0xa4506cebde82bde9,
+    0xbef9a3f7b2c67915,
+    0xc67178f2e372532b,
+    0xca273eceea26619c,
+    0xd186b8c721c0c207,
+    0xeada7dd6cde0eb1e,
+    0xf57d4f7fee6ed178,
+    0x06f067aa72176fba,
+    0x0a637dc5a2c898a6,
+    0x113f9804bef90dae,
+    0x1b710b35131c471b,
+    0x28db77f523047d84,
+    0x32caab7b40c72493,
+    0x3c9ebe0a15c9bebc,
+    0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6,
+    0x597f299cfc657e2a,
+    0x5fcb6fab3ad6faec,
+    0x6c44198c4a475817,
+];
+
+const SHA512_H: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+const SHA384_H: [u64; 8] = [
+    0xcbbb9d5dc1059ed8,
+    0x629a292a367cd507,
+    0x9159015a3070dd17,
+    0x152fecd8f70e5939,
+    0x67332667ffc00b31,
+    0x8eb44a8768581511,
+    0xdb0c2e0d64f98fa7,
+    0x47b5481dbefa4fa4,
+];
+
+/// Shared implementation of SHA-512 and SHA-384, which only differ by
+/// their initial values and the length of the output.
+#[derive(Clone)]
+struct Sha512Core {
+    state: [u64; 8],
+    initial: [u64; 8],
+    buffer: [u8; 128],
+    buffer_len: usize,
+    length: u128,
+}
+
+impl Sha512Core {
+    fn new(initial: [u64; 8]) -> Sha512Core {
+        Sha512Core {
+            state: initial,
+            initial,
+            buffer: [0u8; 128],
+            buffer_len: 0,
+            length: 0,
+        }
+    }
+
+    fn compress(&mut self, block: &[u8]) {
+        let mut w = [0u64; 80];
+        for (i, word) in block.chunks_exact(8).enumerate() {
+            w[i] = u64::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..80 {
+            let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^ (w[i - 15] >> 7);
+            let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^ (w[i - 2] >> 6);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..80 {
+            let s1 = e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(SHA512_K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.length += data.len() as u128;
+
+        if self.buffer_len > 0 {
+            let take = data.len().min(128 - self.buffer_len);
+            self.buffer[self.buffer_len..self.buffer_len + take].copy_from_slice(&data[..take]);
+            self.buffer_len += take;
+            data = &data[take..];
+
+            if self.buffer_len < 128 {
+                return;
+            }
+            let block = self.buffer;
+            self.compress(&block);
+            self.buffer_len = 0;
+        }
+
+        let mut blocks = data.chunks_exact(128);
+        for block in &mut blocks {
+            self.compress(block);
+        }
+        let rest = blocks.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffer_len = rest.len();
+    }
+
+    fn finalize(&mut self, output_size: usize) -> Vec<u8> {
+        let bit_length = self.length.wrapping_mul(8);
+
+        // Append the 1 bit, then zeros until 16 bytes are left in the block for the length.
+        let mut padding = vec![0x80u8];
+        let padded_len = (self.buffer_len + 1) % 128;
+        let zeros = if padded_len <= 112 {
+            112 - padded_len
+        } else {
+            240 - padded_len
+        };
+        padding.resize(1 + zeros, 0);
+        padding.extend_from_slice(&bit_length.to_be_bytes());
+        self.update(&padding);
+
+        let mut result: Vec<u8> = self
+            .state
+            .iter()
+            .flat_map(|word| word.to_be_bytes())
+            .collect();
+        result.truncate(output_size);
+        *self = Sha512Core::new(self.initial);
+        result
+    }
+}
+
+/// SHA-512 as specified in FIPS 180-4.
+#[derive(Clone)]
+pub struct Sha512(Sha512Core);
+
+impl Sha512 {
+    pub fn new() -> Sha512 {
+        Sha512(Sha512Core::new(SHA512_H))
+    }
+}
+
+impl Default for Sha512 {
+    fn default() -> Self {
+        Sha512::new()
+    }
+}
+
+impl Digest for Sha512 {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(&mut self) -> Vec<u8> {
+        self.0.finalize(64)
+    }
+
+    fn output_size(&self) -> usize {
+        64
+    }
+
+    fn box_clone(&self) -> Box<dyn Digest> {
+        Box::new(self.clone())
+    }
+}
+
+/// SHA-384 as specified in FIPS 180-4.
+#[derive(Clone)]
+pub struct Sha384(Sha512Core);
+
+impl Sha384 {
+    pub fn new() -> Sha384 {
+        Sha384(Sha512Core::new(SHA384_H))
+    }
+}
+
+impl Default for Sha384 {
+    fn default() -> Self {
+        Sha384::new()
+    }
+}
+
+impl Digest for Sha384 {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(&mut self) -> Vec<u8> {
+        self.0.finalize(48)
+    }
+
+    fn output_size(&self) -> usize {
+        48
+    }
+
+    fn box_clone(&self) -> Box<dyn Digest> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    const TWO_BLOCK_INPUT_1024: &[u8] = b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu";
+
+    #[test]
+    fn sha256_returns_correct_hash_for_empty_input() {
+        assert_eq!(
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            to_hex(&HashAlgorithm::Sha256.digest(b""))
+        );
+    }
+
+    // test vectors from https://csrc.nist.gov/projects/cryptographic-standards-and-guidelines/example-values
+    #[test]
+    fn sha256_returns_correct_hash_for_short_input() {
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            to_hex(&HashAlgorithm::Sha256.digest(b"abc"))
+        );
+    }
+
+    #[test]
+    fn sha256_returns_correct_hash_for_two_block_input() {
+        assert_eq!(
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            to_hex(
+                &HashAlgorithm::Sha256
+                    .digest(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")
+            )
+        );
+    }
+
+    #[test]
+    fn sha256_returns_same_hash_for_streamed_input() {
+        let data = vec![b'a'; 1_000_000];
+
+        let mut hasher = Sha256::new();
+        for chunk in data.chunks(997) {
+            hasher.update(chunk);
+        }
+
+        assert_eq!(
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0",
+            to_hex(&hasher.finalize())
+        );
+    }
+
+    #[test]
+    fn finalize_resets_the_state() {
+        let mut hasher = Sha256::new();
+        hasher.update(b"abc");
+        let first = hasher.finalize();
+        hasher.update(b"abc");
+
+        assert_eq!(first, hasher.finalize());
+    }
+
+    #[test]
+    fn sha384_returns_correct_hash_for_empty_input() {
+        assert_eq!(
+            "38b060a751ac96384cd9327eb1b1e36a21fdb71114be07434c0cc7bf63f6e1da274edebfe76f65fbd51ad2f14898b95b",
+            to_hex(&HashAlgorithm::Sha384.digest(b""))
+        );
+    }
+
+    #[test]
+    fn sha384_returns_correct_hash_for_short_input() {
+        assert_eq!(
+            "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7",
+            to_hex(&HashAlgorithm::Sha384.digest(b"abc"))
+        );
+    }
+
+    #[test]
+    fn sha384_returns_correct_hash_for_two_block_input() {
+        assert_eq!(
+            "09330c33f71147e83d192fc782cd1b4753111b173b3b05d22fa08086e3b0f712fcc7c71a557e2db966c3e9fa91746039",
+            to_hex(&HashAlgorithm::Sha384.digest(TWO_BLOCK_INPUT_1024))
+        );
+    }
+
+    #[test]
+    fn sha512_returns_correct_hash_for_empty_input() {
+        assert_eq!(
+            "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e",
+            to_hex(&HashAlgorithm::Sha512.digest(b""))
+        );
+    }
+
+    #[test]
+    fn sha512_returns_correct_hash_for_short_input() {
+        assert_eq!(
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+            to_hex(&HashAlgorithm::Sha512.digest(b"abc"))
+        );
+    }
+
+    #[test]
+    fn sha512_returns_correct_hash_for_two_block_input() {
+        assert_eq!(
+            "8e959b75dae313da8cf4f72814fc143f8f7779c6eb9f7fa17299aeadb6889018501d289e4900f7e4331b99dec4b5433ac7d329eeb6dd26545e96e55b874be909",
+            to_hex(&HashAlgorithm::Sha512.digest(TWO_BLOCK_INPUT_1024))
+        );
+    }
+
+    #[test]
+    fn sha512_returns_same_hash_for_streamed_input() {
+        let data = vec![b'a'; 1_000_000];
+
+        let mut hasher = Sha512::new();
+        for chunk in data.chunks(997) {
+            hasher.update(chunk);
+        }
+
+        assert_eq!(
+            "e718483d0ce769644e2e42c7bc15b4638e1f98b13b2044285632a803afa973ebde0ff244877ea60a4cb0432ce577c31beb009c5c2c49aa2e4eadb217ad8cc09b",
+            to_hex(&hasher.finalize())
+        );
+    }
+
+    #[test]
+    fn digest_reader_returns_same_hash_as_digest() {
+        let data = vec![b'x'; 20_000];
+
+        for hash in [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha384,
+            HashAlgorithm::Sha512,
+        ] {
+            assert_eq!(
+                hash.digest(&data),
+                hash.digest_reader(&mut &data[..]).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn hash_algorithm_parses_names() {
+        assert_eq!(Ok(HashAlgorithm::Sha384), "SHA-384".parse());
+        assert_eq!(Ok(HashAlgorithm::Sha512), "sha512".parse());
+        assert!("md5".parse::<HashAlgorithm>().is_err());
+    }
+}
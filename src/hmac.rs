@@ -0,0 +1,143 @@
+//! HMAC (RFC 2104) and the PBKDF2 key derivation function (RFC 8018, section 5.2)
+//! built on the hash functions of `hash`.
+
+use crate::hash::{Digest, HashAlgorithm};
+
+/// A hash function keyed for HMAC. The padded key is hashed once when the
+/// `Hmac` is created, and every MAC continues from copies of those states.
+pub struct Hmac {
+    inner: Box<dyn Digest>,
+    outer: Box<dyn Digest>,
+}
+
+impl Hmac {
+    pub fn new(hash: HashAlgorithm, key: &[u8]) -> Hmac {
+        let mut block = vec![0u8; hash.block_size()];
+        if key.len() > block.len() {
+            let hashed = hash.digest(key);
+            block[..hashed.len()].copy_from_slice(&hashed);
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner = hash.hasher();
+        inner.update(&block.iter().map(|byte| byte ^ 0x36).collect::<Vec<u8>>());
+        let mut outer = hash.hasher();
+        outer.update(&block.iter().map(|byte| byte ^ 0x5c).collect::<Vec<u8>>());
+        Hmac { inner, outer }
+    }
+
+    /// Returns the MAC of the message.
+    pub fn mac(&self, message: &[u8]) -> Vec<u8> {
+        let mut inner = self.inner.box_clone();
+        inner.update(message);
+        let mut outer = self.outer.box_clone();
+        outer.update(&inner.finalize());
+        outer.finalize()
+    }
+}
+
+/// Returns the HMAC of the message with the given key.
+pub fn hmac(hash: HashAlgorithm, key: &[u8], message: &[u8]) -> Vec<u8> {
+    Hmac::new(hash, key).mac(message)
+}
+
+/// Derives a key of the given length from a password with PBKDF2, using HMAC
+/// with the given hash function as the pseudorandom function.
+pub fn pbkdf2(
+    hash: HashAlgorithm,
+    password: &[u8],
+    salt: &[u8],
+    iterations: u32,
+    length: usize,
+) -> Vec<u8> {
+    let prf = Hmac::new(hash, password);
+    let mut key = Vec::with_capacity(length);
+
+    let mut block_index: u32 = 1;
+    while key.len() < length {
+        let mut u = prf.mac(&[salt, &block_index.to_be_bytes()].concat());
+        let mut block = u.clone();
+        for _ in 1..iterations {
+            u = prf.mac(&u);
+            for (byte, u_byte) in block.iter_mut().zip(&u) {
+                *byte ^= u_byte;
+            }
+        }
+
+        let take = block.len().min(length - key.len());
+        key.extend_from_slice(&block[..take]);
+        block_index += 1;
+    }
+
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    // test vectors from RFC 4231
+    #[test]
+    fn hmac_sha256_returns_correct_mac() {
+        assert_eq!(
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            to_hex(&hmac(HashAlgorithm::Sha256, &[0x0b; 20], b"Hi There"))
+        );
+        assert_eq!(
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            to_hex(&hmac(
+                HashAlgorithm::Sha256,
+                b"Jefe",
+                b"what do ya want for nothing?"
+            ))
+        );
+    }
+
+    #[test]
+    fn hmac_hashes_long_keys() {
+        assert_eq!(
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            to_hex(&hmac(
+                HashAlgorithm::Sha256,
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            ))
+        );
+    }
+
+    #[test]
+    fn hmac_sha512_returns_correct_mac() {
+        assert_eq!(
+            "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737",
+            to_hex(&hmac(
+                HashAlgorithm::Sha512,
+                b"Jefe",
+                b"what do ya want for nothing?"
+            ))
+        );
+    }
+
+    // test vectors from RFC 7914, section 11
+    #[test]
+    fn pbkdf2_sha256_derives_correct_key() {
+        assert_eq!(
+            "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783",
+            to_hex(&pbkdf2(HashAlgorithm::Sha256, b"passwd", b"salt", 1, 64))
+        );
+        assert_eq!(
+            "4ddcd8f60b98be21830cee5ef22701f9641a4418d04c0414aeff08876b34ab56a1d425a1225833549adb841b51c9b3176a272bdebba1d078478f62b397f33c8d",
+            to_hex(&pbkdf2(
+                HashAlgorithm::Sha256,
+                b"Password",
+                b"NaCl",
+                80000,
+                64
+            ))
+        );
+    }
+}
@@ -0,0 +1,461 @@
+//! A minimal JSON (RFC 8259) parser and writer, used for JSON Web Keys and the
+//! machine readable output of the CLI.
+//!
+//! Numbers are kept as their text, since the values of interest are either
+//! small or big integers that do not fit into a float. Objects keep the order
+//! of their members, and duplicate member names are rejected.
+
+use std::fmt;
+
+/// Objects and arrays can not be nested deeper than this.
+const MAX_DEPTH: usize = 64;
+
+/// Errors produced while parsing JSON.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonError {
+    /// The input ended in the middle of a value.
+    UnexpectedEnd,
+    /// The character at the byte offset is not valid there.
+    UnexpectedCharacter(usize),
+    /// A string contains an invalid escape sequence or control character.
+    InvalidString,
+    /// An object contains the same member name twice.
+    DuplicateKey(String),
+    /// Objects and arrays are nested too deeply.
+    TooDeep,
+    /// There was data left after the value.
+    TrailingData,
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::UnexpectedEnd => write!(f, "Unexpected end of JSON data"),
+            JsonError::UnexpectedCharacter(position) => {
+                write!(
+                    f,
+                    "Unexpected character in JSON data at offset {}",
+                    position
+                )
+            }
+            JsonError::InvalidString => write!(f, "Invalid JSON string"),
+            JsonError::DuplicateKey(key) => write!(f, "Duplicate JSON member: {}", key),
+            JsonError::TooDeep => write!(f, "JSON data is nested too deeply"),
+            JsonError::TrailingData => write!(f, "Trailing data after JSON value"),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+/// A JSON value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    /// A number as it was written.
+    Number(String),
+    String(String),
+    Array(Vec<Value>),
+    /// The members of an object in their original order.
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Creates a string value.
+    pub fn string(value: &str) -> Value {
+        Value::String(value.to_string())
+    }
+
+    /// Creates an object from its members.
+    pub fn object(members: Vec<(&str, Value)>) -> Value {
+        Value::Object(
+            members
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// Returns the member of an object with the given name.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members
+                .iter()
+                .find(|(member, _)| member == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Sets the member of an object with the given name, replacing an existing
+    /// member. Does nothing if the value is not an object.
+    pub fn insert(&mut self, name: &str, value: Value) {
+        if let Value::Object(members) = self {
+            match members.iter_mut().find(|(member, _)| member == name) {
+                Some((_, existing)) => *existing = value,
+                None => members.push((name.to_string(), value)),
+            }
+        }
+    }
+
+    /// Returns the value of a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the elements of an array.
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+/// Writes the value without any whitespace.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("null"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Number(value) => f.write_str(value),
+            Value::String(value) => write_string(f, value),
+            Value::Array(values) => {
+                f.write_str("[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_str("]")
+            }
+            Value::Object(members) => {
+                f.write_str("{")?;
+                for (i, (name, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+/// Parses a JSON text. Whitespace around the value is allowed.
+pub fn parse(text: &str) -> Result<Value, JsonError> {
+    let mut parser = Parser {
+        text: text.as_bytes(),
+        position: 0,
+    };
+    let value = parser.value(0)?;
+    parser.skip_whitespace();
+    if parser.position != parser.text.len() {
+        return Err(JsonError::TrailingData);
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.text.get(self.position) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Result<u8, JsonError> {
+        self.text
+            .get(self.position)
+            .copied()
+            .ok_or(JsonError::UnexpectedEnd)
+    }
+
+    fn next(&mut self) -> Result<u8, JsonError> {
+        let byte = self.peek()?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), JsonError> {
+        match self.next()? {
+            byte if byte == expected => Ok(()),
+            _ => Err(JsonError::UnexpectedCharacter(self.position - 1)),
+        }
+    }
+
+    fn literal(&mut self, literal: &str, value: Value) -> Result<Value, JsonError> {
+        for byte in literal.bytes() {
+            self.expect(byte)?;
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, JsonError> {
+        if depth > MAX_DEPTH {
+            return Err(JsonError::TooDeep);
+        }
+        self.skip_whitespace();
+        match self.peek()? {
+            b'n' => self.literal("null", Value::Null),
+            b't' => self.literal("true", Value::Bool(true)),
+            b'f' => self.literal("false", Value::Bool(false)),
+            b'"' => Ok(Value::String(self.string()?)),
+            b'[' => self.array(depth),
+            b'{' => self.object(depth),
+            b'-' | b'0'..=b'9' => self.number(),
+            _ => Err(JsonError::UnexpectedCharacter(self.position)),
+        }
+    }
+
+    fn digits(&mut self) -> Result<usize, JsonError> {
+        let start = self.position;
+        while self.text.get(self.position).is_some_and(u8::is_ascii_digit) {
+            self.position += 1;
+        }
+        match self.position - start {
+            0 if self.position == self.text.len() => Err(JsonError::UnexpectedEnd),
+            0 => Err(JsonError::UnexpectedCharacter(self.position)),
+            count => Ok(count),
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, JsonError> {
+        let start = self.position;
+        if self.peek()? == b'-' {
+            self.position += 1;
+        }
+        let integer_start = self.position;
+        if self.digits()? > 1 && self.text[integer_start] == b'0' {
+            return Err(JsonError::UnexpectedCharacter(integer_start));
+        }
+        if self.text.get(self.position) == Some(&b'.') {
+            self.position += 1;
+            self.digits()?;
+        }
+        if let Some(b'e' | b'E') = self.text.get(self.position) {
+            self.position += 1;
+            if let Some(b'+' | b'-') = self.text.get(self.position) {
+                self.position += 1;
+            }
+            self.digits()?;
+        }
+        let number = std::str::from_utf8(&self.text[start..self.position]).unwrap();
+        Ok(Value::Number(number.to_string()))
+    }
+
+    fn hex4(&mut self) -> Result<u32, JsonError> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let digit = (self.next()? as char)
+                .to_digit(16)
+                .ok_or(JsonError::InvalidString)?;
+            value = value << 4 | digit;
+        }
+        Ok(value)
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+        loop {
+            match self.next()? {
+                b'"' => break,
+                b'\\' => {
+                    let c = match self.next()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let high = self.hex4()?;
+                            let code = if (0xd800..0xdc00).contains(&high) {
+                                self.expect(b'\\').map_err(|_| JsonError::InvalidString)?;
+                                self.expect(b'u').map_err(|_| JsonError::InvalidString)?;
+                                let low = self.hex4()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(JsonError::InvalidString);
+                                }
+                                0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                            } else {
+                                high
+                            };
+                            char::from_u32(code).ok_or(JsonError::InvalidString)?
+                        }
+                        _ => return Err(JsonError::InvalidString),
+                    };
+                    bytes.extend_from_slice(c.encode_utf8(&mut [0u8; 4]).as_bytes());
+                }
+                byte if byte < 0x20 => return Err(JsonError::InvalidString),
+                byte => bytes.push(byte),
+            }
+        }
+        // The input is a str and escapes are encoded as UTF-8, so this only
+        // fails if a string is split in the middle of a character.
+        String::from_utf8(bytes).map_err(|_| JsonError::InvalidString)
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Value, JsonError> {
+        self.expect(b'[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.peek()? == b']' {
+            self.position += 1;
+            return Ok(Value::Array(values));
+        }
+        loop {
+            values.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+            match self.next()? {
+                b',' => continue,
+                b']' => return Ok(Value::Array(values)),
+                _ => return Err(JsonError::UnexpectedCharacter(self.position - 1)),
+            }
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Value, JsonError> {
+        self.expect(b'{')?;
+        let mut members: Vec<(String, Value)> = Vec::new();
+        self.skip_whitespace();
+        if self.peek()? == b'}' {
+            self.position += 1;
+            return Ok(Value::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let name = self.string()?;
+            if members.iter().any(|(member, _)| *member == name) {
+                return Err(JsonError::DuplicateKey(name));
+            }
+            self.skip_whitespace();
+            self.expect(b':')?;
+            members.push((name, self.value(depth + 1)?));
+            self.skip_whitespace();
+            match self.next()? {
+                b',' => continue,
+                b'}' => return Ok(Value::Object(members)),
+                _ => return Err(JsonError::UnexpectedCharacter(self.position - 1)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_returns_values() {
+        let value = parse(
+            " {\"a\": [1, -2.5e+3, true, false, null], \"b\": {\"c\": \"d\\n\\u00e9\\ud83d\\ude00\"}} ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            Value::object(vec![
+                (
+                    "a",
+                    Value::Array(vec![
+                        Value::Number("1".to_string()),
+                        Value::Number("-2.5e+3".to_string()),
+                        Value::Bool(true),
+                        Value::Bool(false),
+                        Value::Null,
+                    ])
+                ),
+                ("b", Value::object(vec![("c", Value::string("d\né😀"))])),
+            ]),
+            value
+        );
+        assert_eq!(
+            Some("d\né😀"),
+            value.get("b").unwrap().get("c").unwrap().as_str()
+        );
+    }
+
+    #[test]
+    fn display_writes_parsable_json() {
+        let value = Value::object(vec![
+            (
+                "name",
+                Value::string("quote \" backslash \\ tab \t bell \u{7}"),
+            ),
+            (
+                "list",
+                Value::Array(vec![Value::Null, Value::Number("0".to_string())]),
+            ),
+            ("empty", Value::Object(Vec::new())),
+        ]);
+        let text = value.to_string();
+
+        assert_eq!(
+            "{\"name\":\"quote \\\" backslash \\\\ tab \\t bell \\u0007\",\"list\":[null,0],\"empty\":{}}",
+            text
+        );
+        assert_eq!(Ok(value), parse(&text));
+    }
+
+    #[test]
+    fn insert_replaces_existing_member() {
+        let mut value = Value::object(vec![("a", Value::Null), ("b", Value::Null)]);
+
+        value.insert("a", Value::Bool(true));
+        value.insert("c", Value::Bool(false));
+
+        assert_eq!("{\"a\":true,\"b\":null,\"c\":false}", value.to_string());
+    }
+
+    #[test]
+    fn parse_rejects_invalid_json() {
+        for (text, err) in [
+            ("", JsonError::UnexpectedEnd),
+            ("{\"a\":1,}", JsonError::UnexpectedCharacter(7)),
+            ("[1 2]", JsonError::UnexpectedCharacter(3)),
+            ("01", JsonError::UnexpectedCharacter(0)),
+            ("1.", JsonError::UnexpectedEnd),
+            ("\"a\nb\"", JsonError::InvalidString),
+            ("\"\\ud800\"", JsonError::InvalidString),
+            (
+                "{\"a\":1,\"a\":2}",
+                JsonError::DuplicateKey("a".to_string()),
+            ),
+            ("{} {}", JsonError::TrailingData),
+            ("nul", JsonError::UnexpectedEnd),
+        ] {
+            assert_eq!(Err(err), parse(text), "{}", text);
+        }
+        assert_eq!(Err(JsonError::TooDeep), parse(&"[".repeat(100)));
+    }
+}
@@ -0,0 +1,236 @@
+//! JSON Web Keys (RFC 7517) with the RSA parameters of RFC 7518, section 6.3,
+//! JWK Sets, and the JWK thumbprints of RFC 7638, which are used as `kid`.
+
+use crate::algorithms;
+use crate::encoding::{base64url_decode, base64url_encode};
+use crate::error::{self, Error};
+use crate::hash::HashAlgorithm;
+use crate::json::{self, Value};
+use crate::key_format::{DecodedKey, PrivateComponents};
+use num_bigint::{BigInt, Sign};
+
+const KEY_TYPE: &str = "RSA";
+
+/// Encodes a number as base64url without leading zero bytes.
+fn encode_uint(value: &BigInt) -> Value {
+    Value::String(base64url_encode(&value.to_bytes_be().1))
+}
+
+fn decode_uint(jwk: &Value, name: &str) -> error::Result<Option<BigInt>> {
+    let Some(value) = jwk.get(name) else {
+        return Ok(None);
+    };
+    let bytes = value
+        .as_str()
+        .and_then(base64url_decode)
+        .filter(|bytes| !bytes.is_empty())
+        .ok_or_else(|| Error::Parse(format!("Invalid JWK parameter: {}", name)))?;
+    Ok(Some(BigInt::from_bytes_be(Sign::Plus, &bytes)))
+}
+
+fn require_uint(jwk: &Value, name: &str) -> error::Result<BigInt> {
+    decode_uint(jwk, name)?.ok_or_else(|| Error::Parse(format!("Missing JWK parameter: {}", name)))
+}
+
+/// The JWK thumbprint of RFC 7638: the base64url encoded SHA-256 hash of the
+/// required members of the public key, sorted by name.
+pub(crate) fn thumbprint(n: &BigInt, e: &BigInt) -> String {
+    let canonical = Value::object(vec![
+        ("e", encode_uint(e)),
+        ("kty", Value::string(KEY_TYPE)),
+        ("n", encode_uint(n)),
+    ]);
+    base64url_encode(&HashAlgorithm::Sha256.digest(canonical.to_string().as_bytes()))
+}
+
+/// The JWK of a public key, with its thumbprint as `kid`.
+pub(crate) fn public_jwk(n: &BigInt, e: &BigInt) -> Value {
+    Value::object(vec![
+        ("kty", Value::string(KEY_TYPE)),
+        ("kid", Value::String(thumbprint(n, e))),
+        ("n", encode_uint(n)),
+        ("e", encode_uint(e)),
+    ])
+}
+
+/// The JWK of a private key, with its thumbprint as `kid`.
+pub(crate) fn private_jwk(key: &PrivateComponents) -> Value {
+    let mut jwk = public_jwk(&key.n, &key.e);
+    if let Value::Object(members) = &mut jwk {
+        for (name, value) in [
+            ("d", &key.d),
+            ("p", &key.p),
+            ("q", &key.q),
+            ("dp", &key.dp),
+            ("dq", &key.dq),
+            ("qi", &key.qinv),
+        ] {
+            members.push((name.to_string(), encode_uint(value)));
+        }
+    }
+    jwk
+}
+
+/// A JWK Set of the given keys.
+pub(crate) fn jwk_set(keys: Vec<Value>) -> Value {
+    Value::object(vec![("keys", Value::Array(keys))])
+}
+
+/// Returns true if the contents look like a JSON object.
+pub(crate) fn is_jwk(contents: &[u8]) -> bool {
+    contents
+        .iter()
+        .find(|byte| !byte.is_ascii_whitespace())
+        .is_some_and(|byte| *byte == b'{')
+}
+
+/// Decodes a JWK, or a JWK Set that contains exactly one RSA key. Private keys
+/// without the primes are accepted, the primes are recovered from the exponents.
+pub(crate) fn decode(text: &str) -> error::Result<DecodedKey> {
+    let value = json::parse(text)?;
+    let jwk = match value.get("keys") {
+        Some(keys) => {
+            let keys = keys
+                .as_array()
+                .ok_or_else(|| Error::Parse("Invalid JWK Set".to_string()))?;
+            let mut rsa_keys = keys
+                .iter()
+                .filter(|key| key.get("kty").and_then(Value::as_str) == Some(KEY_TYPE));
+            match (rsa_keys.next(), rsa_keys.next()) {
+                (Some(key), None) => key,
+                (None, _) => return Err(Error::Parse("The JWK Set has no RSA key".to_string())),
+                (Some(_), Some(_)) => {
+                    return Err(Error::Parse(
+                        "JWK Sets with several RSA keys are not supported".to_string(),
+                    ))
+                }
+            }
+        }
+        None => &value,
+    };
+
+    match jwk.get("kty").and_then(Value::as_str) {
+        Some(KEY_TYPE) => {}
+        Some(other) => return Err(Error::Parse(format!("Unsupported JWK key type: {}", other))),
+        None => return Err(Error::Parse("Missing JWK parameter: kty".to_string())),
+    }
+    if jwk.get("oth").is_some() {
+        return Err(Error::Parse(
+            "Keys with more than two primes are not supported".to_string(),
+        ));
+    }
+
+    let n = require_uint(jwk, "n")?;
+    let e = require_uint(jwk, "e")?;
+    let Some(d) = decode_uint(jwk, "d")? else {
+        return Ok(DecodedKey::Public { n, e });
+    };
+    let (p, q) = match (decode_uint(jwk, "p")?, decode_uint(jwk, "q")?) {
+        (Some(p), Some(q)) => (p, q),
+        _ => {
+            let (p, q) = algorithms::recover_primes(&n, &e, &d)
+                .ok_or_else(|| Error::InvalidKey("Failed to recover the primes".to_string()))?;
+            // The larger prime comes first, as in keys generated by OpenSSL.
+            match p > q {
+                true => (p, q),
+                false => (q, p),
+            }
+        }
+    };
+    let computed = PrivateComponents::from_primes(&n, &e, &d, &p, &q);
+    Ok(DecodedKey::Private(PrivateComponents {
+        dp: decode_uint(jwk, "dp")?.unwrap_or(computed.dp),
+        dq: decode_uint(jwk, "dq")?.unwrap_or(computed.dq),
+        qinv: decode_uint(jwk, "qi")?.unwrap_or(computed.qinv),
+        ..computed
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_test_components() -> PrivateComponents {
+        // example from https://en.wikipedia.org/wiki/RSA_(cryptosystem)
+        PrivateComponents::from_primes(
+            &BigInt::from(3233),
+            &BigInt::from(17),
+            &BigInt::from(413),
+            &BigInt::from(61),
+            &BigInt::from(53),
+        )
+    }
+
+    // example from RFC 7638, section 3.1
+    #[test]
+    fn thumbprint_matches_rfc_7638_example() {
+        let n = "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw";
+        let n = BigInt::from_bytes_be(Sign::Plus, &base64url_decode(n).unwrap());
+
+        assert_eq!(
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs",
+            thumbprint(&n, &BigInt::from(65537))
+        );
+    }
+
+    #[test]
+    fn decode_returns_encoded_keys() {
+        let key = get_test_components();
+        let public = public_jwk(&key.n, &key.e).to_string();
+        let private = private_jwk(&key).to_string();
+
+        assert!(public.starts_with("{\"kty\":\"RSA\",\"kid\":"));
+        assert!(public.ends_with(",\"n\":\"DKE\",\"e\":\"EQ\"}"));
+        assert!(is_jwk(b"\n {"));
+        assert_eq!(
+            DecodedKey::Public {
+                n: key.n.clone(),
+                e: key.e.clone()
+            },
+            decode(&public).unwrap()
+        );
+        assert_eq!(DecodedKey::Private(key), decode(&private).unwrap());
+    }
+
+    #[test]
+    fn decode_recovers_missing_primes() {
+        let key = get_test_components();
+        let jwk = "{\"kty\":\"RSA\",\"n\":\"DKE\",\"e\":\"EQ\",\"d\":\"AZ0\"}";
+
+        assert_eq!(DecodedKey::Private(key), decode(jwk).unwrap());
+    }
+
+    #[test]
+    fn decode_reads_set_with_one_rsa_key() {
+        let key = get_test_components();
+        let set = jwk_set(vec![
+            Value::object(vec![("kty", Value::string("OKP"))]),
+            public_jwk(&key.n, &key.e),
+        ]);
+
+        assert_eq!(
+            DecodedKey::Public { n: key.n, e: key.e },
+            decode(&set.to_string()).unwrap()
+        );
+        assert!(matches!(
+            decode(&jwk_set(vec![set.clone(), set]).to_string()),
+            Err(Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn decode_rejects_invalid_keys() {
+        for jwk in [
+            "{\"kty\":\"EC\",\"n\":\"DKE\",\"e\":\"EQ\"}",
+            "{\"n\":\"DKE\",\"e\":\"EQ\"}",
+            "{\"kty\":\"RSA\",\"n\":\"DKE\"}",
+            "{\"kty\":\"RSA\",\"n\":\"DK+\",\"e\":\"EQ\"}",
+            "{\"kty\":\"RSA\",\"n\":\"\",\"e\":\"EQ\"}",
+            "{\"kty\":\"RSA\",\"n\":\"DKE\",\"e\":\"EQ\",\"d\":\"AZ0\",\"oth\":[]}",
+            "{\"keys\":{}}",
+            "[]",
+        ] {
+            assert!(matches!(decode(jwk), Err(Error::Parse(_))), "{}", jwk);
+        }
+    }
+}
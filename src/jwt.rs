@@ -0,0 +1,485 @@
+//! JSON Web Tokens (RFC 7519) in the compact JWS serialization (RFC 7515),
+//! signed with the RSA algorithms of RFC 7518: RS256, RS384, RS512 and PS256.
+//!
+//! A token is `BASE64URL(header) "." BASE64URL(claims) "." BASE64URL(signature)`,
+//! where the signature is made over the first two parts.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::encoding::{base64url_decode, base64url_encode};
+use crate::error::{self, Error};
+use crate::hash::HashAlgorithm;
+use crate::json::{self, Value};
+use crate::keys::Key;
+use crate::signature::SignatureScheme;
+
+/// Signature algorithms of JSON Web Signatures supported for RSA keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// RSASSA-PKCS1-v1_5 with SHA-256.
+    Rs256,
+    /// RSASSA-PKCS1-v1_5 with SHA-384.
+    Rs384,
+    /// RSASSA-PKCS1-v1_5 with SHA-512.
+    Rs512,
+    /// RSASSA-PSS with SHA-256 and a 32 byte salt.
+    Ps256,
+}
+
+impl Algorithm {
+    /// The signature scheme used by the algorithm.
+    pub fn scheme(&self) -> SignatureScheme {
+        match self {
+            Algorithm::Rs256 => SignatureScheme::Pkcs1v15 {
+                hash: HashAlgorithm::Sha256,
+            },
+            Algorithm::Rs384 => SignatureScheme::Pkcs1v15 {
+                hash: HashAlgorithm::Sha384,
+            },
+            Algorithm::Rs512 => SignatureScheme::Pkcs1v15 {
+                hash: HashAlgorithm::Sha512,
+            },
+            Algorithm::Ps256 => SignatureScheme::Pss {
+                hash: HashAlgorithm::Sha256,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Algorithm::Rs256 => write!(f, "RS256"),
+            Algorithm::Rs384 => write!(f, "RS384"),
+            Algorithm::Rs512 => write!(f, "RS512"),
+            Algorithm::Ps256 => write!(f, "PS256"),
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    /// Parses the `alg` names of RFC 7518, which are case-sensitive in tokens,
+    /// but accepted in any case here for the command line.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "RS256" => Ok(Algorithm::Rs256),
+            "RS384" => Ok(Algorithm::Rs384),
+            "RS512" => Ok(Algorithm::Rs512),
+            "PS256" => Ok(Algorithm::Ps256),
+            _ => Err(format!("Unsupported JWT algorithm: {}", s)),
+        }
+    }
+}
+
+/// Reasons why a token is rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JwtError {
+    /// The token is not three base64url encoded parts with a JSON header and claims.
+    Malformed,
+    /// The header names an algorithm that is not supported or not allowed.
+    UnsupportedAlgorithm(String),
+    /// The header lists critical extensions, none of which are supported.
+    UnsupportedCritical,
+    /// The signature was not made with the private key matching the key.
+    InvalidSignature,
+    /// A registered claim has the wrong type.
+    InvalidClaim(&'static str),
+    /// The `exp` claim is in the past.
+    Expired,
+    /// The `nbf` claim is in the future.
+    NotYetValid,
+    /// The `aud` claim does not contain the expected audience.
+    InvalidAudience,
+}
+
+impl fmt::Display for JwtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JwtError::Malformed => write!(f, "Malformed token"),
+            JwtError::UnsupportedAlgorithm(alg) => {
+                write!(f, "Unsupported or disallowed token algorithm: {}", alg)
+            }
+            JwtError::UnsupportedCritical => {
+                write!(f, "The token uses unsupported critical header parameters")
+            }
+            JwtError::InvalidSignature => write!(f, "The signature of the token is invalid"),
+            JwtError::InvalidClaim(claim) => write!(f, "Invalid {} claim", claim),
+            JwtError::Expired => write!(f, "The token has expired"),
+            JwtError::NotYetValid => write!(f, "The token is not valid yet"),
+            JwtError::InvalidAudience => {
+                write!(f, "The token is not addressed to the expected audience")
+            }
+        }
+    }
+}
+
+impl std::error::Error for JwtError {}
+
+/// The checks applied by `verify` besides the signature.
+#[derive(Debug, Clone)]
+pub struct Validation {
+    /// The audience the token must be addressed to. Tokens with an `aud` claim
+    /// are rejected if no audience is given.
+    pub audience: Option<String>,
+    /// Seconds of clock skew allowed when checking `exp` and `nbf`.
+    pub leeway: u64,
+    /// Only accept tokens signed with this algorithm.
+    pub algorithm: Option<Algorithm>,
+    /// The current time in seconds since the Unix epoch.
+    pub now: u64,
+}
+
+impl Default for Validation {
+    /// No audience, a minute of leeway, any algorithm and the current time.
+    fn default() -> Self {
+        Validation {
+            audience: None,
+            leeway: 60,
+            algorithm: None,
+            now: now(),
+        }
+    }
+}
+
+/// The current time in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+fn decode_json(part: &str) -> Result<Value, JwtError> {
+    let bytes = base64url_decode(part).ok_or(JwtError::Malformed)?;
+    let text = String::from_utf8(bytes).map_err(|_| JwtError::Malformed)?;
+    match json::parse(&text) {
+        Ok(value @ Value::Object(_)) => Ok(value),
+        _ => Err(JwtError::Malformed),
+    }
+}
+
+/// Reads a NumericDate claim. Fractions of seconds are allowed.
+fn numeric_date(claims: &Value, name: &'static str) -> Result<Option<f64>, JwtError> {
+    match claims.get(name) {
+        None => Ok(None),
+        Some(Value::Number(number)) => number
+            .parse()
+            .map(Some)
+            .map_err(|_| JwtError::InvalidClaim(name)),
+        Some(_) => Err(JwtError::InvalidClaim(name)),
+    }
+}
+
+/// Signs the claims with the private key and returns the token. The header
+/// contains the JWK thumbprint of the key as `kid` if the key knows its
+/// public exponent.
+pub fn sign(key: &Key, algorithm: Algorithm, claims: &Value) -> error::Result<String> {
+    if !key.is_private() {
+        return Err(Error::InvalidKey(
+            "Tokens can only be signed with a private key".to_string(),
+        ));
+    }
+    if !matches!(claims, Value::Object(_)) {
+        return Err(Error::Parse("The claims must be a JSON object".to_string()));
+    }
+
+    let mut header = vec![
+        ("alg", Value::String(algorithm.to_string())),
+        ("typ", Value::string("JWT")),
+    ];
+    if let Some(kid) = key.jwk_thumbprint() {
+        header.push(("kid", Value::String(kid)));
+    }
+    let signing_input = format!(
+        "{}.{}",
+        base64url_encode(Value::object(header).to_string().as_bytes()),
+        base64url_encode(claims.to_string().as_bytes())
+    );
+    let signature = key.sign(&algorithm.scheme(), &mut signing_input.as_bytes())?;
+    Ok(format!(
+        "{}.{}",
+        signing_input,
+        base64url_encode(&signature)
+    ))
+}
+
+/// Verifies the signature of the token with the key and checks its `exp`,
+/// `nbf` and `aud` claims. Returns the claims.
+pub fn verify(key: &Key, token: &str, validation: &Validation) -> error::Result<Value> {
+    let token = token.trim();
+    let parts: Vec<&str> = token.split('.').collect();
+    let [header, claims, signature] = parts[..] else {
+        return Err(JwtError::Malformed.into());
+    };
+    let header = decode_json(header)?;
+    let claims = decode_json(claims)?;
+    let signature = base64url_decode(signature).ok_or(JwtError::Malformed)?;
+
+    let name = header
+        .get("alg")
+        .and_then(Value::as_str)
+        .ok_or(JwtError::Malformed)?;
+    // Algorithm names are case-sensitive, so they are compared with the name
+    // of the parsed algorithm.
+    let algorithm = match name.parse::<Algorithm>() {
+        Ok(algorithm) if algorithm.to_string() == name => algorithm,
+        _ => return Err(JwtError::UnsupportedAlgorithm(name.to_string()).into()),
+    };
+    if validation
+        .algorithm
+        .is_some_and(|allowed| allowed != algorithm)
+    {
+        return Err(JwtError::UnsupportedAlgorithm(name.to_string()).into());
+    }
+    if header.get("crit").is_some() {
+        return Err(JwtError::UnsupportedCritical.into());
+    }
+
+    let signing_input = &token[..token.rfind('.').unwrap()];
+    if !key.verify(
+        &algorithm.scheme(),
+        &mut signing_input.as_bytes(),
+        &signature,
+    )? {
+        return Err(JwtError::InvalidSignature.into());
+    }
+
+    let now = validation.now as f64;
+    let leeway = validation.leeway as f64;
+    if numeric_date(&claims, "exp")?.is_some_and(|exp| now >= exp + leeway) {
+        return Err(JwtError::Expired.into());
+    }
+    if numeric_date(&claims, "nbf")?.is_some_and(|nbf| now + leeway < nbf) {
+        return Err(JwtError::NotYetValid.into());
+    }
+    let audiences = match claims.get("aud") {
+        None => Vec::new(),
+        Some(Value::String(audience)) => vec![audience.as_str()],
+        Some(Value::Array(audiences)) => audiences
+            .iter()
+            .map(Value::as_str)
+            .collect::<Option<Vec<&str>>>()
+            .ok_or(JwtError::InvalidClaim("aud"))?,
+        Some(_) => return Err(JwtError::InvalidClaim("aud").into()),
+    };
+    let addressed = match &validation.audience {
+        Some(expected) => audiences.contains(&expected.as_str()),
+        None => audiences.is_empty(),
+    };
+    if !addressed {
+        return Err(JwtError::InvalidAudience.into());
+    }
+
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::test_crt_key_pair;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn validation() -> Validation {
+        Validation {
+            now: NOW,
+            leeway: 0,
+            ..Validation::default()
+        }
+    }
+
+    fn claims(members: Vec<(&str, Value)>) -> Value {
+        Value::object(members)
+    }
+
+    fn number(value: u64) -> Value {
+        Value::Number(value.to_string())
+    }
+
+    fn verify_error(token: &str, validation: &Validation) -> JwtError {
+        match verify(test_crt_key_pair().public(), token, validation) {
+            Err(Error::Jwt(err)) => err,
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn verify_returns_signed_claims() {
+        let keys = test_crt_key_pair();
+        let claims = claims(vec![
+            ("sub", Value::string("service")),
+            ("exp", number(NOW + 1)),
+        ]);
+
+        for algorithm in [
+            Algorithm::Rs256,
+            Algorithm::Rs384,
+            Algorithm::Rs512,
+            Algorithm::Ps256,
+        ] {
+            let token = sign(keys.private(), algorithm, &claims).unwrap();
+
+            assert_eq!(
+                claims,
+                verify(keys.public(), &token, &validation()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn sign_writes_header_with_kid() {
+        let keys = test_crt_key_pair();
+        let token = sign(keys.private(), Algorithm::Rs256, &claims(Vec::new())).unwrap();
+        let header = decode_json(token.split('.').next().unwrap()).unwrap();
+
+        assert_eq!(Some("RS256"), header.get("alg").and_then(Value::as_str));
+        assert_eq!(
+            keys.public().jwk_thumbprint().as_deref(),
+            header.get("kid").and_then(Value::as_str)
+        );
+        assert!(matches!(
+            sign(keys.public(), Algorithm::Rs256, &claims(Vec::new())),
+            Err(Error::InvalidKey(_))
+        ));
+    }
+
+    // Signed with the private key of test_key_pair by the Python cryptography package.
+    #[test]
+    fn verify_accepts_token_of_other_implementation() {
+        let token = "eyJhbGciOiJSUzI1NiIsInR5cCI6IkpXVCJ9.eyJzdWIiOiJ0ZXN0In0.\
+            BJcDxVubYTqETqcBJ5S5BjxNJhGMaYCySEUX5JW4GP9oizv5mcRN9zThoXk98tTb6AOEThzoDLdn1M_chYJROgY4yg\
+            sWF_voCOlZJ_UnK2FLAX-VGPBmINOOW0htCpvg9ZQ9mTl8-BUJ7djCteLgYO6L9SzLcEqBR2KBnf6nGMnGYwAuLT-T\
+            o6fzQBp9v1XR9ykIemyBu6lyDHCoU1VwRrL3jMdfj1X7B8WFyVrbvrYLSrrrpOrFdl5iyGWX846bUNEKcNqjPoA2r2\
+            _HOTfFK7Nri2w8NvZXn7-BmYHM168Btuf8tEsk-p1CzCZbV7K97N88IgQYSimbJZYtwq4-Ig";
+
+        let claims = verify(test_crt_key_pair().public(), token, &validation()).unwrap();
+
+        assert_eq!(Some("test"), claims.get("sub").and_then(Value::as_str));
+    }
+
+    #[test]
+    fn verify_rejects_modified_token() {
+        let keys = test_crt_key_pair();
+        let token = sign(keys.private(), Algorithm::Ps256, &claims(Vec::new())).unwrap();
+        let (signing_input, _) = token.rsplit_once('.').unwrap();
+        let other = sign(
+            keys.private(),
+            Algorithm::Ps256,
+            &claims(vec![("admin", Value::Bool(true))]),
+        )
+        .unwrap();
+        let (_, other_signature) = other.rsplit_once('.').unwrap();
+
+        assert_eq!(
+            JwtError::InvalidSignature,
+            verify_error(
+                &format!("{}.{}", signing_input, other_signature),
+                &validation()
+            )
+        );
+        assert_eq!(
+            JwtError::Malformed,
+            verify_error(&format!("{}.", token), &validation())
+        );
+        assert_eq!(
+            JwtError::Malformed,
+            verify_error("eyJhbGciOiJSUzI1NiJ9.e30", &validation())
+        );
+    }
+
+    #[test]
+    fn verify_checks_algorithm() {
+        let keys = test_crt_key_pair();
+        let token = sign(keys.private(), Algorithm::Rs256, &claims(Vec::new())).unwrap();
+        let pinned = Validation {
+            algorithm: Some(Algorithm::Ps256),
+            ..validation()
+        };
+        let (_, rest) = token.split_once('.').unwrap();
+        let none = format!("{}.{}", base64url_encode(b"{\"alg\":\"none\"}"), rest);
+        let lowercase = format!("{}.{}", base64url_encode(b"{\"alg\":\"rs256\"}"), rest);
+        let critical = format!(
+            "{}.{}",
+            base64url_encode(b"{\"alg\":\"RS256\",\"crit\":[\"exp\"]}"),
+            rest
+        );
+
+        assert_eq!(
+            JwtError::UnsupportedAlgorithm("RS256".to_string()),
+            verify_error(&token, &pinned)
+        );
+        assert_eq!(
+            JwtError::UnsupportedAlgorithm("none".to_string()),
+            verify_error(&none, &validation())
+        );
+        assert_eq!(
+            JwtError::UnsupportedAlgorithm("rs256".to_string()),
+            verify_error(&lowercase, &validation())
+        );
+        assert_eq!(
+            JwtError::UnsupportedCritical,
+            verify_error(&critical, &validation())
+        );
+    }
+
+    #[test]
+    fn verify_checks_time_claims() {
+        let keys = test_crt_key_pair();
+        let token = |members| sign(keys.private(), Algorithm::Rs256, &claims(members)).unwrap();
+        let with_leeway = Validation {
+            leeway: 10,
+            ..validation()
+        };
+
+        let expired = token(vec![("exp", number(NOW))]);
+        assert_eq!(JwtError::Expired, verify_error(&expired, &validation()));
+        assert!(verify(keys.public(), &expired, &with_leeway).is_ok());
+
+        let early = token(vec![("nbf", number(NOW + 5))]);
+        assert_eq!(JwtError::NotYetValid, verify_error(&early, &validation()));
+        assert!(verify(keys.public(), &early, &with_leeway).is_ok());
+
+        let invalid = token(vec![("exp", Value::string("tomorrow"))]);
+        assert_eq!(
+            JwtError::InvalidClaim("exp"),
+            verify_error(&invalid, &validation())
+        );
+    }
+
+    #[test]
+    fn verify_checks_audience() {
+        let keys = test_crt_key_pair();
+        let token = |aud| {
+            sign(
+                keys.private(),
+                Algorithm::Rs256,
+                &claims(vec![("aud", aud)]),
+            )
+            .unwrap()
+        };
+        let api = Validation {
+            audience: Some("api".to_string()),
+            ..validation()
+        };
+
+        let single = token(Value::string("api"));
+        let list = token(Value::Array(vec![
+            Value::string("web"),
+            Value::string("api"),
+        ]));
+        let other = token(Value::string("web"));
+        assert!(verify(keys.public(), &single, &api).is_ok());
+        assert!(verify(keys.public(), &list, &api).is_ok());
+        assert_eq!(JwtError::InvalidAudience, verify_error(&other, &api));
+        assert_eq!(
+            JwtError::InvalidAudience,
+            verify_error(&single, &validation())
+        );
+
+        let unaddressed = sign(keys.private(), Algorithm::Rs256, &claims(Vec::new())).unwrap();
+        assert_eq!(JwtError::InvalidAudience, verify_error(&unaddressed, &api));
+    }
+}
@@ -1,129 +1,865 @@
-use std::fs::File;
-use std::io::{self, prelude::*, Error};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, prelude::*};
+use std::num::NonZeroUsize;
+use std::path::Path;
 
 use crate::algorithms::{self, modular_pow};
-use num_bigint::{BigInt, RandomBits};
+use crate::container;
+use crate::der;
+use crate::envelope;
+use crate::error::{self, Error};
+use crate::hash::HashAlgorithm;
+use crate::json::Value;
+use crate::jwk;
+use crate::key_format::{self, DecodedKey, KeyFormat, PrivateComponents};
+use crate::montgomery::MontgomeryContext;
+use crate::padding::{self, Padding, PaddingError};
+use crate::pem;
+use crate::signature::SignatureScheme;
+use crate::ssh;
+use num_bigint::{BigInt, BigUint, RandBigInt, RandomBits, Sign};
 #[allow(unused_imports)]
 use num_traits::identities::One;
+use num_traits::ToPrimitive;
 use rand::Rng;
 use std::str::FromStr;
 
-const KEY_SIZE: u64 = 1024;
-const MR_ITERATIONS: isize = 4;
+const DEFAULT_BITS: u64 = 2048;
+/// Smallest modulus accepted by `KeyPair::generate_with`.
+pub const MIN_BITS: u64 = 512;
+/// Candidate primes are first checked for divisibility by the primes below this.
+const SIEVE_LIMIT: u32 = 2048;
+/// How far from a random starting point a prime is searched for.
+const MAX_SIEVE_DISTANCE: u32 = 1 << 16;
 const DEFAULT_EXP: i32 = 65_537;
+/// Number of blocks each thread processes per batch in `Key::process_blocks`.
+const BLOCKS_PER_JOB: usize = 16;
+/// Permissions of written private key files on Unix, before the umask.
+const PRIVATE_FILE_MODE: u32 = 0o600;
+/// Permissions of written public key files on Unix, before the umask.
+const PUBLIC_FILE_MODE: u32 = 0o644;
+
+/// Parameters for generating a keypair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyParams {
+    /// Length of the modulus in bits.
+    pub bits: u64,
+    /// The public exponent.
+    pub exponent: BigInt,
+}
+
+impl Default for KeyParams {
+    fn default() -> Self {
+        KeyParams {
+            bits: DEFAULT_BITS,
+            exponent: BigInt::from(DEFAULT_EXP),
+        }
+    }
+}
+
+impl KeyParams {
+    /// Checks that a secure keypair can be generated with the parameters.
+    pub fn validate(&self) -> error::Result<()> {
+        if self.bits < MIN_BITS {
+            return Err(Error::Size(format!(
+                "Key size of {} bits is too small, the minimum is {} bits",
+                self.bits, MIN_BITS
+            )));
+        }
+        if self.exponent <= BigInt::one()
+            || &self.exponent % 2 == BigInt::from(0)
+            || self.exponent.bits() >= self.bits
+        {
+            return Err(Error::InvalidKey(
+                "The public exponent must be an odd number greater than one and smaller than the modulus".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
 
 #[derive(Debug)]
 pub struct Key {
     exp: BigInt,
     modulus: BigInt,
+    /// The primes and CRT values of a private key, if they are known.
+    crt: Option<PrivateComponents>,
+    /// Precomputed values for exponentiation modulo the modulus and the primes.
+    contexts: Contexts,
+    /// Number of threads used to process blocks.
+    jobs: NonZeroUsize,
+    /// The comment of an OpenSSH key.
+    comment: Option<String>,
+}
+
+#[derive(Debug)]
+struct Contexts {
+    modulus: Option<MontgomeryContext>,
+    p: Option<MontgomeryContext>,
+    q: Option<MontgomeryContext>,
 }
 
 impl Key {
-    fn write_to_file(&self, path: &str) -> std::io::Result<()> {
-        let key_string = self.modulus.to_string() + "\n" + &self.exp.to_string();
+    fn new(exp: BigInt, modulus: BigInt, crt: Option<PrivateComponents>) -> Key {
+        let contexts = Contexts {
+            modulus: MontgomeryContext::new(&modulus),
+            p: crt.as_ref().and_then(|crt| MontgomeryContext::new(&crt.p)),
+            q: crt.as_ref().and_then(|crt| MontgomeryContext::new(&crt.q)),
+        };
+        Key {
+            exp,
+            modulus,
+            crt,
+            contexts,
+            jobs: NonZeroUsize::MIN,
+            comment: None,
+        }
+    }
 
-        let mut file = File::create(path)?;
-        file.write_all(key_string.as_bytes())?;
+    fn with_comment(mut self, comment: Option<String>) -> Key {
+        self.comment = comment;
+        self
+    }
 
-        Ok(())
+    /// Writes the key in the decimal format.
+    fn write_decimal(&self, output: &mut dyn Write) -> io::Result<()> {
+        let mut key_string = self.modulus.to_string() + "\n" + &self.exp.to_string();
+        if let Some(crt) = &self.crt {
+            for value in [&crt.e, &crt.p, &crt.q, &crt.dp, &crt.dq, &crt.qinv] {
+                key_string += &("\n".to_string() + &value.to_string());
+            }
+        }
+
+        output.write_all(key_string.as_bytes())
+    }
+
+    /// Creates a key with values from the given filepath. The format is detected
+    /// from the contents: PEM and DER encoded PKCS #1, PKCS #8 and SubjectPublicKeyInfo
+    /// keys, OpenSSH keys and JSON Web Keys are supported in addition to the decimal
+    /// format written by `KeyPair::write_to_file`.
+    /// Encrypted keys are rejected, use `from_file_with_passphrase` for them.
+    pub fn from_file(path: &str) -> error::Result<Key> {
+        Key::from_file_with_passphrase(path, &no_passphrase)
     }
 
-    /// Creates a keypair with values from the given filepath.
-    pub fn from_file(path: &str) -> Result<Key, Error> {
-        let file = File::open(path)?;
-        let mut file_buf = io::BufReader::new(file).lines();
+    /// Like `from_file`, but also reads private keys encrypted with a passphrase.
+    /// The passphrase is only requested if the key is encrypted.
+    pub fn from_file_with_passphrase(
+        path: &str,
+        passphrase: &dyn Fn() -> error::Result<Vec<u8>>,
+    ) -> error::Result<Key> {
+        Key::read(path, passphrase)?.check()
+    }
+
+    /// Creates a public key from a DER encoded SubjectPublicKeyInfo, like the
+    /// one in a certificate.
+    pub(crate) fn from_public_key_info(data: &[u8]) -> error::Result<Key> {
+        match key_format::decode_der(data)? {
+            DecodedKey::Public { n, e } => Key::new(e, n, None).check(),
+            _ => Err(Error::Parse("Expected a public key".to_string())),
+        }
+    }
 
-        let modulus = match file_buf.next() {
-            Some(string) => string?,
-            None => panic!("Invalid key file: {}", path),
+    /// Reads a key without checking that its values can be used.
+    fn read(path: &str, passphrase: &dyn Fn() -> error::Result<Vec<u8>>) -> error::Result<Key> {
+        let contents = std::fs::read(path)?;
+
+        let decoded = if pem::is_pem(&contents) {
+            let text = String::from_utf8_lossy(&contents);
+            if ssh::is_private(&text) {
+                let (components, comment) = ssh::decode_private(&text, passphrase)?;
+                let key = Key::new(components.d.clone(), components.n.clone(), Some(components));
+                return Ok(key.with_comment(comment));
+            }
+            key_format::decode_pem(&text)?
+        } else if ssh::is_public(&contents) {
+            let (n, e, comment) = ssh::decode_public(&String::from_utf8_lossy(&contents))?;
+            return Ok(Key::new(e, n, None).with_comment(comment));
+        } else if jwk::is_jwk(&contents) {
+            jwk::decode(&String::from_utf8_lossy(&contents))?
+        } else if contents.first() == Some(&der::SEQUENCE) {
+            key_format::decode_der(&contents)?
+        } else {
+            return Key::from_decimal(&contents, path);
         };
-        let exp = match file_buf.next() {
-            Some(string) => string?,
-            None => panic!("Invalid key file: {}", path),
+        let decoded = match decoded {
+            DecodedKey::Encrypted(data) => key_format::decode_encrypted(&data, &passphrase()?)?,
+            decoded => decoded,
         };
 
-        Ok(Key {
-            modulus: match BigInt::from_str(&modulus) {
-                Ok(num) => num,
-                Err(_) => panic!("Invalid key file: {}", path),
-            },
-            exp: match BigInt::from_str(&exp) {
-                Ok(num) => num,
-                Err(_) => panic!("Invalid key file: {}", path),
-            },
+        Ok(match decoded {
+            DecodedKey::Public { n, e } => Key::new(e, n, None),
+            DecodedKey::Private(components) => {
+                Key::new(components.d.clone(), components.n.clone(), Some(components))
+            }
+            DecodedKey::Encrypted(_) => unreachable!("encrypted keys are decrypted above"),
         })
     }
 
-    /// Reads data from the input, encrypts it using self, then writes it to the output.
-    pub fn encrypt(&self, input: &mut dyn Read, output: &mut dyn Write) -> std::io::Result<()> {
-        let in_bytes: usize = (self.modulus.bits() / 8 - 1).try_into().unwrap();
-        let out_bytes: usize = ((self.modulus.bits() + 7) / 8).try_into().unwrap();
+    fn from_decimal(contents: &[u8], path: &str) -> error::Result<Key> {
+        let invalid = || Error::Parse(format!("Invalid key file: {}", path));
+        let parse = |line: &str| BigInt::from_str(line.trim()).map_err(|_| invalid());
+
+        let text = std::str::from_utf8(contents).map_err(|_| invalid())?;
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+
+        let modulus = parse(lines.next().ok_or_else(invalid)?)?;
+        let exp = parse(lines.next().ok_or_else(invalid)?)?;
+
+        // Private keys written by newer versions are followed by e, p, q, dP, dQ and qInv.
+        let extra = lines.map(parse).collect::<error::Result<Vec<BigInt>>>()?;
+        let crt = match extra.as_slice() {
+            [] => None,
+            [e, p, q, dp, dq, qinv] => Some(PrivateComponents {
+                n: modulus.clone(),
+                e: e.clone(),
+                d: exp.clone(),
+                p: p.clone(),
+                q: q.clone(),
+                dp: dp.clone(),
+                dq: dq.clone(),
+                qinv: qinv.clone(),
+            }),
+            _ => return Err(invalid()),
+        };
+
+        Ok(Key::new(exp, modulus, crt))
+    }
+
+    /// Checks that the values of a key read from a file can be used.
+    fn check(self) -> error::Result<Key> {
+        if self.modulus.bits() < 16 || self.exp.sign() != Sign::Plus {
+            return Err(Error::InvalidKey(
+                "The modulus or the exponent is out of range".to_string(),
+            ));
+        }
+        if let Some(crt) = &self.crt {
+            if &crt.p * &crt.q != self.modulus {
+                return Err(Error::InvalidKey(
+                    "The primes do not match the modulus".to_string(),
+                ));
+            }
+        }
+        Ok(self)
+    }
+
+    /// Length of the modulus in bits.
+    pub fn bits(&self) -> u64 {
+        self.modulus.bits()
+    }
 
-        let mut current_in_bytes: Vec<u8> = vec![0u8; in_bytes + 1];
+    /// The public exponent, if it is known. Private keys read from old decimal
+    /// files only contain the private exponent.
+    pub fn public_exponent(&self) -> Option<&BigInt> {
+        match &self.crt {
+            Some(crt) => Some(&crt.e),
+            None if !self.is_private() => Some(&self.exp),
+            None => None,
+        }
+    }
 
-        let mut amount_of_bytes_read = in_bytes;
+    /// The SHA-256 hash of the DER encoded SubjectPublicKeyInfo of the public key,
+    /// which is the same for both halves of a keypair. Returns `None` if the public
+    /// exponent is not known.
+    pub fn fingerprint(&self) -> Option<Vec<u8>> {
+        Some(HashAlgorithm::Sha256.digest(&self.public_key_info()?))
+    }
 
-        while amount_of_bytes_read > 0 {
-            current_in_bytes.fill(0u8);
+    /// The DER encoded SubjectPublicKeyInfo of the public key, as embedded in
+    /// certificates. Returns `None` if the public exponent is not known.
+    pub(crate) fn public_key_info(&self) -> Option<Vec<u8>> {
+        Some(key_format::spki_der(&self.modulus, self.public_exponent()?))
+    }
 
-            amount_of_bytes_read = input.take(in_bytes as u64).read(&mut current_in_bytes)?;
-            if amount_of_bytes_read == 0 {
-                break;
+    /// The fingerprint shown by `ssh-keygen -l`, like `SHA256:...`. Returns `None`
+    /// if the public exponent is not known.
+    pub fn ssh_fingerprint(&self) -> Option<String> {
+        Some(ssh::fingerprint(&self.modulus, self.public_exponent()?))
+    }
+
+    /// The JWK thumbprint of RFC 7638, which is used as `kid` of exported JSON Web
+    /// Keys. Returns `None` if the public exponent is not known.
+    pub fn jwk_thumbprint(&self) -> Option<String> {
+        Some(jwk::thumbprint(&self.modulus, self.public_exponent()?))
+    }
+
+    /// The comment of a key read from an OpenSSH key file.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// Writes the public half of the key in the given format. Fails if the
+    /// public exponent is not known.
+    pub fn write_public(&self, format: KeyFormat, output: &mut dyn Write) -> error::Result<()> {
+        let e = self.public_exponent().ok_or_else(missing_public_exponent)?;
+        write_public_key(format, &self.modulus, e, self.comment(), output)
+    }
+
+    /// Writes the public halves of the keys as a JWK Set. Fails if the public
+    /// exponent of a key is not known.
+    pub fn write_jwk_set(keys: &[Key], output: &mut dyn Write) -> error::Result<()> {
+        let jwks = keys
+            .iter()
+            .map(|key| {
+                let e = key.public_exponent().ok_or_else(missing_public_exponent)?;
+                Ok(jwk::public_jwk(&key.modulus, e))
+            })
+            .collect::<error::Result<Vec<Value>>>()?;
+        writeln!(output, "{}", jwk::jwk_set(jwks))?;
+        Ok(())
+    }
+
+    /// Returns true if the exponent of the key is secret. Keys read from old
+    /// decimal files do not say which half of the keypair they are, but a private
+    /// exponent is always longer than half of the modulus and a public one shorter.
+    pub fn is_private(&self) -> bool {
+        self.crt.is_some() || self.exp.bits() > self.modulus.bits() / 2
+    }
+
+    /// Raises the number to the exponent of the key modulo the modulus.
+    ///
+    /// Private key operations use constant-time exponentiation, and the input is
+    /// blinded with `r^e` for a random `r` if the public exponent is known, so
+    /// that the timing depends neither on the exponent nor on the input.
+    fn apply(&self, x: &BigInt) -> BigInt {
+        if !self.is_private() {
+            return pow(&self.contexts.modulus, x, &self.exp, &self.modulus);
+        }
+
+        let n = &self.modulus;
+        match self.crt.as_ref().map(|crt| &crt.e) {
+            Some(e) => {
+                let (r, r_inv) = self.blinding_factor();
+                let blinded = (x * pow(&self.contexts.modulus, &r, e, n)) % n;
+                (self.apply_private(&blinded) * r_inv) % n
+            }
+            None => self.apply_private(x),
+        }
+    }
+
+    /// Returns a random number and its inverse modulo the modulus.
+    fn blinding_factor(&self) -> (BigInt, BigInt) {
+        let n = &self.modulus;
+        loop {
+            let r = rand::thread_rng().gen_bigint_range(&BigInt::from(2), n);
+            let (gcd, r_inv, _) = algorithms::extended_eucledian(&r, n);
+            if gcd == BigInt::one() {
+                return (r, (r_inv % n + n) % n);
             }
+        }
+    }
+
+    /// Raises the number to the private exponent.
+    ///
+    /// Private keys with known primes use the Chinese Remainder Theorem, which is
+    /// about three times faster. Since a fault in one of the halves would reveal
+    /// a prime factor, the result is checked with the public exponent and
+    /// recomputed without the CRT if it is wrong.
+    fn apply_private(&self, x: &BigInt) -> BigInt {
+        let contexts = &self.contexts;
+        let crt = match &self.crt {
+            Some(crt) => crt,
+            None => return pow_secret(&contexts.modulus, x, &self.exp, &self.modulus),
+        };
+
+        let m1 = pow_secret(&contexts.p, x, &crt.dp, &crt.p);
+        let m2 = pow_secret(&contexts.q, x, &crt.dq, &crt.q);
+        let h = ((&crt.qinv * (m1 - &m2)) % &crt.p + &crt.p) % &crt.p;
+        let m = m2 + h * &crt.q;
+
+        if pow(&contexts.modulus, &m, &crt.e, &self.modulus) == x % &self.modulus {
+            m
+        } else {
+            pow_secret(&contexts.modulus, x, &self.exp, &self.modulus)
+        }
+    }
+
+    /// Sets how many threads `encrypt`, `decrypt`, `encrypt_with` and `decrypt_with`
+    /// use. Blocks are read in batches, exponentiated in parallel and written in
+    /// order, so the memory use only depends on the number of threads. The
+    /// default is one thread.
+    pub fn set_jobs(&mut self, jobs: NonZeroUsize) {
+        self.jobs = jobs;
+    }
+
+    /// Reads data from the input, encrypts it using self, then writes it to the output.
+    pub fn encrypt(&self, input: &mut dyn Read, output: &mut dyn Write) -> error::Result<()> {
+        let in_bytes: usize = (self.modulus.bits() / 8 - 1).try_into().unwrap();
+        let out_bytes: usize = self.modulus.bits().div_ceil(8).try_into().unwrap();
 
+        self.process_blocks(input, output, in_bytes, true, &|block| {
             // Preserve leading null bytes
-            current_in_bytes[amount_of_bytes_read] = 1u8;
+            let mut marked = block.to_vec();
+            marked.push(1u8);
 
-            let mut dencrypted_bytes = modular_pow(
-                &BigInt::from_bytes_le(num_bigint::Sign::Plus, &current_in_bytes),
-                &self.exp,
-                &self.modulus,
-            )
-            .to_bytes_le()
-            .1;
+            let mut dencrypted_bytes = self
+                .apply(&BigInt::from_bytes_le(num_bigint::Sign::Plus, &marked))
+                .to_bytes_le()
+                .1;
 
             // Fill in missing bytes
-            let mut i = 0;
-            while i < out_bytes - dencrypted_bytes.len() {
-                dencrypted_bytes.push(0u8);
-                i += 1;
+            dencrypted_bytes.resize(out_bytes, 0u8);
+            Ok(dencrypted_bytes)
+        })
+    }
+
+    /// Reads data from the input, decrypts it using self, then writes it to the output.
+    ///
+    /// Fails with `PaddingError::Truncated` if the input ends in the middle of a
+    /// block, and with `PaddingError::Decryption` if a block is not smaller than
+    /// the modulus or does not decrypt to data followed by the marker byte that
+    /// `encrypt` appends, which happens with the wrong key or corrupted input.
+    pub fn decrypt(&self, input: &mut dyn Read, output: &mut dyn Write) -> error::Result<()> {
+        let in_bytes: usize = self.modulus.bits().div_ceil(8).try_into().unwrap();
+        let out_bytes: usize = (self.modulus.bits() / 8 - 1).try_into().unwrap();
+
+        self.process_blocks(input, output, in_bytes, false, &|block| {
+            let c = BigInt::from_bytes_le(num_bigint::Sign::Plus, block);
+            if c >= self.modulus {
+                return Err(PaddingError::Decryption.into());
+            }
+            let mut dencrypted_bytes = self.apply(&c).to_bytes_le().1;
+
+            // The marker byte is the most significant byte of every block.
+            if dencrypted_bytes.pop() != Some(1u8) || dencrypted_bytes.len() > out_bytes {
+                return Err(PaddingError::Decryption.into());
             }
+            Ok(dencrypted_bytes)
+        })
+    }
 
-            output.write_all(&dencrypted_bytes)?;
+    /// Like `encrypt`, but pads every block with the given padding.
+    pub fn encrypt_with(
+        &self,
+        padding: &Padding,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> error::Result<()> {
+        if *padding == Padding::Raw {
+            return self.encrypt(input, output);
         }
 
-        Ok(())
+        let k = self.size_in_bytes();
+        let in_bytes = padding
+            .max_message_len(k)
+            .filter(|len| *len > 0)
+            .ok_or(PaddingError::KeyTooSmall)?;
+
+        self.process_blocks(input, output, in_bytes, true, &|block| {
+            Ok(self.encrypt_block(padding, block)?)
+        })
     }
 
-    /// Reads data from the input, decrypts it using self, then writes it to the output.
-    pub fn decrypt(&self, input: &mut dyn Read, output: &mut dyn Write) -> std::io::Result<()> {
-        let in_bytes: usize = ((self.modulus.bits() + 7) / 8).try_into().unwrap();
+    /// Like `decrypt`, but removes the given padding from every block.
+    /// Fails with a `PaddingError` if a block is not correctly padded, except
+    /// with `Padding::Pkcs1v15`, where such a block decrypts to a synthetic
+    /// message, so that the result does not reveal whether the padding was valid.
+    pub fn decrypt_with(
+        &self,
+        padding: &Padding,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> error::Result<()> {
+        if *padding == Padding::Raw {
+            return self.decrypt(input, output);
+        }
+
+        let k = self.size_in_bytes();
+        self.process_blocks(input, output, k, false, &|block| {
+            Ok(self.decrypt_block(padding, block)?)
+        })
+    }
 
-        let mut current_in_bytes: Vec<u8> = vec![0u8; in_bytes];
+    /// Splits the input into blocks of the given length, transforms them on
+    /// `self.jobs` threads and writes the results to the output in order. Only
+    /// the last block may be shorter, and only if `partial_last_block` is set,
+    /// otherwise a short block is a `PaddingError::Truncated`.
+    fn process_blocks(
+        &self,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+        block_len: usize,
+        partial_last_block: bool,
+        transform: &(dyn Fn(&[u8]) -> error::Result<Vec<u8>> + Sync),
+    ) -> error::Result<()> {
+        let jobs = self.jobs.get();
+        let batch_len = jobs * BLOCKS_PER_JOB;
+        let mut blocks = BlockReader::new(input, block_len);
+        let mut batch: Vec<Vec<u8>> = Vec::with_capacity(batch_len);
 
-        let mut amount_of_bytes_read = in_bytes;
+        loop {
+            batch.clear();
+            let mut truncated = false;
+            while batch.len() < batch_len {
+                match blocks.next_block()? {
+                    Some(block) if block.len() < block_len && !partial_last_block => {
+                        truncated = true;
+                        break;
+                    }
+                    Some(block) => batch.push(block),
+                    None => break,
+                }
+            }
 
-        while amount_of_bytes_read > 0 {
-            current_in_bytes.fill(0);
-            amount_of_bytes_read = input.read(&mut current_in_bytes)?;
-            if amount_of_bytes_read == 0 {
-                break;
+            for result in transform_blocks(&batch, jobs, transform) {
+                output.write_all(&result?)?;
+            }
+            if truncated {
+                return Err(PaddingError::Truncated.into());
             }
+            if blocks.is_finished() {
+                return Ok(());
+            }
+        }
+    }
 
-            let mut dencrypted_bytes = modular_pow(
-                &BigInt::from_bytes_le(num_bigint::Sign::Plus, &current_in_bytes),
-                &self.exp,
-                &self.modulus,
-            )
-            .to_bytes_le()
-            .1;
+    /// Pads and encrypts a single message that fits into one block.
+    pub(crate) fn encrypt_block(
+        &self,
+        padding: &Padding,
+        message: &[u8],
+    ) -> Result<Vec<u8>, PaddingError> {
+        let k = self.size_in_bytes();
+        let em = padding.encode(message, k)?;
+        let c = self.apply(&padding::os2ip(&em));
+        Ok(self.to_block(&c))
+    }
+
+    /// Decrypts a single block and removes its padding.
+    pub(crate) fn decrypt_block(
+        &self,
+        padding: &Padding,
+        block: &[u8],
+    ) -> Result<Vec<u8>, PaddingError> {
+        let k = self.size_in_bytes();
+        let c = padding::os2ip(block);
+        if block.len() != k || c >= self.modulus {
+            return Err(PaddingError::Decryption);
+        }
+        let em = self.to_block(&self.apply(&c));
+        padding.decode(&em, k, || {
+            padding::implicit_rejection_key(&self.exp, block, k)
+        })
+    }
+
+    /// Encrypts the data read from the input with a random symmetric key, which is
+    /// itself encrypted with self using the given padding, and writes the result
+    /// to the output. See the `envelope` module for the format.
+    pub fn encrypt_hybrid(
+        &self,
+        padding: &Padding,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> error::Result<()> {
+        envelope::seal(self, padding, input, output)
+    }
+
+    /// Decrypts data written by `encrypt_hybrid`. The padding of the symmetric key
+    /// is read from the header, only the OAEP label has to be given.
+    pub fn decrypt_hybrid(
+        &self,
+        label: &[u8],
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> error::Result<()> {
+        envelope::open(self, label, input, output)
+    }
+
+    /// Encrypts the data read from the input like `encrypt_with`, but starts the
+    /// output with a header that records the padding, the block length and the
+    /// fingerprint of the key. See the `container` module for the format.
+    pub fn encrypt_container(
+        &self,
+        padding: &Padding,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> error::Result<()> {
+        container::seal(self, padding, input, output)
+    }
 
-            dencrypted_bytes.pop();
+    /// Decrypts data written by `encrypt_container`. The header is checked against
+    /// self and the padding is read from it, only the OAEP label has to be given.
+    pub fn decrypt_container(
+        &self,
+        label: &[u8],
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> error::Result<()> {
+        container::open(self, label, input, output)
+    }
 
-            output.write_all(&dencrypted_bytes)?;
+    /// Signs the data read from the input with self and returns the detached signature.
+    /// Fails with `Error::InvalidKey` if self is a public key.
+    pub fn sign(&self, scheme: &SignatureScheme, input: &mut dyn Read) -> error::Result<Vec<u8>> {
+        if !self.is_private() {
+            return Err(Error::InvalidKey("Signing needs a private key".to_string()));
         }
+        let m_hash = scheme.hash().digest_reader(input)?;
+        let mod_bits: usize = self.modulus.bits().try_into().unwrap();
+        let em = scheme.encode(&m_hash, mod_bits)?;
 
-        Ok(())
+        let s = self.apply(&padding::os2ip(&em));
+        Ok(self.to_block(&s))
+    }
+
+    /// Checks that the signature was made over the data read from the input
+    /// with the private key matching self.
+    pub fn verify(
+        &self,
+        scheme: &SignatureScheme,
+        input: &mut dyn Read,
+        signature: &[u8],
+    ) -> error::Result<bool> {
+        let m_hash = scheme.hash().digest_reader(input)?;
+        let mod_bits: usize = self.modulus.bits().try_into().unwrap();
+
+        if signature.len() != self.size_in_bytes() {
+            return Ok(false);
+        }
+        let s = padding::os2ip(signature);
+        if s >= self.modulus {
+            return Ok(false);
+        }
+
+        let m = self.apply(&s);
+        // With PSS the encoded message can be a byte shorter than the modulus,
+        // a larger representative is invalid (RFC 8017, section 8.1.2, step 2c).
+        let Some(em) = padding::i2osp(&m, scheme.encoded_len(mod_bits)) else {
+            return Ok(false);
+        };
+        Ok(scheme.verify(&m_hash, &em, mod_bits))
+    }
+
+    /// Length of the modulus in bytes.
+    pub(crate) fn size_in_bytes(&self) -> usize {
+        self.modulus.bits().div_ceil(8).try_into().unwrap()
+    }
+
+    /// Encodes a number smaller than the modulus as a block of `size_in_bytes` bytes.
+    fn to_block(&self, x: &BigInt) -> Vec<u8> {
+        padding::i2osp(x, self.size_in_bytes()).expect("the number is smaller than the modulus")
+    }
+}
+
+/// Writes a file by writing a temporary file in the same directory and moving it
+/// in place. Without `overwrite` the temporary file is hard linked to the path
+/// instead, which fails if the file has been created in the meantime.
+pub(crate) fn write_file_atomically(
+    path: &Path,
+    contents: &[u8],
+    mode: u32,
+    overwrite: bool,
+) -> io::Result<()> {
+    let with_path =
+        |err: io::Error| io::Error::new(err.kind(), format!("{}: {}", path.display(), err));
+    let file_name = path.file_name().ok_or_else(|| {
+        with_path(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "not a file name",
+        ))
+    })?;
+    let temp_name = format!(
+        ".{}.{:08x}.tmp",
+        file_name.to_string_lossy(),
+        rand::random::<u32>()
+    );
+    let temp_path = path.with_file_name(temp_name);
+
+    let write = || -> io::Result<()> {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, mode);
+        #[cfg(not(unix))]
+        let _ = mode;
+
+        let mut file = options.open(&temp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        if overwrite {
+            fs::rename(&temp_path, path)
+        } else {
+            fs::hard_link(&temp_path, path)?;
+            fs::remove_file(&temp_path)
+        }
+    };
+    write().map_err(|err| {
+        let _ = fs::remove_file(&temp_path);
+        with_path(err)
+    })
+}
+
+pub(crate) fn missing_public_exponent() -> Error {
+    Error::InvalidKey("The key does not contain the public exponent".to_string())
+}
+
+/// Writes a public key in the given format. The comment is only stored by
+/// `KeyFormat::Ssh`.
+fn write_public_key(
+    format: KeyFormat,
+    n: &BigInt,
+    e: &BigInt,
+    comment: Option<&str>,
+    output: &mut dyn Write,
+) -> error::Result<()> {
+    match format {
+        KeyFormat::Decimal => output.write_all(format!("{}\n{}", n, e).as_bytes())?,
+        KeyFormat::Ssh => output.write_all(ssh::encode_public(n, e, comment).as_bytes())?,
+        format => output.write_all(&key_format::encode_public(format, n, e))?,
+    }
+    Ok(())
+}
+
+/// Passphrase callback for keys that are not expected to be encrypted.
+fn no_passphrase() -> error::Result<Vec<u8>> {
+    Err(Error::Passphrase(
+        "The key is encrypted, but no passphrase was given".to_string(),
+    ))
+}
+
+/// Exponentiation with a precomputed context, if there is one for the modulus.
+fn pow(
+    context: &Option<MontgomeryContext>,
+    base: &BigInt,
+    exponent: &BigInt,
+    modulus: &BigInt,
+) -> BigInt {
+    match context {
+        Some(context) => context.pow(base, exponent),
+        None => modular_pow(base, exponent, modulus),
+    }
+}
+
+/// Like `pow`, but in constant time for a secret exponent.
+fn pow_secret(
+    context: &Option<MontgomeryContext>,
+    base: &BigInt,
+    exponent: &BigInt,
+    modulus: &BigInt,
+) -> BigInt {
+    match context {
+        Some(context) => context.pow_secret(base, exponent),
+        // Only even moduli have no context, and they are not valid keys.
+        None => modular_pow(base, exponent, modulus),
+    }
+}
+
+/// Transforms the blocks on up to `jobs` threads, each of which gets a
+/// contiguous range of the blocks, and returns the results in order.
+fn transform_blocks(
+    blocks: &[Vec<u8>],
+    jobs: usize,
+    transform: &(dyn Fn(&[u8]) -> error::Result<Vec<u8>> + Sync),
+) -> Vec<error::Result<Vec<u8>>> {
+    if jobs == 1 || blocks.len() < 2 {
+        return blocks.iter().map(|block| transform(block)).collect();
+    }
+
+    let chunk_len = blocks.len().div_ceil(jobs);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = blocks
+            .chunks(chunk_len)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|block| transform(block))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+/// Reads a stream as consecutive blocks of a fixed length. Reads are repeated
+/// until a block is full, so the blocks stay aligned however the input is split
+/// into reads, for example by a pipe.
+pub(crate) struct BlockReader<'a> {
+    input: &'a mut dyn Read,
+    block_len: usize,
+    finished: bool,
+}
+
+impl<'a> BlockReader<'a> {
+    pub(crate) fn new(input: &'a mut dyn Read, block_len: usize) -> BlockReader<'a> {
+        BlockReader {
+            input,
+            block_len,
+            finished: false,
+        }
+    }
+
+    /// Returns the next block, or `None` at the end of the input. Only the last
+    /// block can be shorter than the block length.
+    pub(crate) fn next_block(&mut self) -> io::Result<Option<Vec<u8>>> {
+        if self.finished {
+            return Ok(None);
+        }
+        let mut block = vec![0u8; self.block_len];
+        let amount_of_bytes_read = read_block(self.input, &mut block)?;
+        if amount_of_bytes_read < self.block_len || amount_of_bytes_read == 0 {
+            self.finished = true;
+            block.truncate(amount_of_bytes_read);
+        }
+        Ok(Some(block).filter(|block| !block.is_empty()))
+    }
+
+    /// Returns true once the end of the input has been reached.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+/// Reads until the buffer is full or the input ends. Returns the amount of bytes read.
+pub(crate) fn read_block(input: &mut dyn Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match input.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// A consistency check of a keypair performed by `KeyPair::validate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCheck {
+    /// Both halves have the same modulus and public exponent.
+    PublicKey,
+    /// The modulus is the product of the primes.
+    Modulus,
+    /// The first prime passes the Miller-Rabin test.
+    PrimeP,
+    /// The second prime passes the Miller-Rabin test.
+    PrimeQ,
+    /// The private exponent is an inverse of the public exponent modulo λ(n).
+    Exponents,
+    /// dP, dQ and qInv match the primes and the private exponent.
+    CrtValues,
+    /// A random number encrypted with the public key decrypts to itself.
+    RoundTrip,
+}
+
+impl fmt::Display for KeyCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            KeyCheck::PublicKey => "the public key matches the private key",
+            KeyCheck::Modulus => "n = p * q",
+            KeyCheck::PrimeP => "p is prime",
+            KeyCheck::PrimeQ => "q is prime",
+            KeyCheck::Exponents => "e * d = 1 mod lambda(n)",
+            KeyCheck::CrtValues => "dP, dQ and qInv match p, q and d",
+            KeyCheck::RoundTrip => "encryption round trip",
+        };
+        write!(f, "{}", description)
     }
 }
 
@@ -134,26 +870,275 @@ pub struct KeyPair {
 }
 
 impl KeyPair {
-    /// Generates a keypair with random prime numbers.
+    /// Generates a 2048-bit keypair with the public exponent 65537.
     pub fn generate() -> KeyPair {
+        KeyPair::generate_with(&KeyParams::default()).unwrap()
+    }
+
+    /// Generates a keypair with random prime numbers. The modulus has exactly
+    /// the requested amount of bits.
+    pub fn generate_with(params: &KeyParams) -> error::Result<KeyPair> {
+        params.validate()?;
+
+        let p_bits = params.bits / 2;
+        let q_bits = params.bits - p_bits;
         loop {
-            let p = generate_probable_prime();
-            let q = generate_probable_prime();
-            let e = BigInt::from(DEFAULT_EXP);
+            let p = generate_probable_prime(p_bits);
+            let q = generate_probable_prime(q_bits);
+            // FIPS 186-5, appendix A.1.3 requires |p - q| > 2^(nlen/2 - 100).
+            let distance = (&p - &q).magnitude().bits();
+            if distance <= params.bits / 2 - 100 || (&p * &q).bits() != params.bits {
+                continue;
+            }
 
-            match generate_from_primes(&p, &q, &e) {
-                Ok(key_pair) => return key_pair,
+            match generate_from_primes(&p, &q, &params.exponent) {
+                Ok(key_pair) => return Ok(key_pair),
                 Err(_) => continue,
             }
         }
     }
 
-    /// Writes the keypair to the current directory.
-    pub fn write_to_file(&self) -> std::io::Result<()> {
-        self.public.write_to_file("key.public")?;
-        self.private.write_to_file("key.private")?;
+    /// Reads a keypair from a private key file without checking its values, so
+    /// that it can be checked with `validate`. The public key is derived from the
+    /// private key unless a public key file is given, which is needed for private
+    /// keys that do not contain the public exponent.
+    pub fn from_private_file(
+        private_path: &str,
+        public_path: Option<&str>,
+        passphrase: &dyn Fn() -> error::Result<Vec<u8>>,
+    ) -> error::Result<KeyPair> {
+        let private = Key::read(private_path, passphrase)?;
+        match public_path {
+            Some(path) => Ok(KeyPair {
+                public: Key::read(path, &no_passphrase)?,
+                private,
+            }),
+            None => KeyPair::from_private_key(private),
+        }
+    }
+
+    /// Creates a keypair from a private key that contains the public exponent.
+    pub fn from_private_key(private: Key) -> error::Result<KeyPair> {
+        let e = private
+            .public_exponent()
+            .ok_or_else(missing_public_exponent)?;
+        let public = Key::new(e.clone(), private.modulus.clone(), None)
+            .with_comment(private.comment.clone());
+        Ok(KeyPair { public, private })
+    }
+
+    /// Checks that the values of the keypair are consistent and returns the
+    /// result of every check that applies to it. Without stored primes they are
+    /// recovered from the exponents, which only succeeds if `e * d = 1 mod λ(n)`,
+    /// and the checks of the primes are skipped if that fails. The CRT values
+    /// are only checked if they are stored.
+    pub fn validate(&self) -> Vec<(KeyCheck, bool)> {
+        let one = BigInt::one();
+        let n = &self.private.modulus;
+        let (e, d) = (&self.public.exp, &self.private.exp);
+        let mut results = Vec::new();
+
+        let public_key =
+            self.public.modulus == *n && self.private.crt.as_ref().is_none_or(|crt| crt.e == *e);
+        results.push((KeyCheck::PublicKey, public_key));
+
+        let primes = match &self.private.crt {
+            Some(crt) => Some((crt.p.clone(), crt.q.clone())),
+            None if n.bits() >= 16 && e.sign() == Sign::Plus && d.sign() == Sign::Plus => {
+                algorithms::recover_primes(n, e, d)
+            }
+            None => None,
+        };
+        match &primes {
+            Some((p, q)) => {
+                let is_prime = |prime: &BigInt| {
+                    algorithms::miller_rabin(prime, miller_rabin_rounds(prime.bits()))
+                };
+                results.push((KeyCheck::Modulus, p * q == *n));
+                results.push((KeyCheck::PrimeP, is_prime(p)));
+                results.push((KeyCheck::PrimeQ, is_prime(q)));
+
+                let exponents = *p > one && *q > one && {
+                    let lambda = algorithms::least_common_multiple(&(p - 1), &(q - 1));
+                    (e * d) % &lambda == one
+                };
+                results.push((KeyCheck::Exponents, exponents));
+            }
+            None => results.push((KeyCheck::Exponents, false)),
+        }
+
+        let mut crt_values = true;
+        if let Some(crt) = &self.private.crt {
+            crt_values = crt.p > one
+                && crt.q > one
+                && *crt == PrivateComponents::from_primes(n, &crt.e, d, &crt.p, &crt.q);
+            results.push((KeyCheck::CrtValues, crt_values));
+        }
+
+        let round_trip =
+            public_key && n.bits() >= 16 && e.sign() == Sign::Plus && d.sign() == Sign::Plus && {
+                // Inconsistent CRT values are not used, so that they can not
+                // break the exponentiation.
+                let plain;
+                let private = if crt_values {
+                    &self.private
+                } else {
+                    plain = Key::new(d.clone(), n.clone(), None);
+                    &plain
+                };
+                let m = rand::thread_rng().gen_bigint_range(&BigInt::from(2), n);
+                private.apply(&self.public.apply(&m)) == m
+            };
+        results.push((KeyCheck::RoundTrip, round_trip));
+
+        results
+    }
+
+    /// The public half of the keypair.
+    pub fn public(&self) -> &Key {
+        &self.public
+    }
+
+    /// The private half of the keypair.
+    pub fn private(&self) -> &Key {
+        &self.private
+    }
+
+    /// Sets the comment written to OpenSSH key files.
+    pub fn set_comment(&mut self, comment: &str) {
+        self.public.comment = Some(comment.to_string());
+        self.private.comment = Some(comment.to_string());
+    }
+
+    /// Writes the keypair to `key.public` and `key.private` in the current directory.
+    pub fn write_to_file(&self) -> error::Result<()> {
+        self.write_to_file_as(KeyFormat::Decimal)
+    }
+
+    /// Writes the keypair to `key.public` and `key.private` in the current
+    /// directory in the given format, replacing existing files.
+    pub fn write_to_file_as(&self, format: KeyFormat) -> error::Result<()> {
+        self.save(
+            Path::new("key.public"),
+            Path::new("key.private"),
+            format,
+            None,
+            true,
+        )
+    }
+
+    /// Writes the keypair to the given files, with the private key encrypted if
+    /// a passphrase is given.
+    ///
+    /// Each file is first written to a temporary file in the same directory and
+    /// then moved in place, so that it is never left half-written. The private
+    /// key file is only readable by its owner. Existing files are only replaced
+    /// if `overwrite` is set.
+    pub fn save(
+        &self,
+        public_path: &Path,
+        private_path: &Path,
+        format: KeyFormat,
+        passphrase: Option<&[u8]>,
+        overwrite: bool,
+    ) -> error::Result<()> {
+        let mut public = Vec::new();
+        self.write_public(format, &mut public)?;
+        let mut private = Vec::new();
+        match passphrase {
+            Some(passphrase) => self.write_encrypted_private(format, passphrase, &mut private)?,
+            None => self.write_private(format, &mut private)?,
+        }
+
+        if !overwrite {
+            if let Some(path) = [public_path, private_path]
+                .into_iter()
+                .find(|path| path.exists())
+            {
+                return Err(Error::Io(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} already exists", path.display()),
+                )));
+            }
+        }
+        write_file_atomically(private_path, &private, PRIVATE_FILE_MODE, overwrite)?;
+        write_file_atomically(public_path, &public, PUBLIC_FILE_MODE, overwrite)?;
+        Ok(())
+    }
+
+    /// Writes the public key in the given format.
+    pub fn write_public(&self, format: KeyFormat, output: &mut dyn Write) -> error::Result<()> {
+        write_public_key(
+            format,
+            &self.public.modulus,
+            &self.public.exp,
+            self.public.comment(),
+            output,
+        )
+    }
+
+    /// Writes the private key in the given format.
+    pub fn write_private(&self, format: KeyFormat, output: &mut dyn Write) -> error::Result<()> {
+        match format {
+            KeyFormat::Decimal => self.private.write_decimal(output)?,
+            KeyFormat::Ssh => output.write_all(&ssh::encode_private(
+                &self.private_components()?,
+                self.private.comment(),
+                None,
+            ))?,
+            format => output.write_all(&key_format::encode_private(
+                format,
+                &self.private_components()?,
+            ))?,
+        }
+        Ok(())
+    }
+
+    /// Writes the private key encrypted with the passphrase. Only `KeyFormat::Pkcs8`,
+    /// `KeyFormat::Pkcs8Der` and `KeyFormat::Ssh` support encryption.
+    pub fn write_encrypted_private(
+        &self,
+        format: KeyFormat,
+        passphrase: &[u8],
+        output: &mut dyn Write,
+    ) -> error::Result<()> {
+        if format == KeyFormat::Ssh {
+            // bcrypt_pbkdf is not defined for an empty passphrase.
+            if passphrase.is_empty() {
+                return Err(Error::Passphrase(
+                    "The passphrase must not be empty".to_string(),
+                ));
+            }
+            output.write_all(&ssh::encode_private(
+                &self.private_components()?,
+                self.private.comment(),
+                Some(passphrase),
+            ))?;
+            return Ok(());
+        }
+        let private =
+            key_format::encode_encrypted_private(format, &self.private_components()?, passphrase)
+                .ok_or_else(|| {
+                Error::InvalidKey(format!(
+                    "Keys in the {} format can not be encrypted",
+                    format
+                ))
+            })?;
+        output.write_all(&private)?;
         Ok(())
     }
+
+    /// Returns all values of the private key. Keys read from old decimal files
+    /// do not store the primes, so they are recovered from the exponents.
+    fn private_components(&self) -> error::Result<PrivateComponents> {
+        if let Some(crt) = &self.private.crt {
+            return Ok(crt.clone());
+        }
+        let (n, e, d) = (&self.public.modulus, &self.public.exp, &self.private.exp);
+        let (p, q) = algorithms::recover_primes(n, e, d)
+            .ok_or_else(|| Error::InvalidKey("Failed to recover the primes".to_string()))?;
+        Ok(PrivateComponents::from_primes(n, e, d, &p, &q))
+    }
 }
 
 /// Generates a keypair from two prime numbers.
@@ -168,44 +1153,106 @@ fn generate_from_primes(p: &BigInt, q: &BigInt, e: &BigInt) -> Result<KeyPair, &
     if gcd != BigInt::one() {
         Err("No multiplicative inverse found")
     } else {
+        let crt = PrivateComponents::from_primes(&n, e, &d, p, q);
         Ok(KeyPair {
-            public: Key {
-                exp: e.clone(),
-                modulus: n.clone(),
-            },
-            private: Key { exp: d, modulus: n },
+            public: Key::new(e.clone(), n.clone(), None),
+            private: Key::new(d, n, Some(crt)),
         })
     }
 }
 
-/// Generates a probable prime number by testing randomly generated numbers
-/// numbers for primality.
-fn generate_probable_prime() -> BigInt {
-    let mut num: BigInt = rand::thread_rng().sample(RandomBits::new(KEY_SIZE));
-    while !algorithms::miller_rabin(&num, MR_ITERATIONS) {
-        num = rand::thread_rng().sample(RandomBits::new(KEY_SIZE));
+/// Generates a probable prime number of the given length.
+///
+/// A random odd number with the two highest bits set is chosen, so that the
+/// product of two such primes has exactly twice as many bits. The following odd
+/// numbers are then sieved with the small primes, and only the candidates that
+/// are not divisible by any of them are tested with Miller-Rabin.
+fn generate_probable_prime(bits: u64) -> BigInt {
+    let small_primes = algorithms::small_primes(SIEVE_LIMIT);
+    let rounds = miller_rabin_rounds(bits);
+
+    loop {
+        let mut start: BigUint = rand::thread_rng().sample(RandomBits::new(bits));
+        start.set_bit(bits - 1, true);
+        start.set_bit(bits - 2, true);
+        start.set_bit(0, true);
+
+        let residues: Vec<u32> = small_primes
+            .iter()
+            .map(|prime| (&start % prime).to_u32().unwrap())
+            .collect();
+
+        for delta in (0..MAX_SIEVE_DISTANCE).step_by(2) {
+            let divisible = residues
+                .iter()
+                .zip(&small_primes)
+                .any(|(residue, prime)| (residue + delta) % prime == 0);
+            if divisible {
+                continue;
+            }
+
+            let candidate = BigInt::from(&start + delta);
+            if candidate.bits() != bits {
+                break;
+            }
+            if algorithms::miller_rabin(&candidate, rounds) {
+                return candidate;
+            }
+        }
+    }
+}
+
+/// The number of Miller-Rabin rounds for random candidates of the given length,
+/// from FIPS 186-5, table B.1. Shorter primes than those in the table get enough
+/// rounds for an error probability below 2^-100.
+fn miller_rabin_rounds(bits: u64) -> isize {
+    match bits {
+        1536.. => 4,
+        1024.. => 5,
+        512.. => 8,
+        _ => 17,
+    }
+}
+
+/// A fixed 2048-bit keypair for the unit tests of other modules.
+#[cfg(test)]
+pub(crate) fn test_key_pair() -> KeyPair {
+    KeyPair {
+        private: Key::new(
+            BigInt::from_str("83823589842337345716418534590881196875435896898405184197878463072091858738260690885059858855840890997781375963671981878180622207276287410204364232662535538028357299053623155931732212167813402837119710102265467966716905494371924280804633753980549664902039873368265936932500324990678840725836417520570031050977020391623788866928987762824073297097323130060361852489990090764313238485589932494406080968498265640453817169552540095733083773775638207721011670181249752626263778607463361772158444127287048534847623537777283779305764113256091027874343061999145002176744068249207148202460458229711423683286138201987329205533").unwrap(),
+            BigInt::from_str("1036094667116699957794031654006081978994519669637716761721879892060921789104339276119982642913634892651733197723792916672490510973174371735308852113790826056473350952392537787124370663975479266036459517990539460120339327077229962893991754754588995075350011727457840136185573281158165376037935679447822863292727314069042603139807056816418241224303148746879694236180240345992665098156479345946045666246915319417310506472587982909698492734403006522827558508404716161793199143147983405663796210020223235604312596277251183247192863971627633753966391027463195544174516160377912482286386280685703288417180144207312345469879").unwrap(),
+            None,
+        ),
+        public: Key::new(
+            BigInt::from_str("65537").unwrap(),
+            BigInt::from_str("1036094667116699957794031654006081978994519669637716761721879892060921789104339276119982642913634892651733197723792916672490510973174371735308852113790826056473350952392537787124370663975479266036459517990539460120339327077229962893991754754588995075350011727457840136185573281158165376037935679447822863292727314069042603139807056816418241224303148746879694236180240345992665098156479345946045666246915319417310506472587982909698492734403006522827558508404716161793199143147983405663796210020223235604312596277251183247192863971627633753966391027463195544174516160377912482286386280685703288417180144207312345469879").unwrap(),
+            None,
+        )
+    }
+}
+
+/// The keypair of `test_key_pair` with the primes and the public exponent in
+/// the private key.
+#[cfg(test)]
+pub(crate) fn test_crt_key_pair() -> KeyPair {
+    let keys = test_key_pair();
+    let crt = keys.private_components().unwrap();
+    KeyPair {
+        private: Key::new(crt.d.clone(), crt.n.clone(), Some(crt)),
+        public: keys.public,
     }
-    num
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use num_bigint::BigInt;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
     use std::str::FromStr;
 
     fn get_test_keys() -> KeyPair {
-        KeyPair {
-            private: Key {
-                modulus: BigInt::from_str("1036094667116699957794031654006081978994519669637716761721879892060921789104339276119982642913634892651733197723792916672490510973174371735308852113790826056473350952392537787124370663975479266036459517990539460120339327077229962893991754754588995075350011727457840136185573281158165376037935679447822863292727314069042603139807056816418241224303148746879694236180240345992665098156479345946045666246915319417310506472587982909698492734403006522827558508404716161793199143147983405663796210020223235604312596277251183247192863971627633753966391027463195544174516160377912482286386280685703288417180144207312345469879").unwrap(),
-                exp: BigInt::from_str("83823589842337345716418534590881196875435896898405184197878463072091858738260690885059858855840890997781375963671981878180622207276287410204364232662535538028357299053623155931732212167813402837119710102265467966716905494371924280804633753980549664902039873368265936932500324990678840725836417520570031050977020391623788866928987762824073297097323130060361852489990090764313238485589932494406080968498265640453817169552540095733083773775638207721011670181249752626263778607463361772158444127287048534847623537777283779305764113256091027874343061999145002176744068249207148202460458229711423683286138201987329205533").unwrap(),
-            },
-            public: Key {
-                modulus: BigInt::from_str("1036094667116699957794031654006081978994519669637716761721879892060921789104339276119982642913634892651733197723792916672490510973174371735308852113790826056473350952392537787124370663975479266036459517990539460120339327077229962893991754754588995075350011727457840136185573281158165376037935679447822863292727314069042603139807056816418241224303148746879694236180240345992665098156479345946045666246915319417310506472587982909698492734403006522827558508404716161793199143147983405663796210020223235604312596277251183247192863971627633753966391027463195544174516160377912482286386280685703288417180144207312345469879").unwrap(),
-                exp: BigInt::from_str("65537").unwrap(),
-
-            }
-        }
+        test_key_pair()
     }
 
     #[test]
@@ -265,6 +1312,856 @@ mod tests {
         assert_eq!(original, decrypted)
     }
 
+    #[test]
+    fn decrypt_with_oaep_returns_original_string() {
+        let keys = get_test_keys();
+
+        let original = vec![42u8; 1000];
+        let mut encrypted = Vec::new();
+
+        keys.public
+            .encrypt_with(&Padding::oaep(), &mut &original[..], &mut encrypted)
+            .unwrap();
+
+        let mut decrypted = Vec::new();
+
+        keys.private
+            .decrypt_with(&Padding::oaep(), &mut &encrypted[..], &mut decrypted)
+            .unwrap();
+
+        assert_eq!(original, decrypted)
+    }
+
+    #[test]
+    fn decrypt_with_oaep_rejects_wrong_label() {
+        let keys = get_test_keys();
+        let padding = Padding::Oaep {
+            hash: crate::hash::HashAlgorithm::Sha256,
+            label: b"label".to_vec(),
+        };
+
+        let mut encrypted = Vec::new();
+        keys.public
+            .encrypt_with(&padding, &mut &b"FooBarBaz"[..], &mut encrypted)
+            .unwrap();
+
+        let err = keys
+            .private
+            .decrypt_with(&Padding::oaep(), &mut &encrypted[..], &mut Vec::new())
+            .unwrap_err();
+
+        assert!(matches!(err, Error::Padding(PaddingError::Decryption)));
+    }
+
+    #[test]
+    fn parallel_encrypt_matches_sequential_encrypt() {
+        let mut keys = get_test_crt_key_pair();
+        // More than one batch of blocks, with a partial last block.
+        let message: Vec<u8> = (0..255 * 50 + 17).map(|i| (i % 251) as u8).collect();
+
+        let mut sequential = Vec::new();
+        keys.public
+            .encrypt(&mut &message[..], &mut sequential)
+            .unwrap();
+        keys.public.set_jobs(NonZeroUsize::new(3).unwrap());
+        keys.private.set_jobs(NonZeroUsize::new(3).unwrap());
+        let mut parallel = Vec::new();
+        keys.public
+            .encrypt(&mut &message[..], &mut parallel)
+            .unwrap();
+        let mut decrypted = Vec::new();
+        keys.private
+            .decrypt(&mut &parallel[..], &mut decrypted)
+            .unwrap();
+
+        assert_eq!(sequential, parallel);
+        assert_eq!(message, decrypted);
+    }
+
+    #[test]
+    fn parallel_decrypt_with_keeps_block_order_and_errors() {
+        let mut keys = get_test_crt_key_pair();
+        keys.private.set_jobs(NonZeroUsize::new(4).unwrap());
+        let message: Vec<u8> = (0..190 * 5).map(|i| (i % 256) as u8).collect();
+        let mut encrypted = Vec::new();
+        keys.public
+            .encrypt_with(&Padding::oaep(), &mut &message[..], &mut encrypted)
+            .unwrap();
+
+        let mut decrypted = Vec::new();
+        keys.private
+            .decrypt_with(&Padding::oaep(), &mut &encrypted[..], &mut decrypted)
+            .unwrap();
+        assert_eq!(message, decrypted);
+
+        let mut decrypted = Vec::new();
+        let result = keys.private.decrypt_with(
+            &Padding::oaep(),
+            &mut &encrypted[..encrypted.len() - 1],
+            &mut decrypted,
+        );
+        assert!(matches!(result, Err(Error::Padding(_))));
+        assert_eq!(message[..190 * 4], decrypted);
+    }
+
+    #[test]
+    fn decrypt_with_oaep_rejects_truncated_input() {
+        let keys = get_test_keys();
+
+        let mut encrypted = Vec::new();
+        keys.public
+            .encrypt_with(&Padding::oaep(), &mut &b"FooBarBaz"[..], &mut encrypted)
+            .unwrap();
+        encrypted.pop();
+
+        assert!(keys
+            .private
+            .decrypt_with(&Padding::oaep(), &mut &encrypted[..], &mut Vec::new())
+            .is_err());
+    }
+
+    /// A reader that returns the data in reads of the given lengths, like a pipe.
+    struct ChunkedReader<'a> {
+        data: &'a [u8],
+        chunk_lens: Vec<usize>,
+        reads: usize,
+    }
+
+    impl Read for ChunkedReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let chunk_len = self.chunk_lens[self.reads % self.chunk_lens.len()];
+            let len = chunk_len.min(buf.len()).min(self.data.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            self.reads += 1;
+            Ok(len)
+        }
+    }
+
+    fn chunked(data: &[u8], chunk_lens: Vec<usize>) -> ChunkedReader<'_> {
+        ChunkedReader {
+            data,
+            chunk_lens,
+            reads: 0,
+        }
+    }
+
+    fn shared_crt_key_pair() -> &'static KeyPair {
+        static KEYS: std::sync::OnceLock<KeyPair> = std::sync::OnceLock::new();
+        KEYS.get_or_init(get_test_crt_key_pair)
+    }
+
+    fn raw_encrypt(message: &[u8]) -> Vec<u8> {
+        let mut encrypted = Vec::new();
+        shared_crt_key_pair()
+            .public
+            .encrypt(&mut &message[..], &mut encrypted)
+            .unwrap();
+        encrypted
+    }
+
+    proptest! {
+        #[test]
+        fn block_reader_keeps_blocks_aligned(
+            data in vec(any::<u8>(), 0..2000),
+            block_len in 1usize..300,
+            chunk_lens in vec(1usize..500, 1..8),
+        ) {
+            let mut input = chunked(&data, chunk_lens);
+            let mut reader = BlockReader::new(&mut input, block_len);
+            let mut blocks = Vec::new();
+            while let Some(block) = reader.next_block().unwrap() {
+                blocks.push(block);
+            }
+
+            prop_assert_eq!(data.len().div_ceil(block_len), blocks.len());
+            for block in blocks.iter().take(blocks.len().saturating_sub(1)) {
+                prop_assert_eq!(block_len, block.len());
+            }
+            prop_assert_eq!(data, blocks.concat());
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(16))]
+
+        #[test]
+        fn raw_decrypt_returns_original_data_from_chunked_input(
+            message in vec(any::<u8>(), 0..1000),
+            chunk_lens in vec(1usize..600, 1..8),
+        ) {
+            let encrypted = raw_encrypt(&message);
+            let mut decrypted = Vec::new();
+            shared_crt_key_pair()
+                .private
+                .decrypt(&mut chunked(&encrypted, chunk_lens), &mut decrypted)
+                .unwrap();
+
+            prop_assert_eq!(message, decrypted);
+        }
+
+        #[test]
+        fn raw_decrypt_rejects_truncated_input(
+            message in vec(any::<u8>(), 1..1000),
+            cut in 1usize..256,
+        ) {
+            let encrypted = raw_encrypt(&message);
+            let result = shared_crt_key_pair().private.decrypt(
+                &mut &encrypted[..encrypted.len() - cut],
+                &mut Vec::new(),
+            );
+
+            prop_assert!(matches!(result, Err(Error::Padding(PaddingError::Truncated))));
+        }
+
+        #[test]
+        fn raw_decrypt_detects_corrupted_block(
+            message in vec(any::<u8>(), 1..1000),
+            position in any::<prop::sample::Index>(),
+            bit in 0u8..8,
+        ) {
+            let mut encrypted = raw_encrypt(&message);
+            let position = position.index(encrypted.len());
+            encrypted[position] ^= 1 << bit;
+            let mut decrypted = Vec::new();
+            let result = shared_crt_key_pair()
+                .private
+                .decrypt(&mut &encrypted[..], &mut decrypted);
+
+            prop_assert!(result.is_err() || decrypted != message);
+        }
+    }
+
+    #[test]
+    fn raw_decrypt_rejects_wrong_key() {
+        let keys = get_test_crt_key_pair();
+        let encrypted = raw_encrypt(b"FooBarBaz");
+
+        // Decrypting with the public key does not produce the marker byte.
+        let result = keys.public.decrypt(&mut &encrypted[..], &mut Vec::new());
+
+        assert!(matches!(
+            result,
+            Err(Error::Padding(PaddingError::Decryption))
+        ));
+    }
+
+    #[test]
+    fn encrypt_container_rejects_key_too_large_for_header() {
+        let modulus = (BigInt::from(1) << (8 * 65536)) - 1;
+        let key = Key::new(BigInt::from(65537), modulus, None);
+
+        let result =
+            key.encrypt_container(&Padding::oaep(), &mut &b"FooBarBaz"[..], &mut Vec::new());
+
+        assert!(matches!(result, Err(Error::Size(_))));
+    }
+
+    #[test]
+    fn decrypt_with_pkcs1v15_returns_original_string() {
+        let keys = get_test_keys();
+
+        let original = vec![7u8; 1000];
+        let mut encrypted = Vec::new();
+
+        keys.public
+            .encrypt_with(&Padding::Pkcs1v15, &mut &original[..], &mut encrypted)
+            .unwrap();
+
+        let mut decrypted = Vec::new();
+
+        keys.private
+            .decrypt_with(&Padding::Pkcs1v15, &mut &encrypted[..], &mut decrypted)
+            .unwrap();
+
+        assert_eq!(original, decrypted)
+    }
+
+    #[test]
+    fn decrypt_with_pkcs1v15_hides_invalid_padding() {
+        let keys = get_test_keys();
+
+        let mut encrypted = Vec::new();
+        keys.public
+            .encrypt_with(&Padding::oaep(), &mut &b"FooBarBaz"[..], &mut encrypted)
+            .unwrap();
+        let decrypt = |key: &Key| {
+            let mut decrypted = Vec::new();
+            key.decrypt_with(&Padding::Pkcs1v15, &mut &encrypted[..], &mut decrypted)
+                .map(|_| decrypted)
+        };
+
+        // A block with invalid padding decrypts to a synthetic message, which is
+        // the same every time, and not to an error.
+        let synthetic = decrypt(&keys.private).unwrap();
+        assert_ne!(b"FooBarBaz".to_vec(), synthetic);
+        assert_eq!(synthetic, decrypt(&keys.private).unwrap());
+        assert_eq!(synthetic, decrypt(&test_crt_key_pair().private).unwrap());
+    }
+
+    // The message OpenSSL 3.5 returns with implicit rejection for the ciphertext 2.
+    #[test]
+    fn decrypt_block_with_pkcs1v15_matches_openssl_implicit_rejection() {
+        let keys = get_test_keys();
+        let mut block = vec![0u8; 256];
+        block[255] = 2;
+
+        assert_eq!(
+            crate::encoding::hex_decode(
+                "4282c27f112273bcfbbff8feea49f72b8b178dbdfb45c2141446581810a3cb84\
+                 c434c6b20fde5cfe81eb33e662e6864cf36faa48170e4f837ace6a74f8a6b189\
+                 14f275c52bb6fcd2d6c752d9e6d6c4324454c6b5d6f603706584de74375fcd87\
+                 d94e908a432b"
+            )
+            .unwrap(),
+            keys.private
+                .decrypt_block(&Padding::Pkcs1v15, &block)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn verify_with_pss_rejects_too_large_representative() {
+        // With the exponent 1 the signature is the representative itself. The
+        // modulus has 1025 bits, so the encoded message is one byte shorter.
+        let modulus = (BigInt::from(1) << 1025) - 1;
+        let key = Key::new(BigInt::from(1), modulus, None);
+        let scheme = SignatureScheme::Pss {
+            hash: crate::hash::HashAlgorithm::Sha256,
+        };
+        let m_hash = scheme.hash().digest(b"FooBarBaz");
+        let em = scheme.encode(&m_hash, 1025).unwrap();
+        assert_eq!(128, em.len());
+
+        let signature = key.to_block(&padding::os2ip(&em));
+        assert!(key
+            .verify(&scheme, &mut &b"FooBarBaz"[..], &signature)
+            .unwrap());
+
+        // The same encoded message with a byte in front of it.
+        let too_large = padding::os2ip(&em) + (BigInt::from(1) << 1024);
+        let signature = key.to_block(&too_large);
+        assert!(!key
+            .verify(&scheme, &mut &b"FooBarBaz"[..], &signature)
+            .unwrap());
+    }
+
+    #[test]
+    fn sign_rejects_public_key() {
+        let keys = get_test_keys();
+        let scheme = SignatureScheme::Pss {
+            hash: crate::hash::HashAlgorithm::Sha256,
+        };
+
+        assert!(matches!(
+            keys.public.sign(&scheme, &mut &b"FooBarBaz"[..]),
+            Err(Error::InvalidKey(_))
+        ));
+    }
+
+    #[test]
+    fn verify_accepts_signature() {
+        let keys = get_test_keys();
+        let hash = crate::hash::HashAlgorithm::Sha256;
+
+        for scheme in [
+            SignatureScheme::Pkcs1v15 { hash },
+            SignatureScheme::Pss { hash },
+        ] {
+            let signature = keys.private.sign(&scheme, &mut &b"FooBarBaz"[..]).unwrap();
+
+            assert_eq!(256, signature.len());
+            assert!(keys
+                .public
+                .verify(&scheme, &mut &b"FooBarBaz"[..], &signature)
+                .unwrap());
+        }
+    }
+
+    #[test]
+    fn verify_rejects_signature_for_other_message() {
+        let keys = get_test_keys();
+        let hash = crate::hash::HashAlgorithm::Sha256;
+
+        for scheme in [
+            SignatureScheme::Pkcs1v15 { hash },
+            SignatureScheme::Pss { hash },
+        ] {
+            let signature = keys.private.sign(&scheme, &mut &b"FooBarBaz"[..]).unwrap();
+
+            assert!(!keys
+                .public
+                .verify(&scheme, &mut &b"FooBarBax"[..], &signature)
+                .unwrap());
+        }
+    }
+
+    #[test]
+    fn verify_rejects_signature_with_other_scheme() {
+        let keys = get_test_keys();
+        let hash = crate::hash::HashAlgorithm::Sha256;
+
+        let signature = keys
+            .private
+            .sign(&SignatureScheme::Pss { hash }, &mut &b"FooBarBaz"[..])
+            .unwrap();
+
+        assert!(!keys
+            .public
+            .verify(
+                &SignatureScheme::Pkcs1v15 { hash },
+                &mut &b"FooBarBaz"[..],
+                &signature
+            )
+            .unwrap());
+    }
+
+    #[test]
+    fn pkcs1v15_signature_is_deterministic() {
+        let keys = get_test_keys();
+        let scheme = SignatureScheme::Pkcs1v15 {
+            hash: crate::hash::HashAlgorithm::Sha256,
+        };
+
+        assert_eq!(
+            keys.private.sign(&scheme, &mut &b"FooBarBaz"[..]).unwrap(),
+            keys.private.sign(&scheme, &mut &b"FooBarBaz"[..]).unwrap()
+        );
+    }
+
+    #[test]
+    fn decrypt_hybrid_returns_original_data() {
+        let keys = get_test_keys();
+
+        let original: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let mut encrypted = Vec::new();
+
+        keys.public
+            .encrypt_hybrid(&Padding::oaep(), &mut &original[..], &mut encrypted)
+            .unwrap();
+
+        let mut decrypted = Vec::new();
+
+        keys.private
+            .decrypt_hybrid(b"", &mut &encrypted[..], &mut decrypted)
+            .unwrap();
+
+        assert_eq!(original, decrypted)
+    }
+
+    #[test]
+    fn private_components_recovers_primes() {
+        let keys = get_test_keys();
+
+        let components = keys.private_components().unwrap();
+
+        assert_eq!(keys.public.modulus, &components.p * &components.q);
+        assert_eq!(keys.private.exp, components.d);
+    }
+
+    fn get_test_crt_key() -> Key {
+        let keys = get_test_keys();
+        let crt = keys.private_components().unwrap();
+        Key::new(crt.d.clone(), crt.n.clone(), Some(crt))
+    }
+
+    #[test]
+    fn crt_key_matches_plain_key() {
+        let keys = get_test_keys();
+        let crt_key = get_test_crt_key();
+        let scheme = SignatureScheme::Pkcs1v15 {
+            hash: crate::hash::HashAlgorithm::Sha256,
+        };
+
+        assert_eq!(
+            keys.private.sign(&scheme, &mut &b"FooBarBaz"[..]).unwrap(),
+            crt_key.sign(&scheme, &mut &b"FooBarBaz"[..]).unwrap()
+        );
+
+        let mut encrypted = Vec::new();
+        keys.public
+            .encrypt_with(&Padding::oaep(), &mut &b"FooBarBaz"[..], &mut encrypted)
+            .unwrap();
+        let mut decrypted = Vec::new();
+        crt_key
+            .decrypt_with(&Padding::oaep(), &mut &encrypted[..], &mut decrypted)
+            .unwrap();
+
+        assert_eq!(b"FooBarBaz".to_vec(), decrypted);
+    }
+
+    #[test]
+    fn apply_detects_faulty_crt_result() {
+        let keys = get_test_keys();
+        let mut crt_key = get_test_crt_key();
+        crt_key.crt.as_mut().unwrap().dp += 2;
+
+        let x = BigInt::from(123_456_789);
+
+        assert_eq!(keys.private.apply(&x), crt_key.apply(&x));
+    }
+
+    #[test]
+    fn apply_with_blinding_matches_plain_exponentiation() {
+        let crt_key = get_test_crt_key();
+        let x = BigInt::from(123_456_789);
+
+        let expected = modular_pow(&x, &crt_key.exp, &crt_key.modulus);
+
+        assert_eq!(expected, crt_key.apply(&x));
+        assert_eq!(expected, crt_key.apply(&x));
+    }
+
+    #[test]
+    fn is_private_detects_private_exponent() {
+        let keys = get_test_keys();
+
+        assert!(keys.private.is_private());
+        assert!(!keys.public.is_private());
+        assert!(get_test_crt_key().is_private());
+    }
+
+    #[test]
+    fn fingerprint_is_same_for_both_halves() {
+        let keys = get_test_keys();
+        let crt_key = get_test_crt_key();
+
+        assert_eq!(32, keys.public.fingerprint().unwrap().len());
+        assert_eq!(keys.public.fingerprint(), crt_key.fingerprint());
+        assert_eq!(None, keys.private.fingerprint());
+    }
+
+    #[test]
+    fn decimal_file_keeps_crt_values() {
+        let crt_key = get_test_crt_key();
+        let path = std::env::temp_dir().join(format!("rsa-test-{}.private", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        crt_key
+            .write_decimal(&mut fs::File::create(path).unwrap())
+            .unwrap();
+        let read = Key::from_file(path);
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(crt_key.crt, read.unwrap().crt);
+    }
+
+    #[test]
+    fn save_writes_private_key_for_owner_only() {
+        let keys = get_test_crt_key_pair();
+        let dir = std::env::temp_dir().join(format!("rsa-test-{}-save", std::process::id()));
+        fs::create_dir(&dir).unwrap();
+        let (public_path, private_path) = (dir.join("key.public"), dir.join("key.private"));
+
+        let first = keys.save(&public_path, &private_path, KeyFormat::Pkcs8, None, false);
+        let read = Key::from_file(private_path.to_str().unwrap());
+        #[cfg(unix)]
+        let mode = std::os::unix::fs::PermissionsExt::mode(
+            &fs::metadata(&private_path).unwrap().permissions(),
+        );
+        let second = keys.save(&public_path, &private_path, KeyFormat::Pkcs8, None, false);
+        let forced = keys.save(&public_path, &private_path, KeyFormat::Decimal, None, true);
+        let files = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+
+        first.unwrap();
+        assert_eq!(keys.private.crt, read.unwrap().crt);
+        #[cfg(unix)]
+        assert_eq!(0o600, mode & 0o777);
+        assert!(
+            matches!(second, Err(Error::Io(err)) if err.kind() == io::ErrorKind::AlreadyExists)
+        );
+        forced.unwrap();
+        assert_eq!(2, files);
+    }
+
+    #[test]
+    fn jwk_file_is_detected() {
+        let keys = get_test_crt_key_pair();
+        let path = std::env::temp_dir().join(format!("rsa-test-{}.jwk", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        keys.write_private(KeyFormat::Jwk, &mut fs::File::create(path).unwrap())
+            .unwrap();
+        let read = Key::from_file(path);
+        std::fs::remove_file(path).unwrap();
+
+        let read = read.unwrap();
+        assert_eq!(keys.private.crt, read.crt);
+        assert_eq!(keys.public.jwk_thumbprint(), read.jwk_thumbprint());
+    }
+
+    #[test]
+    fn write_jwk_set_writes_public_keys() {
+        let keys = get_test_crt_key_pair();
+        let mut output = Vec::new();
+
+        Key::write_jwk_set(&[keys.private, keys.public], &mut output).unwrap();
+
+        let set = crate::json::parse(std::str::from_utf8(&output).unwrap()).unwrap();
+        let members = set.get("keys").and_then(Value::as_array).unwrap();
+        assert_eq!(2, members.len());
+        assert_eq!(members[0], members[1]);
+        assert_eq!(None, members[0].get("d"));
+        assert!(matches!(
+            Key::write_jwk_set(&[get_test_keys().private], &mut Vec::new()),
+            Err(Error::InvalidKey(_))
+        ));
+    }
+
+    #[test]
+    fn ssh_files_keep_comment() {
+        let mut keys = get_test_crt_key_pair();
+        keys.set_comment("alice@example");
+        let dir = std::env::temp_dir().join(format!("rsa-test-{}-ssh", std::process::id()));
+        fs::create_dir(&dir).unwrap();
+        let (public_path, private_path) = (dir.join("key.public"), dir.join("key.private"));
+
+        keys.save(&public_path, &private_path, KeyFormat::Ssh, None, false)
+            .unwrap();
+        let public = Key::from_file(public_path.to_str().unwrap());
+        let private = KeyPair::from_private_file(private_path.to_str().unwrap(), None, &|| {
+            panic!("passphrase requested")
+        });
+        fs::remove_dir_all(&dir).unwrap();
+
+        let (public, private) = (public.unwrap(), private.unwrap());
+        assert_eq!(keys.public.modulus, public.modulus);
+        assert_eq!(Some("alice@example"), public.comment());
+        assert_eq!(keys.private.crt, private.private.crt);
+        assert_eq!(Some("alice@example"), private.public.comment());
+        assert_eq!(public.ssh_fingerprint(), private.private.ssh_fingerprint());
+    }
+
+    #[test]
+    fn write_public_writes_to_any_sink() {
+        let keys = get_test_keys();
+        let mut decimal = Vec::new();
+        let mut pem = Vec::new();
+
+        keys.write_public(KeyFormat::Decimal, &mut decimal).unwrap();
+        keys.write_public(KeyFormat::Pkcs8, &mut pem).unwrap();
+
+        assert_eq!(
+            format!("{}\n{}", keys.public.modulus, keys.public.exp).into_bytes(),
+            decimal
+        );
+        assert!(pem.starts_with(b"-----BEGIN PUBLIC KEY-----"));
+    }
+
+    #[test]
+    fn write_encrypted_private_rejects_unsupported_format() {
+        let keys = get_test_crt_key_pair();
+        let mut output = Vec::new();
+
+        let result = keys.write_encrypted_private(KeyFormat::Pkcs1, b"passphrase", &mut output);
+
+        assert!(matches!(result, Err(Error::InvalidKey(_))));
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn encrypted_file_needs_passphrase() {
+        let crt_key = get_test_crt_key();
+        let der = key_format::pkcs8_der(crt_key.crt.as_ref().unwrap());
+        let encrypted = crate::pbes2::encrypt_with(&der, b"passphrase", 10);
+        let path = std::env::temp_dir().join(format!("rsa-test-{}.encrypted", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        std::fs::write(path, pem::encode("ENCRYPTED PRIVATE KEY", &encrypted)).unwrap();
+        let without_passphrase = Key::from_file(path);
+        let with_passphrase = Key::from_file_with_passphrase(path, &|| Ok(b"passphrase".to_vec()));
+        std::fs::remove_file(path).unwrap();
+
+        assert!(matches!(without_passphrase, Err(Error::Passphrase(_))));
+        assert_eq!(crt_key.crt, with_passphrase.unwrap().crt);
+    }
+
+    #[test]
+    fn passphrase_is_only_requested_for_encrypted_keys() {
+        let crt_key = get_test_crt_key();
+        let path = std::env::temp_dir().join(format!("rsa-test-{}.plain", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        crt_key
+            .write_decimal(&mut fs::File::create(path).unwrap())
+            .unwrap();
+        let read = Key::from_file_with_passphrase(path, &|| panic!("passphrase requested"));
+        std::fs::remove_file(path).unwrap();
+
+        assert!(read.is_ok());
+    }
+
+    #[test]
+    fn decimal_file_without_crt_values_is_accepted() {
+        let keys = get_test_keys();
+        let contents = format!("{}\n{}\n", keys.private.modulus, keys.private.exp);
+
+        let key = Key::from_decimal(contents.as_bytes(), "test").unwrap();
+
+        assert_eq!(keys.private.exp, key.exp);
+        assert_eq!(None, key.crt);
+    }
+
+    #[test]
+    fn generate_with_returns_key_of_requested_size() {
+        let params = KeyParams {
+            bits: 521,
+            exponent: BigInt::from(3),
+        };
+
+        let keys = KeyPair::generate_with(&params).unwrap();
+
+        assert_eq!(521, keys.public.modulus.bits());
+        assert_eq!(BigInt::from(3), keys.public.exp);
+
+        let mut encrypted = Vec::new();
+        keys.public
+            .encrypt_with(&Padding::Pkcs1v15, &mut &b"FooBarBaz"[..], &mut encrypted)
+            .unwrap();
+        let mut decrypted = Vec::new();
+        keys.private
+            .decrypt_with(&Padding::Pkcs1v15, &mut &encrypted[..], &mut decrypted)
+            .unwrap();
+
+        assert_eq!(b"FooBarBaz".to_vec(), decrypted);
+    }
+
+    #[test]
+    fn generate_with_rejects_invalid_params() {
+        let params = |bits: u64, exponent: i32| KeyParams {
+            bits,
+            exponent: BigInt::from(exponent),
+        };
+
+        assert!(matches!(
+            KeyPair::generate_with(&params(256, 65537)),
+            Err(Error::Size(_))
+        ));
+        for exponent in [1, 65536, -3] {
+            assert!(matches!(
+                KeyPair::generate_with(&params(1024, exponent)),
+                Err(Error::InvalidKey(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn from_decimal_rejects_malformed_file() {
+        for contents in ["", "3233", "3233\nabc", "3233\n17\n1\n2"] {
+            assert!(matches!(
+                Key::from_decimal(contents.as_bytes(), "test"),
+                Err(Error::Parse(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn check_rejects_inconsistent_primes() {
+        let mut key = get_test_crt_key();
+        key.crt.as_mut().unwrap().p += 2;
+
+        assert!(matches!(key.check(), Err(Error::InvalidKey(_))));
+    }
+
+    fn get_test_crt_key_pair() -> KeyPair {
+        test_crt_key_pair()
+    }
+
+    fn failed_checks(keys: &KeyPair) -> Vec<KeyCheck> {
+        keys.validate()
+            .into_iter()
+            .filter(|(_, passed)| !passed)
+            .map(|(check, _)| check)
+            .collect()
+    }
+
+    #[test]
+    fn validate_accepts_consistent_keys() {
+        let checks: Vec<KeyCheck> = get_test_crt_key_pair()
+            .validate()
+            .into_iter()
+            .map(|(check, passed)| {
+                assert!(passed, "{} failed", check);
+                check
+            })
+            .collect();
+
+        assert_eq!(7, checks.len());
+        assert_eq!(Vec::<KeyCheck>::new(), failed_checks(&get_test_keys()));
+    }
+
+    #[test]
+    fn validate_reports_inconsistent_crt_values() {
+        let mut keys = get_test_crt_key_pair();
+        keys.private.crt.as_mut().unwrap().dp += 2;
+
+        assert_eq!(vec![KeyCheck::CrtValues], failed_checks(&keys));
+    }
+
+    #[test]
+    fn validate_reports_wrong_private_exponent() {
+        let mut keys = get_test_keys();
+        keys.private.exp += 2;
+
+        assert_eq!(
+            vec![KeyCheck::Exponents, KeyCheck::RoundTrip],
+            failed_checks(&keys)
+        );
+    }
+
+    #[test]
+    fn validate_reports_composite_prime() {
+        // 7921 = 89^2
+        let keys =
+            generate_from_primes(&BigInt::from(7921), &BigInt::from(7919), &BigInt::from(17))
+                .unwrap();
+
+        let failed = failed_checks(&keys);
+
+        assert!(failed.contains(&KeyCheck::PrimeP));
+        assert!(!failed.contains(&KeyCheck::PrimeQ));
+        assert!(!failed.contains(&KeyCheck::Modulus));
+    }
+
+    #[test]
+    fn validate_reports_mismatched_public_key() {
+        let mut keys = get_test_crt_key_pair();
+        keys.public.exp = BigInt::from(3);
+
+        let failed = failed_checks(&keys);
+
+        assert!(failed.contains(&KeyCheck::PublicKey));
+        assert!(failed.contains(&KeyCheck::RoundTrip));
+    }
+
+    #[test]
+    fn from_private_file_reads_inconsistent_key() {
+        let mut crt_key = get_test_crt_key();
+        crt_key.crt.as_mut().unwrap().p += 2;
+        let path = std::env::temp_dir().join(format!("rsa-test-{}.invalid", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        crt_key
+            .write_decimal(&mut fs::File::create(path).unwrap())
+            .unwrap();
+        let keys = KeyPair::from_private_file(path, None, &no_passphrase);
+        std::fs::remove_file(path).unwrap();
+
+        let keys = keys.unwrap();
+        assert_eq!(Some(&keys.public.exp), crt_key.public_exponent());
+        assert!(failed_checks(&keys).contains(&KeyCheck::Modulus));
+    }
+
+    #[test]
+    fn generate_probable_prime_sets_top_and_bottom_bits() {
+        let prime = generate_probable_prime(256);
+
+        assert_eq!(256, prime.bits());
+        assert!(prime.bit(255) && prime.bit(254) && prime.bit(0));
+        assert!(crate::algorithms::miller_rabin(&prime, 16));
+    }
+
     // example from https://en.wikipedia.org/wiki/RSA_(cryptosystem)
     #[test]
     fn generate_from_primes_generates_correct_keys() {
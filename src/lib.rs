@@ -1,2 +1,25 @@
+pub mod aes;
 pub mod algorithms;
+pub mod armor;
+pub mod blowfish;
+pub mod ca;
+pub mod chacha20poly1305;
+pub mod container;
+pub mod der;
+pub mod encoding;
+pub mod envelope;
+pub mod error;
+pub mod hash;
+pub mod hmac;
+pub mod json;
+pub mod jwk;
+pub mod jwt;
+pub mod key_format;
 pub mod keys;
+pub mod montgomery;
+pub mod padding;
+pub mod pbes2;
+pub mod pem;
+pub mod signature;
+pub mod ssh;
+pub mod x509;
@@ -1,21 +1,76 @@
 use std::{
-    fs::File,
+    fs::{File, OpenOptions},
     io::{self, Read, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
 };
 
-use clap::{Parser, Subcommand};
-use rsa::keys::{Key, KeyPair};
+use clap::{error::ErrorKind, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use num_bigint::BigInt;
+use rsa::armor::{self, ArmorWriter};
+use rsa::ca::CaState;
+use rsa::encoding::{base64_encode, hex_encode};
+use rsa::error::Error;
+use rsa::hash::HashAlgorithm;
+use rsa::json::{self, Value};
+use rsa::jwt::{self, Algorithm, JwtError, Validation};
+use rsa::key_format::KeyFormat;
+use rsa::keys::{Key, KeyPair, KeyParams};
+use rsa::padding::Padding;
+use rsa::pem;
+use rsa::signature::SignatureScheme;
+use rsa::x509::{
+    self, Certificate, CertificateError, CertificateParams, GeneralName, KeyUsage, Name, Request,
+    Validity,
+};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about=None)]
 struct Cli {
+    /// Read the passphrase of encrypted private keys from this environment variable
+    #[arg(long, global = true, value_name = "VAR")]
+    passphrase_env: Option<String>,
+    /// Read the passphrase of encrypted private keys from the first line of this file, for example /dev/fd/3
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATH",
+        conflicts_with = "passphrase_env"
+    )]
+    passphrase_file: Option<String>,
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    Generate,
+    /// Generates a keypair into key.public and key.private
+    Generate {
+        /// decimal, pkcs1, pkcs1-der, pkcs8, pkcs8-der, ssh or jwk
+        #[arg(long, default_value_t = KeyFormat::Decimal)]
+        format: KeyFormat,
+        /// Length of the modulus in bits
+        #[arg(long, default_value_t = 2048)]
+        bits: u64,
+        /// The public exponent
+        #[arg(long, default_value_t = BigInt::from(65537))]
+        exponent: BigInt,
+        /// Encrypt the private key with a passphrase, needs the pkcs8, pkcs8-der or ssh format
+        #[arg(long)]
+        encrypt: bool,
+        /// Comment stored in keys in the ssh format, like user@host
+        #[arg(long)]
+        comment: Option<String>,
+        /// Directory the keys are written to instead of the current directory
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Name of the key files, which are called <NAME>.public and <NAME>.private
+        #[arg(long, default_value = "key")]
+        name: String,
+        /// Overwrite existing key files
+        #[arg(long)]
+        force: bool,
+    },
     Encrypt {
         #[arg(short, long)]
         in_path: Option<String>,
@@ -23,6 +78,26 @@ enum Commands {
         out_path: Option<String>,
         #[arg(short, long)]
         key_path: String,
+        #[arg(long, value_enum, default_value_t = PaddingMode::Oaep)]
+        padding: PaddingMode,
+        /// Hash function used by OAEP
+        #[arg(long, default_value_t = HashAlgorithm::Sha256)]
+        hash: HashAlgorithm,
+        /// Label used by OAEP
+        #[arg(long, default_value = "")]
+        label: String,
+        /// Encrypt the data with a random symmetric key and only the symmetric key with RSA
+        #[arg(long)]
+        hybrid: bool,
+        /// Write only the encrypted blocks, without the header used by default
+        #[arg(long, conflicts_with = "hybrid")]
+        raw: bool,
+        /// Write the encrypted data as base64 between BEGIN and END lines
+        #[arg(long)]
+        armor: bool,
+        /// Number of threads used for the RSA blocks, defaults to the number of CPUs
+        #[arg(long)]
+        jobs: Option<NonZeroUsize>,
     },
     Decrypt {
         #[arg(short, long)]
@@ -31,69 +106,909 @@ enum Commands {
         out_path: Option<String>,
         #[arg(short, long)]
         key_path: String,
+        #[arg(long, value_enum, default_value_t = PaddingMode::Oaep)]
+        padding: PaddingMode,
+        /// Hash function used by OAEP
+        #[arg(long, default_value_t = HashAlgorithm::Sha256)]
+        hash: HashAlgorithm,
+        /// Label used by OAEP
+        #[arg(long, default_value = "")]
+        label: String,
+        /// Read the data as a hybrid envelope written with --hybrid and decrypt it
+        #[arg(long)]
+        hybrid: bool,
+        /// Read data without a header, as written with --raw or by older versions
+        #[arg(long, conflicts_with = "hybrid")]
+        raw: bool,
+        /// Only accept data armored with BEGIN and END lines. Without it armored
+        /// data is detected automatically
+        #[arg(long)]
+        armor: bool,
+        /// Number of threads used for the RSA blocks, defaults to the number of CPUs
+        #[arg(long)]
+        jobs: Option<NonZeroUsize>,
+    },
+    /// Creates a detached signature for a message or file
+    Sign {
+        #[arg(short, long)]
+        in_path: Option<String>,
+        /// Where the signature is written
+        #[arg(short, long)]
+        out_path: Option<String>,
+        #[arg(short, long)]
+        key_path: String,
+        #[arg(long, value_enum, default_value_t = SchemeMode::Pss)]
+        scheme: SchemeMode,
+        /// Hash function used to digest the message
+        #[arg(long, default_value_t = HashAlgorithm::Sha256)]
+        hash: HashAlgorithm,
+        /// Write the signature as base64 between BEGIN and END lines
+        #[arg(long)]
+        armor: bool,
+    },
+    /// Verifies a detached signature, exits with a non-zero code if it is invalid
+    Verify {
+        #[arg(short, long)]
+        in_path: Option<String>,
+        #[arg(short, long)]
+        signature_path: String,
+        #[arg(short, long)]
+        key_path: String,
+        #[arg(long, value_enum, default_value_t = SchemeMode::Pss)]
+        scheme: SchemeMode,
+        /// Hash function used to digest the message
+        #[arg(long, default_value_t = HashAlgorithm::Sha256)]
+        hash: HashAlgorithm,
+        /// Only accept a signature armored with BEGIN and END lines. Without it an
+        /// armored signature is detected automatically
+        #[arg(long)]
+        armor: bool,
+    },
+    /// Prints the hash of a message or file
+    Digest {
+        #[arg(short, long)]
+        in_path: Option<String>,
+        #[arg(long, default_value_t = HashAlgorithm::Sha256)]
+        hash: HashAlgorithm,
+    },
+    /// Prints the size, the type and the fingerprint of a key
+    Inspect {
+        #[arg(short, long)]
+        key_path: String,
+        /// Print the information as a JSON object
+        #[arg(long)]
+        json: bool,
+    },
+    /// Creates and verifies JSON Web Tokens
+    Jwt {
+        #[command(subcommand)]
+        command: JwtCommands,
+    },
+    /// Creates self-signed certificates and certificate signing requests, and verifies certificates
+    Cert {
+        #[command(subcommand)]
+        command: CertCommands,
+    },
+    /// Issues certificates as a local certificate authority
+    Ca {
+        #[command(subcommand)]
+        command: CaCommands,
+    },
+    /// Converts a key to another format
+    Export {
+        /// The key to convert, can be given several times with --set
+        #[arg(short, long, required = true)]
+        key_path: Vec<String>,
+        #[arg(short, long)]
+        out_path: Option<String>,
+        /// decimal, pkcs1, pkcs1-der, pkcs8, pkcs8-der, ssh or jwk
+        #[arg(long)]
+        format: KeyFormat,
+        /// Only export the public half of a private key
+        #[arg(long)]
+        public: bool,
+        /// Write the public halves of the keys as a JWK Set, needs --format jwk
+        #[arg(long)]
+        set: bool,
+    },
+    /// Checks that the values of a private key are consistent
+    Check {
+        #[arg(short, long)]
+        key_path: String,
+        /// The public key, needed if the private key does not contain the public exponent
+        #[arg(short, long)]
+        public_key_path: Option<String>,
     },
 }
 
+#[derive(Subcommand)]
+enum JwtCommands {
+    /// Signs claims with a private key and prints the token
+    Sign {
+        #[arg(short, long)]
+        key_path: String,
+        /// Where the token is written
+        #[arg(short, long)]
+        out_path: Option<String>,
+        /// RS256, RS384, RS512 or PS256
+        #[arg(long, default_value_t = Algorithm::Rs256)]
+        alg: Algorithm,
+        /// The claims as a JSON object
+        #[arg(long, default_value = "{}")]
+        claims: String,
+        /// Sets the iss claim
+        #[arg(long)]
+        issuer: Option<String>,
+        /// Sets the sub claim
+        #[arg(long)]
+        subject: Option<String>,
+        /// Sets the aud claim
+        #[arg(long)]
+        audience: Option<String>,
+        /// Sets the exp claim to this many seconds from now
+        #[arg(long, value_name = "SECONDS")]
+        expires_in: Option<u64>,
+    },
+    /// Verifies a token and prints its claims, exits with a non-zero code if it is rejected
+    Verify {
+        /// The file with the token, read from standard input if not given
+        #[arg(short, long)]
+        in_path: Option<String>,
+        #[arg(short, long)]
+        key_path: String,
+        /// The audience the token must be addressed to, needed for tokens with an aud claim
+        #[arg(long)]
+        audience: Option<String>,
+        /// Only accept tokens signed with this algorithm
+        #[arg(long)]
+        alg: Option<Algorithm>,
+        /// Seconds of clock skew allowed when checking exp and nbf
+        #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+        leeway: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum CertCommands {
+    /// Creates a certificate for a private key, signed by the key itself
+    SelfSign {
+        #[command(flatten)]
+        args: CertificateArgs,
+        /// How many days from now the certificate is valid
+        #[arg(long, default_value_t = 365)]
+        days: u32,
+    },
+    /// Creates a certificate signing request for a private key
+    Csr {
+        #[command(flatten)]
+        args: CertificateArgs,
+    },
+    /// Verifies a certificate chain against trusted CA certificates, exits with a non-zero code if it is rejected
+    Verify {
+        /// The certificate followed by the intermediate CAs that issued it, read from standard input if not given
+        #[arg(short, long)]
+        in_path: Option<String>,
+        /// The trusted CA certificates
+        #[arg(short, long)]
+        trust_path: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CaCommands {
+    /// Issues a leaf certificate for a certificate signing request
+    Sign {
+        /// The certificate signing request, read from standard input if not given
+        #[arg(short, long)]
+        in_path: Option<String>,
+        /// The private key of the CA
+        #[arg(short, long)]
+        key_path: String,
+        /// The certificate of the CA
+        #[arg(short, long)]
+        ca_cert: String,
+        /// The file that tracks the serial numbers, <CA_CERT>.state by default
+        #[arg(long, value_name = "PATH")]
+        state: Option<PathBuf>,
+        /// How many days from now the certificate is valid, at most until the CA certificate expires
+        #[arg(long, default_value_t = 90)]
+        days: u32,
+        #[arg(short, long)]
+        out_path: Option<String>,
+        /// Write DER instead of PEM
+        #[arg(long)]
+        der: bool,
+    },
+}
+
+#[derive(Args)]
+struct CertificateArgs {
+    /// The private key the certificate is made for
+    #[arg(short, long)]
+    key_path: String,
+    #[arg(short, long)]
+    out_path: Option<String>,
+    /// The distinguished name of the subject, like "CN=example.com,O=Example,C=DE"
+    #[arg(long)]
+    subject: Name,
+    /// A subject alternative name like DNS:example.com, IP:127.0.0.1, email:me@example.com or URI:https://example.com, can be given several times
+    #[arg(long = "san", value_name = "NAME")]
+    alt_names: Vec<GeneralName>,
+    /// Mark the key as a certificate authority
+    #[arg(long)]
+    ca: bool,
+    /// How many intermediate CAs may follow this CA, unlimited if not given
+    #[arg(long, requires = "ca")]
+    path_len: Option<u32>,
+    /// digitalSignature, nonRepudiation, keyEncipherment, dataEncipherment, keyAgreement, keyCertSign or cRLSign, can be given several times. Defaults to digitalSignature and keyEncipherment, or digitalSignature, keyCertSign and cRLSign with --ca
+    #[arg(long, value_name = "USAGE")]
+    key_usage: Vec<KeyUsage>,
+    /// Write DER instead of PEM
+    #[arg(long)]
+    der: bool,
+}
+
+impl CertificateArgs {
+    fn params(&self) -> CertificateParams {
+        CertificateParams {
+            subject: self.subject.clone(),
+            alt_names: self.alt_names.clone(),
+            ca: self.ca,
+            path_len: self.path_len,
+            key_usage: self.key_usage.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SchemeMode {
+    /// RSASSA-PSS, signed with a salt as long as the hash and verified with a salt of any length
+    Pss,
+    /// RSASSA-PKCS1-v1_5
+    Pkcs1v15,
+}
+
+fn scheme_from_args(mode: SchemeMode, hash: HashAlgorithm) -> SignatureScheme {
+    match mode {
+        SchemeMode::Pss => SignatureScheme::Pss { hash },
+        SchemeMode::Pkcs1v15 => SignatureScheme::Pkcs1v15 { hash },
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PaddingMode {
+    /// RSAES-OAEP
+    Oaep,
+    /// RSAES-PKCS1-v1_5, for interoperability with older tools
+    Pkcs1v15,
+    /// Legacy unpadded format, insecure
+    Raw,
+}
+
+fn padding_from_args(mode: PaddingMode, hash: HashAlgorithm, label: &str) -> Padding {
+    match mode {
+        PaddingMode::Oaep => Padding::Oaep {
+            hash,
+            label: label.as_bytes().to_vec(),
+        },
+        PaddingMode::Pkcs1v15 => Padding::Pkcs1v15,
+        PaddingMode::Raw => Padding::Raw,
+    }
+}
+
+/// Exit code for an invalid signature in `verify`.
+const EXIT_INVALID_SIGNATURE: i32 = 1;
+
+/// Returns the exit code for an error. Usage errors exit with 2, as reported by clap.
+fn exit_code(err: &Error) -> i32 {
+    match err {
+        Error::Io(_) => 3,
+        Error::Parse(_) => 4,
+        Error::InvalidKey(_) => 5,
+        Error::Padding(_) => 6,
+        Error::Envelope(_) => 7,
+        Error::Size(_) => 8,
+        Error::Passphrase(_) => 9,
+        Error::Container(_) => 10,
+        Error::Jwt(JwtError::InvalidSignature) => EXIT_INVALID_SIGNATURE,
+        Error::Jwt(_) => 11,
+        Error::Certificate(CertificateError::InvalidSignature(_)) => EXIT_INVALID_SIGNATURE,
+        Error::Certificate(_) => 12,
+    }
+}
+
+/// Adds the path to the message of an I/O error.
+fn with_path(path: &str) -> impl FnOnce(io::Error) -> Error + '_ {
+    move |err| Error::Io(io::Error::new(err.kind(), format!("{}: {}", path, err)))
+}
+
+fn already_exists(path: &Path) -> Error {
+    Error::Io(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!(
+            "{} already exists, use --force to overwrite it",
+            path.display()
+        ),
+    ))
+}
+
+fn open_input(in_path: &Option<String>) -> Result<Box<dyn Read>, Error> {
+    Ok(match in_path {
+        Some(path) => Box::new(File::open(path).map_err(with_path(path))?),
+        None => Box::new(io::stdin()),
+    })
+}
+
+/// Dearmors the input, which must be armored if `required` is set and is
+/// detected otherwise.
+fn read_armored<'a>(
+    input: Box<dyn Read + 'a>,
+    label: &str,
+    required: bool,
+) -> Result<Box<dyn Read + 'a>, Error> {
+    Ok(match required {
+        true => armor::require(input, label)?,
+        false => armor::detect(input, label)?,
+    })
+}
+
+fn create_output(out_path: &Option<String>) -> Result<Box<dyn Write>, Error> {
+    Ok(match out_path {
+        Some(path) => Box::new(File::create(path).map_err(with_path(path))?),
+        None => Box::new(io::stdout()),
+    })
+}
+
+/// Like `create_output`, but a new file is only readable by its owner.
+fn create_private_output(out_path: &Option<String>) -> Result<Box<dyn Write>, Error> {
+    let Some(path) = out_path else {
+        return Ok(Box::new(io::stdout()));
+    };
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    Ok(Box::new(options.open(path).map_err(with_path(path))?))
+}
+
+/// Creates the output and lets `write` write to it, armored with the label if one is given.
+fn write_output(
+    out_path: &Option<String>,
+    armor_label: Option<&str>,
+    write: impl FnOnce(&mut dyn Write) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut output = create_output(out_path)?;
+    match armor_label {
+        Some(label) => {
+            let mut armored = ArmorWriter::new(output, label)?;
+            write(&mut armored)?;
+            armored.finish()?;
+        }
+        None => write(&mut output)?,
+    }
+    Ok(())
+}
+
+/// Where the passphrase of an encrypted private key comes from.
+struct PassphraseSource<'a> {
+    env: Option<&'a str>,
+    file: Option<&'a str>,
+}
+
+impl PassphraseSource<'_> {
+    /// Reads the passphrase from the environment variable or the file, or
+    /// prompts for it on the terminal. A prompted new passphrase is asked twice.
+    fn read(&self, prompt: &str, new: bool) -> Result<Vec<u8>, Error> {
+        let passphrase = match (self.env, self.file) {
+            (Some(var), _) => std::env::var(var).map_err(|_| {
+                Error::Passphrase(format!("The environment variable {} is not set", var))
+            })?,
+            (None, Some(path)) => {
+                let contents = std::fs::read_to_string(path).map_err(with_path(path))?;
+                contents.lines().next().unwrap_or("").to_string()
+            }
+            (None, None) => {
+                let prompt_password = |prompt: &str| {
+                    rpassword::prompt_password(prompt).map_err(|err| {
+                        Error::Passphrase(format!(
+                            "Could not read the passphrase from the terminal ({}), use --passphrase-env or --passphrase-file",
+                            err
+                        ))
+                    })
+                };
+                let passphrase = prompt_password(prompt)?;
+                if new && prompt_password("Repeat the passphrase: ")? != passphrase {
+                    return Err(Error::Passphrase(
+                        "The passphrases do not match".to_string(),
+                    ));
+                }
+                passphrase
+            }
+        };
+
+        if new && passphrase.is_empty() {
+            return Err(Error::Passphrase(
+                "The passphrase must not be empty".to_string(),
+            ));
+        }
+        Ok(passphrase.into_bytes())
+    }
+}
+
+fn read_key(key_path: &str, passphrase: &PassphraseSource) -> Result<Key, Error> {
+    let prompt = format!("Passphrase for {}: ", key_path);
+    Key::from_file_with_passphrase(key_path, &|| passphrase.read(&prompt, false)).map_err(|err| {
+        match err {
+            Error::Io(err) => with_path(key_path)(err),
+            err => err,
+        }
+    })
+}
+
+fn read_key_pair(
+    key_path: &str,
+    public_key_path: Option<&str>,
+    passphrase: &PassphraseSource,
+) -> Result<KeyPair, Error> {
+    let prompt = format!("Passphrase for {}: ", key_path);
+    let read_passphrase = || passphrase.read(&prompt, false);
+    KeyPair::from_private_file(key_path, public_key_path, &read_passphrase).map_err(|err| match err
+    {
+        // The private key is read first, so the public key failed if the private key can be opened.
+        Error::Io(err) => match public_key_path {
+            Some(path) if File::open(key_path).is_ok() => with_path(path)(err),
+            _ => with_path(key_path)(err),
+        },
+        err => err,
+    })
+}
+
+fn default_jobs() -> NonZeroUsize {
+    std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN)
+}
+
 fn main() {
     let cli = Cli::parse();
 
+    if let Err(err) = run(&cli) {
+        eprintln!("Error: {}", err);
+        std::process::exit(exit_code(&err));
+    }
+}
+
+fn run(cli: &Cli) -> Result<(), Error> {
+    let passphrase = PassphraseSource {
+        env: cli.passphrase_env.as_deref(),
+        file: cli.passphrase_file.as_deref(),
+    };
+
     match &cli.command {
-        Commands::Generate => {
-            let keys = KeyPair::generate();
-            match keys.write_to_file() {
-                Ok(_) => (),
-                Err(e) => panic!("Failed to write keys to file: {:?}", e),
+        Commands::Generate {
+            format,
+            bits,
+            exponent,
+            encrypt,
+            comment,
+            out,
+            name,
+            force,
+        } => {
+            if *encrypt && !format.supports_encryption() {
+                Cli::command()
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        "--encrypt needs --format pkcs8, pkcs8-der or ssh",
+                    )
+                    .exit();
+            }
+            let params = KeyParams {
+                bits: *bits,
+                exponent: exponent.clone(),
             };
+            // Fail before the slow key generation and the passphrase prompt.
+            params.validate()?;
+            let dir = out.as_deref().unwrap_or(Path::new(""));
+            let public_path = dir.join(format!("{}.public", name));
+            let private_path = dir.join(format!("{}.private", name));
+            if !*force {
+                if let Some(path) = [&public_path, &private_path]
+                    .into_iter()
+                    .find(|path| path.exists())
+                {
+                    return Err(already_exists(path));
+                }
+            }
+
+            let passphrase = if *encrypt {
+                let prompt = format!("Passphrase for {}: ", private_path.display());
+                Some(passphrase.read(&prompt, true)?)
+            } else {
+                None
+            };
+            let mut keys = KeyPair::generate_with(&params)?;
+            if let Some(comment) = comment {
+                keys.set_comment(comment);
+            }
+            keys.save(
+                &public_path,
+                &private_path,
+                *format,
+                passphrase.as_deref(),
+                *force,
+            )?;
         }
         Commands::Encrypt {
             in_path,
             out_path,
             key_path,
+            padding,
+            hash,
+            label,
+            hybrid,
+            raw,
+            armor,
+            jobs,
         } => {
-            let key = match Key::from_file(key_path) {
-                Ok(key) => key,
-                Err(e) => panic!("Failed to read key from file: {}", e),
-            };
+            let padding = padding_from_args(*padding, *hash, label);
+            let mut key = read_key(key_path, &passphrase)?;
+            key.set_jobs(jobs.unwrap_or_else(default_jobs));
+            let mut input = open_input(in_path)?;
 
-            let mut input: Box<dyn Read> = match in_path {
-                Some(path) => Box::new(File::open(path).unwrap()),
-                None => Box::new(io::stdin()),
-            };
-
-            let mut output: Box<dyn Write> = match out_path {
-                Some(path) => Box::new(File::create(path).unwrap()),
-                None => Box::new(io::stdout()),
-            };
-
-            match key.encrypt(&mut input, &mut output) {
-                Ok(_) => (),
-                Err(e) => panic!("Failed to encrypt file: {:?}", e),
-            };
+            write_output(out_path, armor.then_some(armor::MESSAGE_LABEL), |output| {
+                if *hybrid {
+                    key.encrypt_hybrid(&padding, &mut input, output)
+                } else if *raw {
+                    key.encrypt_with(&padding, &mut input, output)
+                } else {
+                    key.encrypt_container(&padding, &mut input, output)
+                }
+            })?;
         }
         Commands::Decrypt {
             in_path,
             out_path,
             key_path,
+            padding,
+            hash,
+            label,
+            hybrid,
+            raw,
+            armor,
+            jobs,
         } => {
-            let key = match Key::from_file(key_path) {
-                Ok(key) => key,
-                Err(e) => panic!("Failed to read key from file: {}", e),
-            };
+            let padding = padding_from_args(*padding, *hash, label);
+            let mut key = read_key(key_path, &passphrase)?;
+            key.set_jobs(jobs.unwrap_or_else(default_jobs));
+            let mut input = read_armored(open_input(in_path)?, armor::MESSAGE_LABEL, *armor)?;
+            let mut output = create_output(out_path)?;
 
-            let mut input: Box<dyn Read> = match in_path {
-                Some(path) => Box::new(File::open(path).unwrap()),
-                None => Box::new(io::stdin()),
-            };
+            if *hybrid {
+                key.decrypt_hybrid(label.as_bytes(), &mut input, &mut output)?;
+            } else if *raw {
+                key.decrypt_with(&padding, &mut input, &mut output)?;
+            } else {
+                key.decrypt_container(label.as_bytes(), &mut input, &mut output)?;
+            }
+        }
+        Commands::Sign {
+            in_path,
+            out_path,
+            key_path,
+            scheme,
+            hash,
+            armor,
+        } => {
+            let scheme = scheme_from_args(*scheme, *hash);
+            let key = read_key(key_path, &passphrase)?;
+            let mut input = open_input(in_path)?;
 
-            let mut output: Box<dyn Write> = match out_path {
-                Some(path) => Box::new(File::create(path).unwrap()),
-                None => Box::new(io::stdout()),
-            };
+            let signature = key.sign(&scheme, &mut input)?;
+            write_output(
+                out_path,
+                armor.then_some(armor::SIGNATURE_LABEL),
+                |output| Ok(output.write_all(&signature)?),
+            )?;
+        }
+        Commands::Verify {
+            in_path,
+            signature_path,
+            key_path,
+            scheme,
+            hash,
+            armor,
+        } => {
+            let scheme = scheme_from_args(*scheme, *hash);
+            let key = read_key(key_path, &passphrase)?;
+            let mut input = open_input(in_path)?;
+            let signature_file = File::open(signature_path).map_err(with_path(signature_path))?;
+            let mut signature = Vec::new();
+            read_armored(Box::new(signature_file), armor::SIGNATURE_LABEL, *armor)?
+                .read_to_end(&mut signature)?;
 
-            match key.decrypt(&mut input, &mut output) {
-                Ok(_) => (),
-                Err(e) => panic!("Failed to decrypt file: {:?}", e),
+            if key.verify(&scheme, &mut input, &signature)? {
+                println!("Signature is valid");
+            } else {
+                eprintln!("Signature is invalid");
+                std::process::exit(EXIT_INVALID_SIGNATURE);
+            }
+        }
+        Commands::Digest { in_path, hash } => {
+            let digest = hash.digest_reader(&mut open_input(in_path)?)?;
+            println!(
+                "{}  {}",
+                hex_encode(&digest),
+                in_path.as_deref().unwrap_or("-")
+            );
+        }
+        Commands::Inspect { key_path, json } => {
+            let key = read_key(key_path, &passphrase)?;
+            let key_type = if key.is_private() {
+                "private"
+            } else {
+                "public"
             };
+            let fingerprint = key.fingerprint();
+            let hex = fingerprint.as_deref().map(hex_encode);
+            let base64 = fingerprint.as_deref().map(base64_encode);
+            let ssh_fingerprint = key.ssh_fingerprint();
+            let jwk_thumbprint = key.jwk_thumbprint();
+            let comment = key.comment().map(str::to_string);
+
+            if *json {
+                let string_or_null = |value: &Option<String>| match value {
+                    Some(value) => Value::string(value),
+                    None => Value::Null,
+                };
+                let info = Value::object(vec![
+                    ("type", Value::string(key_type)),
+                    ("bits", Value::Number(key.bits().to_string())),
+                    (
+                        "public_exponent",
+                        key.public_exponent()
+                            .map_or(Value::Null, |e| Value::Number(e.to_string())),
+                    ),
+                    ("fingerprint_sha256_hex", string_or_null(&hex)),
+                    ("fingerprint_sha256_base64", string_or_null(&base64)),
+                    ("fingerprint_ssh", string_or_null(&ssh_fingerprint)),
+                    ("jwk_thumbprint", string_or_null(&jwk_thumbprint)),
+                    ("comment", string_or_null(&comment)),
+                ]);
+                println!("{}", info);
+            } else {
+                let unknown = || "unknown".to_string();
+                println!("Type:             {}", key_type);
+                println!("Modulus:          {} bits", key.bits());
+                println!(
+                    "Public exponent:  {}",
+                    key.public_exponent()
+                        .map_or_else(unknown, |e| e.to_string())
+                );
+                println!("SHA-256 (hex):    {}", hex.unwrap_or_else(unknown));
+                println!("SHA-256 (base64): {}", base64.unwrap_or_else(unknown));
+                println!(
+                    "SSH fingerprint:  {}",
+                    ssh_fingerprint.unwrap_or_else(unknown)
+                );
+                println!(
+                    "JWK thumbprint:   {}",
+                    jwk_thumbprint.unwrap_or_else(unknown)
+                );
+                if let Some(comment) = comment {
+                    println!("Comment:          {}", comment);
+                }
+            }
+        }
+        Commands::Jwt { command } => run_jwt(command, &passphrase)?,
+        Commands::Cert { command } => run_cert(command, &passphrase)?,
+        Commands::Ca { command } => run_ca(command, &passphrase)?,
+        Commands::Export {
+            key_path,
+            out_path,
+            format,
+            public,
+            set,
+        } => {
+            if *set && *format != KeyFormat::Jwk {
+                Cli::command()
+                    .error(ErrorKind::ArgumentConflict, "--set needs --format jwk")
+                    .exit();
+            }
+            if key_path.len() > 1 && !*set {
+                Cli::command()
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        "Several keys can only be exported with --set",
+                    )
+                    .exit();
+            }
+            let mut keys = key_path
+                .iter()
+                .map(|path| read_key(path, &passphrase))
+                .collect::<Result<Vec<Key>, Error>>()?;
+
+            if *set {
+                write_output(out_path, None, |output| Key::write_jwk_set(&keys, output))?;
+            } else {
+                let key = keys.remove(0);
+                if key.is_private() && !*public {
+                    let keys = KeyPair::from_private_key(key)?;
+                    keys.write_private(*format, &mut *create_private_output(out_path)?)?;
+                } else {
+                    write_output(out_path, None, |output| key.write_public(*format, output))?;
+                }
+            }
+        }
+        Commands::Check {
+            key_path,
+            public_key_path,
+        } => {
+            let keys = read_key_pair(key_path, public_key_path.as_deref(), &passphrase)?;
+            let results = keys.validate();
+            for (check, passed) in &results {
+                println!("{:6}  {}", if *passed { "ok" } else { "FAILED" }, check);
+            }
+
+            let failed = results.iter().filter(|(_, passed)| !passed).count();
+            if failed > 0 {
+                return Err(Error::InvalidKey(format!(
+                    "{} of {} checks failed",
+                    failed,
+                    results.len()
+                )));
+            }
         }
     };
+
+    Ok(())
+}
+
+/// Writes a certificate or request as PEM with the label, or as DER.
+fn write_certificate(
+    out_path: &Option<String>,
+    der: bool,
+    label: &str,
+    encoded: &[u8],
+) -> Result<(), Error> {
+    write_output(out_path, None, |output| {
+        match der {
+            true => output.write_all(encoded)?,
+            false => output.write_all(pem::encode(label, encoded).as_bytes())?,
+        }
+        Ok(())
+    })
+}
+
+fn read_certificates(path: &str) -> Result<Vec<Certificate>, Error> {
+    Certificate::read_all(&std::fs::read(path).map_err(with_path(path))?)
+}
+
+fn run_cert(command: &CertCommands, passphrase: &PassphraseSource) -> Result<(), Error> {
+    match command {
+        CertCommands::SelfSign { args, days } => {
+            let key = read_key(&args.key_path, passphrase)?;
+            let certificate =
+                x509::self_signed(&key, &args.params(), Validity::days_from_now(*days))?;
+            write_certificate(
+                &args.out_path,
+                args.der,
+                x509::CERTIFICATE_LABEL,
+                &certificate,
+            )?;
+        }
+        CertCommands::Csr { args } => {
+            let key = read_key(&args.key_path, passphrase)?;
+            let request = x509::request(&key, &args.params())?;
+            write_certificate(&args.out_path, args.der, x509::REQUEST_LABEL, &request)?;
+        }
+        CertCommands::Verify {
+            in_path,
+            trust_path,
+        } => {
+            let mut contents = Vec::new();
+            open_input(in_path)?.read_to_end(&mut contents)?;
+            let chain = Certificate::read_all(&contents)?;
+            let anchors = read_certificates(trust_path)?;
+
+            x509::verify_chain(&chain, &anchors, jwt::now() as i64)?;
+            println!("Certificate is valid");
+        }
+    }
+    Ok(())
+}
+
+fn run_ca(command: &CaCommands, passphrase: &PassphraseSource) -> Result<(), Error> {
+    match command {
+        CaCommands::Sign {
+            in_path,
+            key_path,
+            ca_cert,
+            state,
+            days,
+            out_path,
+            der,
+        } => {
+            let mut contents = Vec::new();
+            open_input(in_path)?.read_to_end(&mut contents)?;
+            let request = Request::read(&contents)?;
+            let ca_key = read_key(key_path, passphrase)?;
+            let ca_certificate = read_certificates(ca_cert)?.remove(0);
+            let state_path = state
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(format!("{}.state", ca_cert)));
+            let mut ca_state = CaState::load(&state_path)?;
+
+            let encoded = x509::issue(
+                &ca_key,
+                &ca_certificate,
+                &request,
+                Validity::days_from_now(*days),
+                ca_state.next_serial(),
+            )?;
+            // The serial number is recorded before the certificate is written, so
+            // that it is never used twice.
+            ca_state.record(&Certificate::from_der(&encoded)?);
+            ca_state.save(&state_path)?;
+            write_certificate(out_path, *der, x509::CERTIFICATE_LABEL, &encoded)?;
+        }
+    }
+    Ok(())
+}
+
+fn run_jwt(command: &JwtCommands, passphrase: &PassphraseSource) -> Result<(), Error> {
+    match command {
+        JwtCommands::Sign {
+            key_path,
+            out_path,
+            alg,
+            claims,
+            issuer,
+            subject,
+            audience,
+            expires_in,
+        } => {
+            let key = read_key(key_path, passphrase)?;
+            let mut claims = json::parse(claims)?;
+            if !matches!(claims, Value::Object(_)) {
+                return Err(Error::Parse("The claims must be a JSON object".to_string()));
+            }
+            let now = jwt::now();
+            if claims.get("iat").is_none() {
+                claims.insert("iat", Value::Number(now.to_string()));
+            }
+            for (name, value) in [("iss", issuer), ("sub", subject), ("aud", audience)] {
+                if let Some(value) = value {
+                    claims.insert(name, Value::string(value));
+                }
+            }
+            if let Some(seconds) = expires_in {
+                claims.insert("exp", Value::Number((now + seconds).to_string()));
+            }
+
+            let token = jwt::sign(&key, *alg, &claims)?;
+            write_output(out_path, None, |output| {
+                writeln!(output, "{}", token)?;
+                Ok(())
+            })?;
+        }
+        JwtCommands::Verify {
+            in_path,
+            key_path,
+            audience,
+            alg,
+            leeway,
+        } => {
+            let key = read_key(key_path, passphrase)?;
+            let mut token = String::new();
+            open_input(in_path)?.read_to_string(&mut token)?;
+            let validation = Validation {
+                audience: audience.clone(),
+                leeway: *leeway,
+                algorithm: *alg,
+                ..Validation::default()
+            };
+
+            println!("{}", jwt::verify(&key, &token, &validation)?);
+        }
+    }
+    Ok(())
 }
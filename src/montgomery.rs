@@ -0,0 +1,411 @@
+//! Modular exponentiation with Montgomery multiplication.
+//!
+//! Numbers are kept as little-endian vectors of 64-bit limbs with as many limbs as
+//! the modulus, in Montgomery form `x * R mod m` with `R = 2^(64 * limbs)`. The
+//! reduction only needs multiplications and shifts, so no division is needed
+//! after the context has been created.
+
+use num_bigint::{BigInt, BigUint, Sign};
+use num_traits::{One, Zero};
+
+/// Precomputed values for exponentiation modulo an odd modulus. Creating the
+/// context needs a few divisions, so it should be reused for the same modulus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MontgomeryContext {
+    modulus: Vec<u64>,
+    /// `-modulus^-1 mod 2^64`
+    m_inv: u64,
+    /// `R^2 mod modulus`, used to convert numbers to Montgomery form.
+    r_squared: Vec<u64>,
+    /// `R mod modulus`, the number one in Montgomery form.
+    one: Vec<u64>,
+    modulus_big: BigUint,
+}
+
+impl MontgomeryContext {
+    /// Creates the context for the modulus. Returns `None` if the modulus is even
+    /// or not greater than one, since Montgomery multiplication needs an odd modulus.
+    pub fn new(modulus: &BigInt) -> Option<MontgomeryContext> {
+        let modulus_big = modulus.to_biguint()?;
+        if modulus_big <= BigUint::one() || !modulus_big.bit(0) {
+            return None;
+        }
+
+        let limbs = modulus_big.to_u64_digits();
+        let len = limbs.len();
+
+        // Newton's iteration doubles the amount of correct low bits on every step.
+        let mut inv: u64 = 1;
+        for _ in 0..6 {
+            inv = inv.wrapping_mul(2u64.wrapping_sub(limbs[0].wrapping_mul(inv)));
+        }
+
+        let r = BigUint::one() << (64 * len);
+        let one = to_limbs(&(&r % &modulus_big), len);
+        let r_squared = to_limbs(&((&r * &r) % &modulus_big), len);
+
+        Some(MontgomeryContext {
+            modulus: limbs,
+            m_inv: inv.wrapping_neg(),
+            r_squared,
+            one,
+            modulus_big,
+        })
+    }
+
+    /// Calculates `base^exponent mod modulus` with a sliding window. Negative
+    /// exponents are treated as zero.
+    pub fn pow(&self, base: &BigInt, exponent: &BigInt) -> BigInt {
+        let len = self.modulus.len();
+        let mut scratch = vec![0u64; 2 * len];
+
+        let base = self.reduce(base);
+        let mut x = vec![0u64; len];
+        self.mul(&base, &self.r_squared, &mut x, &mut scratch);
+
+        let exponent = match exponent.sign() {
+            Sign::Plus => exponent.magnitude().clone(),
+            _ => BigUint::zero(),
+        };
+        let bits = exponent.bits();
+        let window = window_size(bits);
+
+        // Odd powers x, x^3, x^5, ... up to x^(2^window - 1).
+        let mut table = vec![x.clone()];
+        if window > 1 {
+            let mut x_squared = vec![0u64; len];
+            self.square(&x, &mut x_squared, &mut scratch);
+            for i in 1..1 << (window - 1) {
+                let mut next = vec![0u64; len];
+                self.mul(&table[i - 1], &x_squared, &mut next, &mut scratch);
+                table.push(next);
+            }
+        }
+
+        let mut result = self.one.clone();
+        let mut tmp = vec![0u64; len];
+        let mut i = bits;
+        while i > 0 {
+            if !exponent.bit(i - 1) {
+                self.square(&result, &mut tmp, &mut scratch);
+                std::mem::swap(&mut result, &mut tmp);
+                i -= 1;
+                continue;
+            }
+
+            // The longest window of at most `window` bits that ends with a one bit.
+            let mut width = window.min(i);
+            while !exponent.bit(i - width) {
+                width -= 1;
+            }
+            let mut value = 0usize;
+            for bit in (i - width..i).rev() {
+                value = (value << 1) | exponent.bit(bit) as usize;
+            }
+
+            for _ in 0..width {
+                self.square(&result, &mut tmp, &mut scratch);
+                std::mem::swap(&mut result, &mut tmp);
+            }
+            self.mul(&result, &table[value >> 1], &mut tmp, &mut scratch);
+            std::mem::swap(&mut result, &mut tmp);
+            i -= width;
+        }
+
+        let mut one = vec![0u64; len];
+        one[0] = 1;
+        self.mul(&result, &one, &mut tmp, &mut scratch);
+        BigInt::from(from_limbs(&tmp))
+    }
+
+    /// Like `pow`, but for secret exponents: the sequence of multiplications and
+    /// memory accesses depends only on the lengths of the modulus and the exponent,
+    /// not on the bits of the exponent. A fixed window is used, and every table
+    /// entry is read when looking one up.
+    pub fn pow_secret(&self, base: &BigInt, exponent: &BigInt) -> BigInt {
+        let len = self.modulus.len();
+        let mut scratch = vec![0u64; 2 * len];
+
+        let base = self.reduce(base);
+        let mut x = vec![0u64; len];
+        self.mul(&base, &self.r_squared, &mut x, &mut scratch);
+
+        // All powers x^0, x^1, ... up to x^(2^SECRET_WINDOW - 1).
+        let mut table = vec![self.one.clone()];
+        for i in 1..1 << SECRET_WINDOW {
+            let mut next = vec![0u64; len];
+            self.mul(&table[i - 1], &x, &mut next, &mut scratch);
+            table.push(next);
+        }
+
+        // The exponent is processed in whole limbs, at least as many as the modulus has.
+        let mut digits = match exponent.sign() {
+            Sign::Plus => exponent.magnitude().to_u64_digits(),
+            _ => Vec::new(),
+        };
+        digits.resize(digits.len().max(len), 0);
+
+        let mut result = self.one.clone();
+        let mut entry = vec![0u64; len];
+        let mut tmp = vec![0u64; len];
+        for position in (0..digits.len() * 64).step_by(SECRET_WINDOW).rev() {
+            for _ in 0..SECRET_WINDOW {
+                self.square(&result, &mut tmp, &mut scratch);
+                std::mem::swap(&mut result, &mut tmp);
+            }
+            let value = (digits[position / 64] >> (position % 64)) & ((1 << SECRET_WINDOW) - 1);
+            select(&table, value, &mut entry);
+            self.mul(&result, &entry, &mut tmp, &mut scratch);
+            std::mem::swap(&mut result, &mut tmp);
+        }
+
+        let mut one = vec![0u64; len];
+        one[0] = 1;
+        self.mul(&result, &one, &mut tmp, &mut scratch);
+        BigInt::from(from_limbs(&tmp))
+    }
+
+    /// Reduces a number into the range `[0, modulus)` and returns its limbs.
+    fn reduce(&self, x: &BigInt) -> Vec<u64> {
+        let reduced = x.magnitude() % &self.modulus_big;
+        let reduced = if x.sign() == Sign::Minus && !reduced.is_zero() {
+            &self.modulus_big - reduced
+        } else {
+            reduced
+        };
+        to_limbs(&reduced, self.modulus.len())
+    }
+
+    /// Calculates `a * b / R mod modulus` into `out`. The scratch buffer must be
+    /// twice as long as the modulus.
+    fn mul(&self, a: &[u64], b: &[u64], out: &mut [u64], scratch: &mut [u64]) {
+        let len = self.modulus.len();
+        let t = &mut scratch[..2 * len];
+        t.fill(0);
+
+        for (i, &b_i) in b.iter().enumerate() {
+            let mut carry = 0u64;
+            for (t_j, &a_j) in t[i..i + len].iter_mut().zip(a) {
+                let sum = *t_j as u128 + a_j as u128 * b_i as u128 + carry as u128;
+                *t_j = sum as u64;
+                carry = (sum >> 64) as u64;
+            }
+            t[i + len] = carry;
+        }
+
+        self.reduce_product(t, out);
+    }
+
+    /// Like `mul` with `a` as both factors, but computes each cross product once.
+    fn square(&self, a: &[u64], out: &mut [u64], scratch: &mut [u64]) {
+        let len = self.modulus.len();
+        let t = &mut scratch[..2 * len];
+        t.fill(0);
+
+        for (i, &a_i) in a.iter().enumerate() {
+            let mut carry = 0u64;
+            for (t_j, &a_j) in t[2 * i + 1..i + len].iter_mut().zip(&a[i + 1..]) {
+                let sum = *t_j as u128 + a_i as u128 * a_j as u128 + carry as u128;
+                *t_j = sum as u64;
+                carry = (sum >> 64) as u64;
+            }
+            t[i + len] = carry;
+        }
+
+        // Double the cross products and add the squares on the diagonal.
+        let mut shifted_out = 0u64;
+        for limb in t.iter_mut() {
+            let high = *limb >> 63;
+            *limb = (*limb << 1) | shifted_out;
+            shifted_out = high;
+        }
+        let mut carry = 0u128;
+        for (pair, &a_i) in t.chunks_exact_mut(2).zip(a) {
+            let square = a_i as u128 * a_i as u128;
+            let low = pair[0] as u128 + (square as u64) as u128 + carry;
+            pair[0] = low as u64;
+            let high = pair[1] as u128 + (square >> 64) + (low >> 64);
+            pair[1] = high as u64;
+            carry = high >> 64;
+        }
+
+        self.reduce_product(t, out);
+    }
+
+    /// Divides the double length product by R modulo the modulus into `out`.
+    fn reduce_product(&self, t: &mut [u64], out: &mut [u64]) {
+        let len = self.modulus.len();
+
+        // Add multiples of the modulus that make the lower half zero. The upper
+        // half is then the product divided by R.
+        let mut top = 0u64;
+        for i in 0..len {
+            let m = t[i].wrapping_mul(self.m_inv);
+            let mut carry = 0u64;
+            for (t_j, &m_j) in t[i..i + len].iter_mut().zip(&self.modulus) {
+                let sum = *t_j as u128 + m as u128 * m_j as u128 + carry as u128;
+                *t_j = sum as u64;
+                carry = (sum >> 64) as u64;
+            }
+            let sum = t[i + len] as u128 + carry as u128 + top as u128;
+            t[i + len] = sum as u64;
+            top = (sum >> 64) as u64;
+        }
+
+        // The result is below 2 * modulus, so one subtraction is enough. It is
+        // always computed, and the difference is kept without branching if the
+        // result was not below the modulus.
+        let result = &t[len..];
+        let mut borrow = 0u64;
+        for ((o, r), m) in out.iter_mut().zip(result).zip(&self.modulus) {
+            let (diff, b1) = r.overflowing_sub(*m);
+            let (diff, b2) = diff.overflowing_sub(borrow);
+            *o = diff;
+            borrow = (b1 | b2) as u64;
+        }
+        let keep_difference = (top | (borrow ^ 1)).wrapping_neg();
+        for (o, r) in out.iter_mut().zip(result) {
+            *o = (*o & keep_difference) | (r & !keep_difference);
+        }
+    }
+}
+
+/// The window size of `pow_secret`. It divides 64, so a window never spans two limbs.
+const SECRET_WINDOW: usize = 4;
+
+/// The window size for an exponent of the given length, chosen so that
+/// precomputing the table costs less than the multiplications it saves.
+fn window_size(bits: u64) -> u64 {
+    match bits {
+        672.. => 6,
+        240.. => 5,
+        80.. => 4,
+        24.. => 3,
+        _ => 1,
+    }
+}
+
+/// Copies the table entry at the index into `out`, reading every entry so that
+/// the memory access pattern does not depend on the index.
+fn select(table: &[Vec<u64>], index: u64, out: &mut [u64]) {
+    out.fill(0);
+    for (i, entry) in table.iter().enumerate() {
+        let difference = i as u64 ^ index;
+        // All ones if the difference is zero, otherwise zero.
+        let mask = ((difference | difference.wrapping_neg()) >> 63).wrapping_sub(1);
+        for (o, e) in out.iter_mut().zip(entry) {
+            *o |= e & mask;
+        }
+    }
+}
+
+fn to_limbs(x: &BigUint, len: usize) -> Vec<u64> {
+    let mut limbs = x.to_u64_digits();
+    limbs.resize(len, 0);
+    limbs
+}
+
+fn from_limbs(limbs: &[u64]) -> BigUint {
+    let digits: Vec<u32> = limbs
+        .iter()
+        .flat_map(|limb| [*limb as u32, (limb >> 32) as u32])
+        .collect();
+    BigUint::new(digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::RandBigInt;
+
+    #[test]
+    fn new_rejects_even_and_small_moduli() {
+        for modulus in [0, 1, 2, 10, -7] {
+            assert_eq!(None, MontgomeryContext::new(&BigInt::from(modulus)));
+        }
+    }
+
+    #[test]
+    fn pow_returns_correct_result_for_small_numbers() {
+        let context = MontgomeryContext::new(&BigInt::from(3233)).unwrap();
+
+        assert_eq!(
+            BigInt::from(2790),
+            context.pow(&BigInt::from(65), &BigInt::from(17))
+        );
+        assert_eq!(
+            BigInt::from(65),
+            context.pow(&BigInt::from(2790), &BigInt::from(413))
+        );
+        assert_eq!(
+            BigInt::one(),
+            context.pow(&BigInt::from(65), &BigInt::zero())
+        );
+        assert_eq!(
+            BigInt::from(3232),
+            context.pow(&BigInt::from(-1), &BigInt::from(3))
+        );
+    }
+
+    #[test]
+    fn pow_matches_modpow_for_random_numbers() {
+        let mut rng = rand::thread_rng();
+        for bits in [64, 100, 512, 1031, 2048] {
+            let modulus = rng.gen_biguint(bits) | BigUint::one();
+            let base = rng.gen_biguint(bits + 10);
+            let exponent = rng.gen_biguint(bits);
+            let context = MontgomeryContext::new(&BigInt::from(modulus.clone())).unwrap();
+
+            assert_eq!(
+                BigInt::from(base.modpow(&exponent, &modulus)),
+                context.pow(&BigInt::from(base), &BigInt::from(exponent))
+            );
+        }
+    }
+
+    #[test]
+    fn pow_secret_matches_modpow_for_random_numbers() {
+        let mut rng = rand::thread_rng();
+        for bits in [64, 100, 512, 1031] {
+            let modulus = rng.gen_biguint(bits) | BigUint::one();
+            let base = rng.gen_biguint(bits);
+            let exponent = rng.gen_biguint(bits);
+            let context = MontgomeryContext::new(&BigInt::from(modulus.clone())).unwrap();
+
+            assert_eq!(
+                BigInt::from(base.modpow(&exponent, &modulus)),
+                context.pow_secret(&BigInt::from(base), &BigInt::from(exponent))
+            );
+        }
+    }
+
+    #[test]
+    fn pow_secret_handles_small_exponents() {
+        let context = MontgomeryContext::new(&BigInt::from(3233)).unwrap();
+
+        assert_eq!(
+            BigInt::from(65),
+            context.pow_secret(&BigInt::from(2790), &BigInt::from(413))
+        );
+        assert_eq!(
+            BigInt::one(),
+            context.pow_secret(&BigInt::from(2790), &BigInt::zero())
+        );
+    }
+
+    #[test]
+    fn select_returns_entry_at_index() {
+        let table: Vec<Vec<u64>> = (0..16).map(|i| vec![i, i * 100]).collect();
+        let mut out = vec![0u64; 2];
+
+        select(&table, 11, &mut out);
+
+        assert_eq!(vec![11, 1100], out);
+    }
+
+    #[test]
+    fn window_size_grows_with_exponent() {
+        assert_eq!(1, window_size(17));
+        assert_eq!(6, window_size(2048));
+    }
+}
@@ -0,0 +1,519 @@
+use std::fmt;
+use std::io;
+
+use crate::hash::HashAlgorithm;
+use crate::hmac::{self, Hmac};
+use num_bigint::{BigInt, Sign};
+use rand::{Rng, RngCore};
+
+/// Padding applied to each block before it is exponentiated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Padding {
+    /// The original block format: little-endian plaintext followed by a `1u8` sentinel.
+    /// Provides no security and exists for compatibility with old ciphertexts.
+    Raw,
+    /// RSAES-OAEP as specified in RFC 8017, section 7.1.
+    Oaep { hash: HashAlgorithm, label: Vec<u8> },
+    /// RSAES-PKCS1-v1_5 as specified in RFC 8017, section 7.2.
+    /// Only meant for exchanging data with older implementations. Blocks with
+    /// invalid padding decrypt to a random-looking message instead of an error,
+    /// see `pkcs1v15_decode`.
+    Pkcs1v15,
+}
+
+impl Padding {
+    /// OAEP with SHA-256 and an empty label.
+    pub fn oaep() -> Padding {
+        Padding::Oaep {
+            hash: HashAlgorithm::Sha256,
+            label: Vec::new(),
+        }
+    }
+
+    /// Returns the largest message that fits into a block of `k` bytes.
+    pub fn max_message_len(&self, k: usize) -> Option<usize> {
+        match self {
+            Padding::Raw => k.checked_sub(2),
+            Padding::Oaep { hash, .. } => k.checked_sub(2 * hash.output_size() + 2),
+            Padding::Pkcs1v15 => k.checked_sub(11),
+        }
+    }
+
+    /// Pads a message into a block of `k` bytes. Not used for `Padding::Raw`,
+    /// which has its own little-endian block format.
+    pub(crate) fn encode(&self, message: &[u8], k: usize) -> Result<Vec<u8>, PaddingError> {
+        match self {
+            Padding::Raw => unreachable!("raw blocks are not padded"),
+            Padding::Oaep { hash, label } => oaep_encode(*hash, label, message, k),
+            Padding::Pkcs1v15 => pkcs1v15_encode(message, k),
+        }
+    }
+
+    /// Removes the padding from a block of `k` bytes. `rejection_key` is only used
+    /// by PKCS #1 v1.5, see `implicit_rejection_key`.
+    pub(crate) fn decode(
+        &self,
+        em: &[u8],
+        k: usize,
+        rejection_key: impl FnOnce() -> Vec<u8>,
+    ) -> Result<Vec<u8>, PaddingError> {
+        match self {
+            Padding::Raw => unreachable!("raw blocks are not padded"),
+            Padding::Oaep { hash, label } => oaep_decode(*hash, label, em, k),
+            Padding::Pkcs1v15 => pkcs1v15_decode(em, k, &rejection_key()),
+        }
+    }
+}
+
+impl Default for Padding {
+    fn default() -> Self {
+        Padding::oaep()
+    }
+}
+
+/// Errors produced while applying or removing padding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaddingError {
+    /// The message does not fit into a single block.
+    MessageTooLong,
+    /// The modulus is too small for the chosen padding.
+    KeyTooSmall,
+    /// The block could not be decoded. Deliberately carries no detail.
+    Decryption,
+    /// The encrypted data ends in the middle of a block.
+    Truncated,
+}
+
+impl fmt::Display for PaddingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaddingError::MessageTooLong => write!(f, "Message too long for the key"),
+            PaddingError::KeyTooSmall => write!(f, "Key too small for the chosen padding"),
+            PaddingError::Decryption => write!(f, "Decryption error"),
+            PaddingError::Truncated => {
+                write!(f, "The encrypted data ends in the middle of a block")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PaddingError {}
+
+impl From<PaddingError> for io::Error {
+    fn from(err: PaddingError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+/// Converts a nonnegative integer to a big-endian byte string of the given length.
+/// Returns `None` if the integer does not fit into that many bytes.
+pub(crate) fn i2osp(x: &BigInt, len: usize) -> Option<Vec<u8>> {
+    let bytes = x.to_bytes_be().1;
+    let mut result = vec![0u8; len.checked_sub(bytes.len())?];
+    result.extend_from_slice(&bytes);
+    Some(result)
+}
+
+/// Converts a big-endian byte string to a nonnegative integer.
+pub(crate) fn os2ip(bytes: &[u8]) -> BigInt {
+    BigInt::from_bytes_be(Sign::Plus, bytes)
+}
+
+/// Mask generation function MGF1 from RFC 8017, appendix B.2.1.
+pub(crate) fn mgf1(hash: HashAlgorithm, seed: &[u8], len: usize) -> Vec<u8> {
+    let mut mask = Vec::with_capacity(len + hash.output_size());
+    let mut counter: u32 = 0;
+    while mask.len() < len {
+        let mut hasher = hash.hasher();
+        hasher.update(seed);
+        hasher.update(&counter.to_be_bytes());
+        mask.extend_from_slice(&hasher.finalize());
+        counter += 1;
+    }
+    mask.truncate(len);
+    mask
+}
+
+/// Returns 1 if the byte is zero and 0 otherwise, without branching.
+fn ct_is_zero(byte: u8) -> u8 {
+    ((byte as u16).wrapping_sub(1) >> 8) as u8 & 1
+}
+
+/// Returns `a` if the bit is 1 and `b` if it is 0, without branching.
+fn ct_select(bit: u8, a: usize, b: usize) -> usize {
+    let mask = (bit as usize).wrapping_neg();
+    (a & mask) | (b & !mask)
+}
+
+/// Returns 1 if `a < b` and 0 otherwise, without branching. Both must be below 2^63.
+fn ct_less(a: usize, b: usize) -> u8 {
+    (a.wrapping_sub(b) >> (usize::BITS - 1)) as u8
+}
+
+/// Returns the index of the first byte equal to `separator` in `bytes[start..]`, scanning
+/// the whole slice regardless of where it is found. Returns whether the separator was
+/// found, its index and whether any byte before it differed from `filler`, if one is given.
+fn ct_find_separator(
+    bytes: &[u8],
+    start: usize,
+    separator: u8,
+    filler: Option<u8>,
+) -> (u8, usize, u8) {
+    let mut found: u8 = 0;
+    let mut index = 0usize;
+    let mut bad: u8 = 0;
+    for (i, &byte) in bytes.iter().enumerate().skip(start) {
+        let is_separator = ct_is_zero(byte ^ separator);
+        let first = is_separator & !found & 1;
+        index = ct_select(first, i, index);
+        if let Some(filler) = filler {
+            bad |= !found & !is_separator & !ct_is_zero(byte ^ filler) & 1;
+        }
+        found |= is_separator;
+    }
+    (found, index, bad)
+}
+
+fn xor_in_place(target: &mut [u8], mask: &[u8]) {
+    for (byte, mask_byte) in target.iter_mut().zip(mask) {
+        *byte ^= mask_byte;
+    }
+}
+
+/// Encodes a message into a `k` byte OAEP block using a random seed.
+pub(crate) fn oaep_encode(
+    hash: HashAlgorithm,
+    label: &[u8],
+    message: &[u8],
+    k: usize,
+) -> Result<Vec<u8>, PaddingError> {
+    let mut seed = vec![0u8; hash.output_size()];
+    rand::thread_rng().fill_bytes(&mut seed);
+    oaep_encode_with_seed(hash, label, message, k, &seed)
+}
+
+fn oaep_encode_with_seed(
+    hash: HashAlgorithm,
+    label: &[u8],
+    message: &[u8],
+    k: usize,
+    seed: &[u8],
+) -> Result<Vec<u8>, PaddingError> {
+    let h_len = hash.output_size();
+    if k < 2 * h_len + 2 {
+        return Err(PaddingError::KeyTooSmall);
+    }
+    if message.len() > k - 2 * h_len - 2 {
+        return Err(PaddingError::MessageTooLong);
+    }
+
+    // DB = lHash || PS || 0x01 || M
+    let mut db = hash.digest(label);
+    db.resize(k - message.len() - h_len - 2, 0);
+    db.push(1);
+    db.extend_from_slice(message);
+
+    let db_mask = mgf1(hash, seed, db.len());
+    xor_in_place(&mut db, &db_mask);
+    let mut masked_seed = seed.to_vec();
+    xor_in_place(&mut masked_seed, &mgf1(hash, &db, h_len));
+
+    let mut em = Vec::with_capacity(k);
+    em.push(0);
+    em.extend_from_slice(&masked_seed);
+    em.extend_from_slice(&db);
+    Ok(em)
+}
+
+/// Decodes a `k` byte OAEP block. Every malformed block is reported as the same
+/// error and the checks are done without early returns to not leak which one failed.
+pub(crate) fn oaep_decode(
+    hash: HashAlgorithm,
+    label: &[u8],
+    em: &[u8],
+    k: usize,
+) -> Result<Vec<u8>, PaddingError> {
+    let h_len = hash.output_size();
+    if k < 2 * h_len + 2 {
+        return Err(PaddingError::KeyTooSmall);
+    }
+    if em.len() != k {
+        return Err(PaddingError::Decryption);
+    }
+
+    let l_hash = hash.digest(label);
+    let (masked_seed, masked_db) = em[1..].split_at(h_len);
+
+    let mut seed = masked_seed.to_vec();
+    xor_in_place(&mut seed, &mgf1(hash, masked_db, h_len));
+    let mut db = masked_db.to_vec();
+    xor_in_place(&mut db, &mgf1(hash, &seed, k - h_len - 1));
+
+    let mut bad = em[0];
+    for (a, b) in db[..h_len].iter().zip(&l_hash) {
+        bad |= a ^ b;
+    }
+
+    // PS is all zeros and ends with the 0x01 separator.
+    let (found, separator, bad_filler) = ct_find_separator(&db, h_len, 1, Some(0));
+    bad |= bad_filler | (!found & 1);
+
+    if bad != 0 {
+        return Err(PaddingError::Decryption);
+    }
+    Ok(db[separator + 1..].to_vec())
+}
+
+/// Encodes a message into a `k` byte PKCS #1 v1.5 encryption block.
+pub(crate) fn pkcs1v15_encode(message: &[u8], k: usize) -> Result<Vec<u8>, PaddingError> {
+    if k < 11 {
+        return Err(PaddingError::KeyTooSmall);
+    }
+    if message.len() > k - 11 {
+        return Err(PaddingError::MessageTooLong);
+    }
+
+    // EM = 0x00 || 0x02 || PS || 0x00 || M, where PS consists of nonzero random bytes.
+    let mut rng = rand::thread_rng();
+    let mut em = vec![0u8, 2u8];
+    while em.len() < k - message.len() - 1 {
+        let byte = rng.gen::<u8>();
+        if byte != 0 {
+            em.push(byte);
+        }
+    }
+    em.push(0);
+    em.extend_from_slice(message);
+    Ok(em)
+}
+
+/// Derives the key for the implicit rejection of a PKCS #1 v1.5 block from the
+/// private exponent and the `k` byte ciphertext, as OpenSSL 3.2 and the RSA
+/// guidance draft of the CFRG (draft-irtf-cfrg-rsa-guidance) do.
+pub(crate) fn implicit_rejection_key(d: &BigInt, ciphertext: &[u8], k: usize) -> Vec<u8> {
+    // d is smaller than the modulus in a valid key, a larger one is hashed as it is.
+    let d_hash = HashAlgorithm::Sha256.digest(&i2osp(d, k).unwrap_or_else(|| d.to_bytes_be().1));
+    hmac::hmac(HashAlgorithm::Sha256, &d_hash, ciphertext)
+}
+
+/// The pseudorandom function of the implicit rejection, which returns `len` bytes.
+fn rejection_prf(key: &[u8], label: &[u8], len: usize) -> Vec<u8> {
+    let prf = Hmac::new(HashAlgorithm::Sha256, key);
+    // The length in bits is encoded in two bytes, like OpenSSL does.
+    let bits = len * 8;
+    let bits = [(bits >> 8) as u8, bits as u8];
+    let mut output = Vec::with_capacity(len + HashAlgorithm::Sha256.output_size());
+    let mut counter: u16 = 0;
+    while output.len() < len {
+        output.extend_from_slice(&prf.mac(&[&counter.to_be_bytes()[..], label, &bits].concat()));
+        counter += 1;
+    }
+    output.truncate(len);
+    output
+}
+
+/// Decodes a `k` byte PKCS #1 v1.5 encryption block.
+///
+/// All checks are done on the whole block without early returns. If one of them
+/// fails, a synthetic message derived from `rejection_key` is returned instead of
+/// an error, so that neither the result nor the timing tells whether the padding
+/// was valid and the decryption can not be used for Bleichenbacher's attack. The
+/// synthetic message is the same for the same key and ciphertext.
+pub(crate) fn pkcs1v15_decode(
+    em: &[u8],
+    k: usize,
+    rejection_key: &[u8],
+) -> Result<Vec<u8>, PaddingError> {
+    if k < 11 {
+        return Err(PaddingError::KeyTooSmall);
+    }
+    if em.len() != k {
+        return Err(PaddingError::Decryption);
+    }
+
+    let mut bad = em[0] | (em[1] ^ 2);
+    let (found, separator, _) = ct_find_separator(em, 2, 0, None);
+    bad |= !found & 1;
+    // PS must be at least eight bytes long.
+    bad |= ct_less(separator, 10);
+    let good = ct_is_zero(bad);
+
+    // The synthetic message is as long as the last candidate length that is
+    // shorter than k - 10.
+    let synthetic = rejection_prf(rejection_key, b"message", k);
+    let candidates = rejection_prf(rejection_key, b"length", 256);
+    let max_len = k - 10;
+    let mask = usize::MAX >> max_len.leading_zeros();
+    let mut synthetic_len = 0;
+    for pair in candidates.chunks_exact(2) {
+        let len = u16::from_be_bytes([pair[0], pair[1]]) as usize & mask;
+        synthetic_len = ct_select(ct_less(len, max_len), len, synthetic_len);
+    }
+
+    let start = ct_select(good, separator + 1, k - synthetic_len);
+    let block: Vec<u8> = em
+        .iter()
+        .zip(&synthetic)
+        .map(|(&byte, &synthetic_byte)| {
+            ct_select(good, byte as usize, synthetic_byte as usize) as u8
+        })
+        .collect();
+    Ok(block[start..].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn i2osp_pads_to_length() {
+        assert_eq!(Some(vec![0, 0, 1, 2]), i2osp(&BigInt::from(258), 4));
+    }
+
+    #[test]
+    fn i2osp_rejects_too_large_integer() {
+        assert_eq!(None, i2osp(&BigInt::from(0x10000), 2));
+        assert_eq!(Some(vec![0xff, 0xff]), i2osp(&BigInt::from(0xffff), 2));
+    }
+
+    #[test]
+    fn os2ip_is_inverse_of_i2osp() {
+        let x = BigInt::from(123456789);
+        assert_eq!(x, os2ip(&i2osp(&x, 16).unwrap()));
+    }
+
+    #[test]
+    fn mgf1_returns_requested_length_with_hash_prefix() {
+        let mask = mgf1(HashAlgorithm::Sha256, b"seed", 70);
+
+        assert_eq!(70, mask.len());
+        assert_eq!(HashAlgorithm::Sha256.digest(b"seed\0\0\0\0"), mask[..32]);
+    }
+
+    #[test]
+    fn oaep_decode_returns_original_message() {
+        let em = oaep_encode(HashAlgorithm::Sha256, b"label", b"FooBarBaz", 128).unwrap();
+        let message = oaep_decode(HashAlgorithm::Sha256, b"label", &em, 128).unwrap();
+
+        assert_eq!(b"FooBarBaz".to_vec(), message);
+    }
+
+    #[test]
+    fn oaep_encode_is_randomized() {
+        let first = oaep_encode(HashAlgorithm::Sha256, b"", b"FooBarBaz", 128).unwrap();
+        let second = oaep_encode(HashAlgorithm::Sha256, b"", b"FooBarBaz", 128).unwrap();
+
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn oaep_decode_rejects_wrong_label() {
+        let em = oaep_encode(HashAlgorithm::Sha256, b"label", b"FooBarBaz", 128).unwrap();
+
+        assert_eq!(
+            Err(PaddingError::Decryption),
+            oaep_decode(HashAlgorithm::Sha256, b"other", &em, 128)
+        );
+    }
+
+    #[test]
+    fn oaep_decode_rejects_modified_block() {
+        let mut em = oaep_encode(HashAlgorithm::Sha256, b"", b"FooBarBaz", 128).unwrap();
+        em[100] ^= 1;
+
+        assert_eq!(
+            Err(PaddingError::Decryption),
+            oaep_decode(HashAlgorithm::Sha256, b"", &em, 128)
+        );
+    }
+
+    #[test]
+    fn oaep_decode_rejects_nonzero_first_byte() {
+        let mut em =
+            oaep_encode_with_seed(HashAlgorithm::Sha256, b"", b"Foo", 128, &[7u8; 32]).unwrap();
+        em[0] = 1;
+
+        assert_eq!(
+            Err(PaddingError::Decryption),
+            oaep_decode(HashAlgorithm::Sha256, b"", &em, 128)
+        );
+    }
+
+    #[test]
+    fn oaep_encode_rejects_too_long_message() {
+        let message = vec![0u8; 128 - 2 * 32 - 1];
+
+        assert_eq!(
+            Err(PaddingError::MessageTooLong),
+            oaep_encode(HashAlgorithm::Sha256, b"", &message, 128)
+        );
+    }
+
+    #[test]
+    fn oaep_encode_accepts_empty_message() {
+        let em = oaep_encode(HashAlgorithm::Sha256, b"", b"", 66).unwrap();
+
+        assert_eq!(
+            Vec::<u8>::new(),
+            oaep_decode(HashAlgorithm::Sha256, b"", &em, 66).unwrap()
+        );
+    }
+
+    #[test]
+    fn pkcs1v15_decode_returns_original_message() {
+        let em = pkcs1v15_encode(b"FooBarBaz", 64).unwrap();
+
+        assert_eq!(0, em[0]);
+        assert_eq!(2, em[1]);
+        assert!(em[2..64 - 10].iter().all(|byte| *byte != 0));
+        assert_eq!(
+            b"FooBarBaz".to_vec(),
+            pkcs1v15_decode(&em, 64, &[1u8; 32]).unwrap()
+        );
+    }
+
+    /// Checks that the block decodes to a synthetic message, which only depends
+    /// on the rejection key.
+    fn assert_rejected(em: &[u8]) {
+        let message = pkcs1v15_decode(em, 64, &[1u8; 32]).unwrap();
+
+        assert!(message.len() <= 64 - 11);
+        assert_eq!(
+            message,
+            pkcs1v15_decode(&[0u8; 64], 64, &[1u8; 32]).unwrap()
+        );
+        assert_ne!(message, pkcs1v15_decode(em, 64, &[2u8; 32]).unwrap());
+    }
+
+    #[test]
+    fn pkcs1v15_decode_rejects_wrong_block_type() {
+        let mut em = pkcs1v15_encode(b"FooBarBaz", 64).unwrap();
+        em[1] = 1;
+
+        assert_rejected(&em);
+    }
+
+    #[test]
+    fn pkcs1v15_decode_rejects_missing_separator() {
+        let mut em = vec![0u8, 2u8];
+        em.resize(64, 0xff);
+
+        assert_rejected(&em);
+    }
+
+    #[test]
+    fn pkcs1v15_decode_rejects_short_padding_string() {
+        let mut em = vec![0u8, 2u8, 1, 1, 1, 1, 1, 1, 1, 0];
+        em.resize(64, 0xaa);
+
+        assert_rejected(&em);
+    }
+
+    #[test]
+    fn pkcs1v15_encode_rejects_too_long_message() {
+        assert_eq!(
+            Err(PaddingError::MessageTooLong),
+            pkcs1v15_encode(&[1u8; 54], 64)
+        );
+    }
+}
@@ -0,0 +1,326 @@
+//! Passphrase protection of PKCS #8 private keys with PBES2 (RFC 8018, section 6.2),
+//! stored as `EncryptedPrivateKeyInfo` (RFC 5958, section 3).
+//!
+//! Keys are encrypted with AES-256-GCM (RFC 5084) under a key derived with
+//! PBKDF2-HMAC-SHA256, so that a wrong passphrase and a modified key file are
+//! both detected by the authentication tag. OpenSSL does not accept AEAD ciphers
+//! in PBES2, so it can not read these keys. Keys encrypted by OpenSSL with
+//! AES-CBC, which has no authentication, are accepted when decrypting, as are
+//! AES-128-GCM, AES-192-GCM and the other SHA-2 PRFs.
+
+use crate::aes::{self, Aes};
+use crate::der::{self, DerError, Reader};
+use crate::error::{self, Error};
+use crate::hash::HashAlgorithm;
+use crate::hmac;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use rand::RngCore;
+
+const PBES2: [u64; 7] = [1, 2, 840, 113549, 1, 5, 13];
+const PBKDF2: [u64; 7] = [1, 2, 840, 113549, 1, 5, 12];
+const HMAC_WITH_SHA256: [u64; 6] = [1, 2, 840, 113549, 2, 9];
+const HMAC_WITH_SHA384: [u64; 6] = [1, 2, 840, 113549, 2, 10];
+const HMAC_WITH_SHA512: [u64; 6] = [1, 2, 840, 113549, 2, 11];
+const AES128_CBC: [u64; 9] = [2, 16, 840, 1, 101, 3, 4, 1, 2];
+const AES192_CBC: [u64; 9] = [2, 16, 840, 1, 101, 3, 4, 1, 22];
+const AES256_CBC: [u64; 9] = [2, 16, 840, 1, 101, 3, 4, 1, 42];
+const AES128_GCM: [u64; 9] = [2, 16, 840, 1, 101, 3, 4, 1, 6];
+const AES192_GCM: [u64; 9] = [2, 16, 840, 1, 101, 3, 4, 1, 26];
+const AES256_GCM: [u64; 9] = [2, 16, 840, 1, 101, 3, 4, 1, 46];
+
+/// PBKDF2 iterations used when encrypting a key.
+pub const ITERATIONS: u32 = 600_000;
+/// Keys with more iterations are rejected, so that a crafted file can not keep
+/// the program busy for hours.
+const MAX_ITERATIONS: u32 = 10_000_000;
+const SALT_LEN: usize = 16;
+
+/// Returns true if the DER structure is an `EncryptedPrivateKeyInfo` protected with PBES2.
+pub(crate) fn is_encrypted(data: &[u8]) -> bool {
+    let read = || -> Result<bool, DerError> {
+        let mut info = Reader::new(data).read_sequence()?;
+        let mut algorithm = info.read_sequence()?;
+        Ok(algorithm.read_oid()? == PBES2)
+    };
+    read().unwrap_or(false)
+}
+
+/// Encrypts the DER encoding of a `PrivateKeyInfo` and returns the DER encoding
+/// of the `EncryptedPrivateKeyInfo`.
+pub fn encrypt(private_key_info: &[u8], passphrase: &[u8]) -> Vec<u8> {
+    encrypt_with(private_key_info, passphrase, ITERATIONS)
+}
+
+pub(crate) fn encrypt_with(private_key_info: &[u8], passphrase: &[u8], iterations: u32) -> Vec<u8> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; aes::GCM_NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let key = hmac::pbkdf2(HashAlgorithm::Sha256, passphrase, &salt, iterations, 32);
+    let cipher = Aes::new(&key).unwrap();
+    let encrypted = aes::gcm_encrypt(&cipher, &nonce, &[], private_key_info);
+
+    let kdf = der::sequence(&[
+        der::oid(&PBKDF2),
+        der::sequence(&[
+            der::octet_string(&salt),
+            der::integer(&BigInt::from(iterations)),
+            der::sequence(&[der::oid(&HMAC_WITH_SHA256), der::null()]),
+        ]),
+    ]);
+    // GCMParameters, with the tag length given since it differs from the default of 12.
+    let scheme = der::sequence(&[
+        der::oid(&AES256_GCM),
+        der::sequence(&[
+            der::octet_string(&nonce),
+            der::integer(&BigInt::from(aes::GCM_TAG_LEN)),
+        ]),
+    ]);
+    der::sequence(&[
+        der::sequence(&[der::oid(&PBES2), der::sequence(&[kdf, scheme])]),
+        der::octet_string(&encrypted),
+    ])
+}
+
+/// The cipher of an encrypted key and its IV or nonce.
+enum Scheme<'a> {
+    Cbc(&'a [u8; aes::BLOCK_LEN]),
+    Gcm(&'a [u8; aes::GCM_NONCE_LEN]),
+}
+
+/// The parameters read from the `AlgorithmIdentifier` of an encrypted key.
+struct Parameters<'a> {
+    salt: &'a [u8],
+    iterations: u32,
+    prf: HashAlgorithm,
+    key_len: usize,
+    scheme: Scheme<'a>,
+}
+
+fn unsupported(what: &str) -> Error {
+    Error::Parse(format!("Unsupported {} in the encrypted key", what))
+}
+
+fn read_parameters<'a>(algorithm: &mut Reader<'a>) -> error::Result<Parameters<'a>> {
+    if algorithm.read_oid()? != PBES2 {
+        return Err(unsupported("encryption scheme"));
+    }
+    let mut params = algorithm.read_sequence()?;
+    algorithm.finish()?;
+
+    let mut kdf = params.read_sequence()?;
+    if kdf.read_oid()? != PBKDF2 {
+        return Err(unsupported("key derivation function"));
+    }
+    let mut kdf_params = kdf.read_sequence()?;
+    kdf.finish()?;
+    let salt = kdf_params.read_octet_string()?;
+    let iterations = kdf_params
+        .read_unsigned()?
+        .to_u32()
+        .filter(|iterations| (1..=MAX_ITERATIONS).contains(iterations))
+        .ok_or_else(|| unsupported("iteration count"))?;
+    let key_len = match kdf_params.peek_tag() {
+        Some(der::INTEGER) => Some(kdf_params.read_unsigned()?),
+        _ => None,
+    };
+    // The PRF defaults to HMAC-SHA1, which is not supported.
+    let mut prf_algorithm = kdf_params
+        .read_sequence()
+        .map_err(|_| unsupported("PBKDF2 pseudorandom function"))?;
+    kdf_params.finish()?;
+    let prf = match prf_algorithm.read_oid()?.as_slice() {
+        oid if oid == HMAC_WITH_SHA256 => HashAlgorithm::Sha256,
+        oid if oid == HMAC_WITH_SHA384 => HashAlgorithm::Sha384,
+        oid if oid == HMAC_WITH_SHA512 => HashAlgorithm::Sha512,
+        _ => return Err(unsupported("PBKDF2 pseudorandom function")),
+    };
+    if !prf_algorithm.is_empty() {
+        prf_algorithm.read_null()?;
+    }
+    prf_algorithm.finish()?;
+
+    let invalid = || Error::Parse("Invalid parameters in the encrypted key".to_string());
+    let mut scheme_algorithm = params.read_sequence()?;
+    params.finish()?;
+    let (cipher_key_len, gcm) = match scheme_algorithm.read_oid()?.as_slice() {
+        oid if oid == AES128_CBC => (16, false),
+        oid if oid == AES192_CBC => (24, false),
+        oid if oid == AES256_CBC => (32, false),
+        oid if oid == AES128_GCM => (16, true),
+        oid if oid == AES192_GCM => (24, true),
+        oid if oid == AES256_GCM => (32, true),
+        _ => return Err(unsupported("cipher")),
+    };
+    let scheme = if gcm {
+        let mut gcm_params = scheme_algorithm.read_sequence()?;
+        let nonce = gcm_params.read_octet_string()?;
+        // Only full-length tags are supported, the default length is 12.
+        if gcm_params.is_empty() || gcm_params.read_unsigned()? != BigInt::from(aes::GCM_TAG_LEN) {
+            return Err(unsupported("GCM tag length"));
+        }
+        gcm_params.finish()?;
+        Scheme::Gcm(
+            nonce
+                .try_into()
+                .map_err(|_| unsupported("GCM nonce length"))?,
+        )
+    } else {
+        Scheme::Cbc(
+            scheme_algorithm
+                .read_octet_string()?
+                .try_into()
+                .map_err(|_| invalid())?,
+        )
+    };
+    scheme_algorithm.finish()?;
+    if key_len.is_some_and(|len| len != BigInt::from(cipher_key_len)) {
+        return Err(invalid());
+    }
+
+    Ok(Parameters {
+        salt,
+        iterations,
+        prf,
+        key_len: cipher_key_len,
+        scheme,
+    })
+}
+
+/// Decrypts the DER encoding of an `EncryptedPrivateKeyInfo` and returns the
+/// DER encoding of the `PrivateKeyInfo` in it.
+pub fn decrypt(data: &[u8], passphrase: &[u8]) -> error::Result<Vec<u8>> {
+    let mut outer = Reader::new(data);
+    let mut info = outer.read_sequence()?;
+    outer.finish()?;
+    let params = read_parameters(&mut info.read_sequence()?)?;
+    let encrypted = info.read_octet_string()?;
+    info.finish()?;
+
+    let key = hmac::pbkdf2(
+        params.prf,
+        passphrase,
+        params.salt,
+        params.iterations,
+        params.key_len,
+    );
+    let cipher = Aes::new(&key).unwrap();
+    let decrypted = match params.scheme {
+        Scheme::Cbc(iv) => aes::cbc_decrypt(&cipher, iv, encrypted),
+        Scheme::Gcm(nonce) => aes::gcm_decrypt(&cipher, nonce, &[], encrypted),
+    }
+    .ok_or_else(wrong_passphrase)?;
+
+    // With CBC a wrong passphrase produces valid padding once in 256 tries, but
+    // hardly ever a single DER sequence.
+    let mut reader = Reader::new(&decrypted);
+    match (reader.read_sequence(), reader.finish()) {
+        (Ok(_), Ok(())) => Ok(decrypted),
+        _ => Err(wrong_passphrase()),
+    }
+}
+
+/// The error for a passphrase that does not decrypt the key. For keys encrypted
+/// with CBC, which has no authentication, this is also returned when the
+/// decrypted key is not valid.
+pub(crate) fn wrong_passphrase() -> Error {
+    Error::Passphrase("Wrong passphrase or corrupted key".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decrypt_returns_encrypted_data() {
+        let data = der::sequence(&[der::null()]);
+        let encrypted = encrypt_with(&data, b"passphrase", 10);
+
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(data, decrypt(&encrypted, b"passphrase").unwrap());
+    }
+
+    #[test]
+    fn decrypt_rejects_wrong_passphrase() {
+        let encrypted = encrypt_with(&der::sequence(&[der::null()]), b"passphrase", 10);
+
+        for wrong in [&b"wrong"[..], b"", b"passphrase2"] {
+            assert!(matches!(
+                decrypt(&encrypted, wrong),
+                Err(Error::Passphrase(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn decrypt_rejects_modified_key() {
+        let encrypted = encrypt_with(&der::sequence(&[der::null()]), b"passphrase", 10);
+        // The encrypted key and the tag are at the end.
+        let start = encrypted.len() - 4 - aes::GCM_TAG_LEN;
+
+        for i in start..encrypted.len() {
+            let mut modified = encrypted.clone();
+            modified[i] ^= 0x80;
+            assert!(matches!(
+                decrypt(&modified, b"passphrase"),
+                Err(Error::Passphrase(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn decrypt_reads_cbc_keys() {
+        let data = der::sequence(&[der::null(), der::null()]);
+        let salt = [3u8; SALT_LEN];
+        let iv = [5u8; aes::BLOCK_LEN];
+        let key = hmac::pbkdf2(HashAlgorithm::Sha256, b"passphrase", &salt, 10, 16);
+        let encrypted = aes::cbc_encrypt(&Aes::new(&key).unwrap(), &iv, &data);
+        // The parameters OpenSSL writes with -v2 aes-128-cbc.
+        let kdf = der::sequence(&[
+            der::oid(&PBKDF2),
+            der::sequence(&[
+                der::octet_string(&salt),
+                der::integer(&BigInt::from(10)),
+                der::sequence(&[der::oid(&HMAC_WITH_SHA256), der::null()]),
+            ]),
+        ]);
+        let scheme = der::sequence(&[der::oid(&AES128_CBC), der::octet_string(&iv)]);
+        let info = der::sequence(&[
+            der::sequence(&[der::oid(&PBES2), der::sequence(&[kdf, scheme])]),
+            der::octet_string(&encrypted),
+        ]);
+
+        assert_eq!(data, decrypt(&info, b"passphrase").unwrap());
+        assert!(matches!(
+            decrypt(&info, b"wrong"),
+            Err(Error::Passphrase(_))
+        ));
+    }
+
+    #[test]
+    fn decrypt_rejects_unsupported_cipher() {
+        let encrypted = encrypt_with(b"FooBarBaz", b"passphrase", 10);
+        let aes256 = der::oid(&AES256_GCM);
+        let position = encrypted
+            .windows(aes256.len())
+            .position(|window| window == aes256)
+            .unwrap();
+        let mut modified = encrypted.clone();
+        // Change the OID to that of aes256-ECB.
+        modified[position + aes256.len() - 1] = 41;
+
+        assert!(matches!(
+            decrypt(&modified, b"passphrase"),
+            Err(Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn is_encrypted_rejects_plain_keys() {
+        let spki = crate::key_format::spki_der(&BigInt::from(3233), &BigInt::from(17));
+
+        assert!(!is_encrypted(&spki));
+    }
+}
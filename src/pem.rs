@@ -0,0 +1,170 @@
+//! PEM armoring as specified in RFC 7468: base64 data between `-----BEGIN <label>-----`
+//! and `-----END <label>-----` lines.
+
+use std::fmt;
+
+use crate::encoding::{base64_decode, base64_encode};
+
+/// Errors produced while decoding PEM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PemError {
+    /// No `-----BEGIN` line was found.
+    MissingBegin,
+    /// The `-----END` line is missing or has another label.
+    MissingEnd,
+    /// The data between the lines is not valid base64.
+    InvalidBase64,
+}
+
+impl fmt::Display for PemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PemError::MissingBegin => write!(f, "PEM data is missing the BEGIN line"),
+            PemError::MissingEnd => write!(f, "PEM data is missing the END line"),
+            PemError::InvalidBase64 => write!(f, "PEM data contains invalid base64"),
+        }
+    }
+}
+
+impl std::error::Error for PemError {}
+
+/// Returns true if the text looks like PEM.
+pub fn is_pem(text: &[u8]) -> bool {
+    let start = text
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .unwrap_or(text.len());
+    text[start..].starts_with(b"-----BEGIN ")
+}
+
+/// Wraps the data in PEM armor with lines of 64 characters.
+pub fn encode(label: &str, data: &[u8]) -> String {
+    let encoded = base64_encode(data);
+
+    let mut result = format!("-----BEGIN {}-----\n", label);
+    for line in encoded.as_bytes().chunks(64) {
+        result.push_str(std::str::from_utf8(line).unwrap());
+        result.push('\n');
+    }
+    result.push_str(&format!("-----END {}-----\n", label));
+    result
+}
+
+/// Decodes the first PEM block in the text and returns its label and data.
+pub fn decode(text: &str) -> Result<(String, Vec<u8>), PemError> {
+    let mut lines = text.lines().map(str::trim);
+
+    let label = lines
+        .by_ref()
+        .find_map(|line| {
+            line.strip_prefix("-----BEGIN ")
+                .and_then(|rest| rest.strip_suffix("-----"))
+        })
+        .ok_or(PemError::MissingBegin)?
+        .to_string();
+    let end = format!("-----END {}-----", label);
+
+    let mut body = String::new();
+    for line in lines {
+        if line == end {
+            let data = base64_decode(&body).ok_or(PemError::InvalidBase64)?;
+            return Ok((label, data));
+        }
+        // Headers such as those of legacy encrypted keys are not supported.
+        if line.contains(':') {
+            return Err(PemError::InvalidBase64);
+        }
+        body.push_str(line);
+    }
+
+    Err(PemError::MissingEnd)
+}
+
+/// Decodes all PEM blocks in the text, like a chain of certificates.
+pub fn decode_all(text: &str) -> Result<Vec<(String, Vec<u8>)>, PemError> {
+    let mut blocks = Vec::new();
+    let mut block = String::new();
+    for line in text.lines() {
+        block.push_str(line);
+        block.push('\n');
+        if line.trim().starts_with("-----END ") {
+            blocks.push(decode(&block)?);
+            block.clear();
+        }
+    }
+    // Without any block, or with an unfinished one, decoding the rest fails.
+    if blocks.is_empty() || block.contains("-----BEGIN ") {
+        decode(&block)?;
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_wraps_lines() {
+        let encoded = encode("TEST", &[0u8; 60]);
+        let lines: Vec<&str> = encoded.lines().collect();
+
+        assert_eq!("-----BEGIN TEST-----", lines[0]);
+        assert_eq!(64, lines[1].len());
+        assert_eq!(16, lines[2].len());
+        assert_eq!("-----END TEST-----", lines[3]);
+    }
+
+    #[test]
+    fn decode_returns_original_data() {
+        let data: Vec<u8> = (0..200).collect();
+
+        assert_eq!(
+            Ok(("RSA PUBLIC KEY".to_string(), data.clone())),
+            decode(&encode("RSA PUBLIC KEY", &data))
+        );
+    }
+
+    #[test]
+    fn decode_all_returns_all_blocks() {
+        let text = format!("{}Comment\n{}", encode("A", b"foo"), encode("B", b"bar"));
+
+        assert_eq!(
+            Ok(vec![
+                ("A".to_string(), b"foo".to_vec()),
+                ("B".to_string(), b"bar".to_vec())
+            ]),
+            decode_all(&text)
+        );
+        assert_eq!(Err(PemError::MissingBegin), decode_all("foo"));
+        assert_eq!(
+            Err(PemError::MissingEnd),
+            decode_all(&format!("{}-----BEGIN A-----\n", encode("A", b"foo")))
+        );
+    }
+
+    #[test]
+    fn decode_skips_text_before_block() {
+        let text = format!("Some comment\n\n{}", encode("TEST", b"foo"));
+
+        assert_eq!(Ok(("TEST".to_string(), b"foo".to_vec())), decode(&text));
+    }
+
+    #[test]
+    fn decode_rejects_mismatched_end() {
+        assert_eq!(
+            Err(PemError::MissingEnd),
+            decode("-----BEGIN A-----\nZm9v\n-----END B-----\n")
+        );
+    }
+
+    #[test]
+    fn decode_rejects_missing_begin() {
+        assert_eq!(Err(PemError::MissingBegin), decode("Zm9v\n"));
+    }
+
+    #[test]
+    fn is_pem_detects_armor() {
+        assert!(is_pem(b"\n-----BEGIN TEST-----\n"));
+        assert!(!is_pem(b"12345\n65537"));
+    }
+}
@@ -0,0 +1,243 @@
+use crate::hash::HashAlgorithm;
+use crate::padding::{mgf1, PaddingError};
+use rand::RngCore;
+
+/// Signature schemes supported by `Key::sign` and `Key::verify`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureScheme {
+    /// RSASSA-PKCS1-v1_5 as specified in RFC 8017, section 8.2.
+    Pkcs1v15 { hash: HashAlgorithm },
+    /// RSASSA-PSS as specified in RFC 8017, section 8.1. Signatures are made with
+    /// a salt as long as the hash, but a salt of any length is accepted when verifying.
+    Pss { hash: HashAlgorithm },
+}
+
+impl SignatureScheme {
+    /// Hash function used to digest the message.
+    pub fn hash(&self) -> HashAlgorithm {
+        match self {
+            SignatureScheme::Pkcs1v15 { hash } | SignatureScheme::Pss { hash } => *hash,
+        }
+    }
+
+    /// Encodes a message digest into the block that is exponentiated.
+    /// `mod_bits` is the length of the modulus in bits.
+    pub(crate) fn encode(&self, m_hash: &[u8], mod_bits: usize) -> Result<Vec<u8>, PaddingError> {
+        match self {
+            SignatureScheme::Pkcs1v15 { hash } => {
+                emsa_pkcs1v15_encode(*hash, m_hash, mod_bits.div_ceil(8))
+            }
+            SignatureScheme::Pss { hash } => {
+                let mut salt = vec![0u8; hash.output_size()];
+                rand::thread_rng().fill_bytes(&mut salt);
+                emsa_pss_encode(*hash, m_hash, &salt, mod_bits - 1)
+            }
+        }
+    }
+
+    /// Checks that the block recovered from a signature encodes the message digest.
+    pub(crate) fn verify(&self, m_hash: &[u8], em: &[u8], mod_bits: usize) -> bool {
+        match self {
+            SignatureScheme::Pkcs1v15 { hash } => {
+                match emsa_pkcs1v15_encode(*hash, m_hash, mod_bits.div_ceil(8)) {
+                    Ok(expected) => ct_eq(&expected, em),
+                    Err(_) => false,
+                }
+            }
+            SignatureScheme::Pss { hash } => emsa_pss_verify(*hash, m_hash, em, mod_bits - 1),
+        }
+    }
+
+    /// Length of the encoded block in bytes.
+    pub(crate) fn encoded_len(&self, mod_bits: usize) -> usize {
+        match self {
+            SignatureScheme::Pkcs1v15 { .. } => mod_bits.div_ceil(8),
+            SignatureScheme::Pss { .. } => (mod_bits - 1).div_ceil(8),
+        }
+    }
+}
+
+/// Compares two byte strings in time that only depends on their length.
+fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// DER encoded `DigestInfo` prefix that precedes the digest, from RFC 8017, section 9.2.
+fn digest_info_prefix(hash: HashAlgorithm) -> &'static [u8] {
+    match hash {
+        HashAlgorithm::Sha256 => &[
+            0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x01, 0x05, 0x00, 0x04, 0x20,
+        ],
+        HashAlgorithm::Sha384 => &[
+            0x30, 0x41, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x02, 0x05, 0x00, 0x04, 0x30,
+        ],
+        HashAlgorithm::Sha512 => &[
+            0x30, 0x51, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x03, 0x05, 0x00, 0x04, 0x40,
+        ],
+    }
+}
+
+/// EMSA-PKCS1-v1_5 encoding from RFC 8017, section 9.2.
+fn emsa_pkcs1v15_encode(
+    hash: HashAlgorithm,
+    m_hash: &[u8],
+    em_len: usize,
+) -> Result<Vec<u8>, PaddingError> {
+    let prefix = digest_info_prefix(hash);
+    let t_len = prefix.len() + m_hash.len();
+    if em_len < t_len + 11 {
+        return Err(PaddingError::KeyTooSmall);
+    }
+
+    // EM = 0x00 || 0x01 || PS || 0x00 || T, where PS consists of 0xff bytes.
+    let mut em = vec![0u8, 1u8];
+    em.resize(em_len - t_len - 1, 0xff);
+    em.push(0);
+    em.extend_from_slice(prefix);
+    em.extend_from_slice(m_hash);
+    Ok(em)
+}
+
+/// EMSA-PSS encoding from RFC 8017, section 9.1.1.
+fn emsa_pss_encode(
+    hash: HashAlgorithm,
+    m_hash: &[u8],
+    salt: &[u8],
+    em_bits: usize,
+) -> Result<Vec<u8>, PaddingError> {
+    let h_len = hash.output_size();
+    let em_len = em_bits.div_ceil(8);
+    if em_len < h_len + salt.len() + 2 {
+        return Err(PaddingError::KeyTooSmall);
+    }
+
+    let mut hasher = hash.hasher();
+    hasher.update(&[0u8; 8]);
+    hasher.update(m_hash);
+    hasher.update(salt);
+    let h = hasher.finalize();
+
+    // DB = PS || 0x01 || salt
+    let mut db = vec![0u8; em_len - salt.len() - h_len - 2];
+    db.push(1);
+    db.extend_from_slice(salt);
+
+    for (byte, mask) in db.iter_mut().zip(mgf1(hash, &h, em_len - h_len - 1)) {
+        *byte ^= mask;
+    }
+    db[0] &= 0xff >> (8 * em_len - em_bits);
+
+    let mut em = db;
+    em.extend_from_slice(&h);
+    em.push(0xbc);
+    Ok(em)
+}
+
+/// EMSA-PSS verification from RFC 8017, section 9.1.2. The salt length is not
+/// fixed, but recovered from the position of the 0x01 byte that precedes the salt.
+fn emsa_pss_verify(hash: HashAlgorithm, m_hash: &[u8], em: &[u8], em_bits: usize) -> bool {
+    let h_len = hash.output_size();
+    let em_len = em_bits.div_ceil(8);
+    if em.len() != em_len || em_len < h_len + 2 || em[em_len - 1] != 0xbc {
+        return false;
+    }
+
+    let (masked_db, h) = em[..em_len - 1].split_at(em_len - h_len - 1);
+    let top_mask = !(0xffu8 >> (8 * em_len - em_bits));
+    if masked_db[0] & top_mask != 0 {
+        return false;
+    }
+
+    let mut db = masked_db.to_vec();
+    for (byte, mask) in db.iter_mut().zip(mgf1(hash, h, em_len - h_len - 1)) {
+        *byte ^= mask;
+    }
+    db[0] &= !top_mask;
+
+    // DB = PS || 0x01 || salt, where PS consists of zero bytes.
+    let Some(separator) = db.iter().position(|byte| *byte != 0) else {
+        return false;
+    };
+    if db[separator] != 1 {
+        return false;
+    }
+    let salt = &db[separator + 1..];
+
+    let mut hasher = hash.hasher();
+    hasher.update(&[0u8; 8]);
+    hasher.update(m_hash);
+    hasher.update(salt);
+    ct_eq(&hasher.finalize(), h)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emsa_pkcs1v15_encode_builds_correct_block() {
+        let m_hash = HashAlgorithm::Sha256.digest(b"abc");
+        let em = emsa_pkcs1v15_encode(HashAlgorithm::Sha256, &m_hash, 64).unwrap();
+
+        assert_eq!(64, em.len());
+        assert_eq!([0u8, 1u8], em[..2]);
+        assert!(em[2..12].iter().all(|byte| *byte == 0xff));
+        assert_eq!(m_hash, em[32..]);
+        assert_eq!(0x30, em[13]);
+    }
+
+    #[test]
+    fn emsa_pkcs1v15_encode_rejects_small_block() {
+        let m_hash = HashAlgorithm::Sha256.digest(b"abc");
+
+        assert_eq!(
+            Err(PaddingError::KeyTooSmall),
+            emsa_pkcs1v15_encode(HashAlgorithm::Sha256, &m_hash, 61)
+        );
+    }
+
+    #[test]
+    fn emsa_pss_verify_accepts_encoded_block() {
+        let m_hash = HashAlgorithm::Sha256.digest(b"abc");
+        let em = emsa_pss_encode(HashAlgorithm::Sha256, &m_hash, &[3u8; 32], 1023).unwrap();
+
+        assert_eq!(128, em.len());
+        assert!(emsa_pss_verify(HashAlgorithm::Sha256, &m_hash, &em, 1023));
+    }
+
+    #[test]
+    fn emsa_pss_verify_accepts_any_salt_length() {
+        let m_hash = HashAlgorithm::Sha256.digest(b"abc");
+        // The longest salt fills DB except for the 0x01 byte.
+        for salt_len in [0, 20, 64, 128 - 32 - 2] {
+            let salt = vec![3u8; salt_len];
+            let em = emsa_pss_encode(HashAlgorithm::Sha256, &m_hash, &salt, 1023).unwrap();
+
+            assert!(emsa_pss_verify(HashAlgorithm::Sha256, &m_hash, &em, 1023));
+        }
+    }
+
+    #[test]
+    fn emsa_pss_verify_rejects_other_digest() {
+        let m_hash = HashAlgorithm::Sha256.digest(b"abc");
+        let other = HashAlgorithm::Sha256.digest(b"abd");
+        let em = emsa_pss_encode(HashAlgorithm::Sha256, &m_hash, &[3u8; 32], 1023).unwrap();
+
+        assert!(!emsa_pss_verify(HashAlgorithm::Sha256, &other, &em, 1023));
+    }
+
+    #[test]
+    fn emsa_pss_verify_rejects_set_top_bit() {
+        let m_hash = HashAlgorithm::Sha256.digest(b"abc");
+        let mut em = emsa_pss_encode(HashAlgorithm::Sha256, &m_hash, &[3u8; 32], 1023).unwrap();
+        em[0] |= 0x80;
+
+        assert!(!emsa_pss_verify(HashAlgorithm::Sha256, &m_hash, &em, 1023));
+    }
+}
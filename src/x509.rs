@@ -0,0 +1,1596 @@
+//! X.509 v3 certificates (RFC 5280) and PKCS #10 certificate signing requests
+//! (RFC 2986), signed with RSASSA-PKCS1-v1_5 and SHA-256.
+//!
+//! Certificates carry the basic constraints and key usage extensions, which
+//! are critical, the subject alternative names, and the subject and authority
+//! key identifiers. Requests ask for the same extensions, except the key
+//! identifiers, with an extensionRequest attribute.
+//!
+//! Parsed certificates can issue certificates for requests and be verified as
+//! a chain, which also accepts signatures with SHA-384 and SHA-512.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use num_bigint::{BigInt, Sign};
+use rand::RngCore;
+
+use crate::der::{self, DerError, Reader};
+use crate::error::{self, Error};
+use crate::hash::HashAlgorithm;
+use crate::jwt;
+use crate::keys::{self, Key};
+use crate::pem;
+use crate::signature::SignatureScheme;
+
+/// The PEM label of certificates.
+pub const CERTIFICATE_LABEL: &str = "CERTIFICATE";
+/// The PEM label of certificate signing requests.
+pub const REQUEST_LABEL: &str = "CERTIFICATE REQUEST";
+
+/// The PEM label of requests written by older tools.
+const LEGACY_REQUEST_LABEL: &str = "NEW CERTIFICATE REQUEST";
+
+const SHA256_WITH_RSA_ENCRYPTION: &[u64] = &[1, 2, 840, 113549, 1, 1, 11];
+const SHA384_WITH_RSA_ENCRYPTION: &[u64] = &[1, 2, 840, 113549, 1, 1, 12];
+const SHA512_WITH_RSA_ENCRYPTION: &[u64] = &[1, 2, 840, 113549, 1, 1, 13];
+const EXTENSION_REQUEST: &[u64] = &[1, 2, 840, 113549, 1, 9, 14];
+const SUBJECT_KEY_IDENTIFIER: &[u64] = &[2, 5, 29, 14];
+const KEY_USAGE: &[u64] = &[2, 5, 29, 15];
+const SUBJECT_ALT_NAME: &[u64] = &[2, 5, 29, 17];
+const BASIC_CONSTRAINTS: &[u64] = &[2, 5, 29, 19];
+const AUTHORITY_KEY_IDENTIFIER: &[u64] = &[2, 5, 29, 35];
+const EXTENDED_KEY_USAGE: &[u64] = &[2, 5, 29, 37];
+
+const RFC822_NAME: u8 = der::context(1, false);
+const DNS_NAME: u8 = der::context(2, false);
+const URI: u8 = der::context(6, false);
+const IP_ADDRESS: u8 = der::context(7, false);
+
+const COUNTRY: &[u64] = &[2, 5, 4, 6];
+const DOMAIN_COMPONENT: &[u64] = &[0, 9, 2342, 19200300, 100, 1, 25];
+
+/// The attribute types of distinguished names that can be given by their short name.
+const ATTRIBUTE_TYPES: [(&str, &[u64]); 7] = [
+    ("CN", &[2, 5, 4, 3]),
+    ("C", COUNTRY),
+    ("L", &[2, 5, 4, 7]),
+    ("ST", &[2, 5, 4, 8]),
+    ("O", &[2, 5, 4, 10]),
+    ("OU", &[2, 5, 4, 11]),
+    ("DC", DOMAIN_COMPONENT),
+];
+
+/// The last second that can be encoded, which RFC 5280 uses for certificates
+/// without a well-defined expiration date: 9999-12-31 23:59:59 UTC.
+const MAX_TIME: i64 = 253_402_300_799;
+
+/// Reasons why a certificate or a request is rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CertificateError {
+    /// The signature was not made by the issuer, or for a request by the key
+    /// in the request.
+    InvalidSignature(String),
+    /// The signature algorithm is not RSASSA-PKCS1-v1_5 with SHA-256, SHA-384
+    /// or SHA-512.
+    UnsupportedAlgorithm(String),
+    /// The certificate has a critical extension that is not supported.
+    UnsupportedCritical(String),
+    /// The validity period of the certificate has ended.
+    Expired(String),
+    /// The validity period of the certificate has not started yet.
+    NotYetValid(String),
+    /// Neither the chain nor the trust anchors contain the issuer.
+    UnknownIssuer(String),
+    /// The issuer is not a CA, or its key may not sign certificates.
+    NotCa(String),
+    /// More intermediate CAs follow the CA than its path length constraint allows.
+    PathLengthExceeded(String),
+    /// The request asks for a CA certificate, but only leaf certificates are issued.
+    CaRequested,
+}
+
+impl fmt::Display for CertificateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CertificateError::InvalidSignature(name) => {
+                write!(f, "The signature of {} is invalid", name)
+            }
+            CertificateError::UnsupportedAlgorithm(oid) => {
+                write!(f, "Unsupported signature algorithm: {}", oid)
+            }
+            CertificateError::UnsupportedCritical(name) => {
+                write!(f, "{} has an unsupported critical extension", name)
+            }
+            CertificateError::Expired(name) => write!(f, "The certificate {} has expired", name),
+            CertificateError::NotYetValid(name) => {
+                write!(f, "The certificate {} is not valid yet", name)
+            }
+            CertificateError::UnknownIssuer(name) => {
+                write!(f, "The issuer of {} is not trusted", name)
+            }
+            CertificateError::NotCa(name) => {
+                write!(f, "{} is not allowed to issue certificates", name)
+            }
+            CertificateError::PathLengthExceeded(name) => {
+                write!(f, "Too many intermediate CAs below {}", name)
+            }
+            CertificateError::CaRequested => write!(
+                f,
+                "The request asks for a CA certificate, only leaf certificates are issued"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CertificateError {}
+
+fn dotted(oid: &[u64]) -> String {
+    let arcs: Vec<String> = oid.iter().map(u64::to_string).collect();
+    arcs.join(".")
+}
+
+/// A distinguished name with one attribute per relative distinguished name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Name {
+    attributes: Vec<(Vec<u64>, String)>,
+}
+
+impl Name {
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty()
+    }
+
+    fn to_der(&self) -> Vec<u8> {
+        let rdns: Vec<Vec<u8>> = self
+            .attributes
+            .iter()
+            .map(|(oid, value)| {
+                // Countries must be printable strings, domain components IA5 strings.
+                let tag = match oid.as_slice() {
+                    COUNTRY => der::PRINTABLE_STRING,
+                    DOMAIN_COMPONENT => der::IA5_STRING,
+                    _ => der::UTF8_STRING,
+                };
+                der::set(&[der::sequence(&[
+                    der::oid(oid),
+                    der::tlv(tag, value.as_bytes()),
+                ])])
+            })
+            .collect();
+        der::sequence(&rdns)
+    }
+}
+
+impl FromStr for Name {
+    type Err = String;
+
+    /// Parses attributes like `CN=example.com,O=Example,C=DE`, which are
+    /// encoded in the order they are written, as `openssl x509 -subject`
+    /// prints them. Commas in values are escaped with a backslash.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = vec![String::new()];
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some(escaped) => parts.last_mut().unwrap().push(escaped),
+                    None => return Err("Unterminated escape in name".to_string()),
+                },
+                ',' => parts.push(String::new()),
+                c => parts.last_mut().unwrap().push(c),
+            }
+        }
+        if s.trim().is_empty() {
+            return Ok(Name::default());
+        }
+
+        let mut attributes = Vec::new();
+        for part in parts {
+            let (short_name, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid name attribute: {}", part.trim()))?;
+            let (short_name, value) = (short_name.trim(), value.trim());
+            let &(_, oid) = ATTRIBUTE_TYPES
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(short_name))
+                .ok_or_else(|| format!("Unsupported name attribute: {}", short_name))?;
+            let valid = match oid {
+                COUNTRY => value.len() == 2 && value.bytes().all(|b| b.is_ascii_alphabetic()),
+                DOMAIN_COMPONENT => !value.is_empty() && value.is_ascii(),
+                _ => !value.is_empty(),
+            };
+            if !valid {
+                return Err(format!("Invalid value for {}: {}", short_name, value));
+            }
+            attributes.push((oid.to_vec(), value.to_string()));
+        }
+        Ok(Name { attributes })
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (oid, value)) in self.attributes.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match ATTRIBUTE_TYPES.iter().find(|(_, known)| known == oid) {
+                Some((name, _)) => write!(f, "{}=", name)?,
+                None => write!(f, "{}=", dotted(oid))?,
+            }
+            write!(f, "{}", value.replace('\\', "\\\\").replace(',', "\\,"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads an encoded name. Attributes of multi-valued relative distinguished
+/// names are read as if each had its own.
+fn read_name(data: &[u8]) -> Result<Name, DerError> {
+    let mut rdns = Reader::new(data).read_sequence()?;
+    let mut attributes = Vec::new();
+    while !rdns.is_empty() {
+        let mut rdn = Reader::new(rdns.read(der::SET)?);
+        while !rdn.is_empty() {
+            let mut attribute = rdn.read_sequence()?;
+            let oid = attribute.read_oid()?;
+            let (tag, value) = attribute.read_any()?;
+            attribute.finish()?;
+            attributes.push((oid, decode_string(tag, value)?));
+        }
+    }
+    Ok(Name { attributes })
+}
+
+/// Decodes the string types of names. BMPString is UTF-16, the others are
+/// read as UTF-8, which covers PrintableString and IA5String.
+fn decode_string(tag: u8, value: &[u8]) -> Result<String, DerError> {
+    if tag != der::BMP_STRING {
+        return Ok(String::from_utf8_lossy(value).into_owned());
+    }
+    if !value.len().is_multiple_of(2) {
+        return Err(DerError::InvalidValue);
+    }
+    let units = value
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]));
+    char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .map_err(|_| DerError::InvalidValue)
+}
+
+/// A subject alternative name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GeneralName {
+    /// A host name, which may start with a `*.` wildcard label.
+    Dns(String),
+    /// An IPv4 or IPv6 address.
+    Ip(IpAddr),
+    /// An email address.
+    Email(String),
+    /// A URI.
+    Uri(String),
+}
+
+impl GeneralName {
+    fn to_der(&self) -> Vec<u8> {
+        match self {
+            GeneralName::Email(email) => der::tlv(RFC822_NAME, email.as_bytes()),
+            GeneralName::Dns(name) => der::tlv(DNS_NAME, name.as_bytes()),
+            GeneralName::Uri(uri) => der::tlv(URI, uri.as_bytes()),
+            GeneralName::Ip(IpAddr::V4(ip)) => der::tlv(IP_ADDRESS, &ip.octets()),
+            GeneralName::Ip(IpAddr::V6(ip)) => der::tlv(IP_ADDRESS, &ip.octets()),
+        }
+    }
+
+    /// Decodes a name from its tag and content. Returns `None` for the types
+    /// that are not supported, like directory names.
+    fn decode(tag: u8, value: &[u8]) -> Option<GeneralName> {
+        let text = || {
+            std::str::from_utf8(value)
+                .ok()
+                .filter(|text| text.is_ascii())
+                .map(str::to_string)
+        };
+        match tag {
+            RFC822_NAME => text().map(GeneralName::Email),
+            DNS_NAME => text().map(GeneralName::Dns),
+            URI => text().map(GeneralName::Uri),
+            IP_ADDRESS => match value.len() {
+                4 => Some(GeneralName::Ip(IpAddr::from(
+                    <[u8; 4]>::try_from(value).ok()?,
+                ))),
+                16 => Some(GeneralName::Ip(IpAddr::from(
+                    <[u8; 16]>::try_from(value).ok()?,
+                ))),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for GeneralName {
+    type Err = String;
+
+    /// Parses names like `DNS:example.com`, `IP:127.0.0.1`, `email:me@example.com`
+    /// or `URI:https://example.com`, with the type in any case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid subject alternative name: {}", s);
+        let (kind, value) = s.split_once(':').ok_or_else(invalid)?;
+        // Host names, email addresses and URIs are IA5 strings.
+        if value.is_empty() || !value.is_ascii() {
+            return Err(invalid());
+        }
+        match kind.to_ascii_lowercase().as_str() {
+            "dns" => Ok(GeneralName::Dns(value.to_string())),
+            "ip" => value.parse().map(GeneralName::Ip).map_err(|_| invalid()),
+            "email" => Ok(GeneralName::Email(value.to_string())),
+            "uri" => Ok(GeneralName::Uri(value.to_string())),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for GeneralName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeneralName::Dns(name) => write!(f, "DNS:{}", name),
+            GeneralName::Ip(ip) => write!(f, "IP:{}", ip),
+            GeneralName::Email(email) => write!(f, "email:{}", email),
+            GeneralName::Uri(uri) => write!(f, "URI:{}", uri),
+        }
+    }
+}
+
+/// The purposes a key may be used for, in the order of the bits of the key
+/// usage extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyUsage {
+    DigitalSignature,
+    NonRepudiation,
+    KeyEncipherment,
+    DataEncipherment,
+    KeyAgreement,
+    KeyCertSign,
+    CrlSign,
+}
+
+const KEY_USAGES: [(KeyUsage, &str); 7] = [
+    (KeyUsage::DigitalSignature, "digitalSignature"),
+    (KeyUsage::NonRepudiation, "nonRepudiation"),
+    (KeyUsage::KeyEncipherment, "keyEncipherment"),
+    (KeyUsage::DataEncipherment, "dataEncipherment"),
+    (KeyUsage::KeyAgreement, "keyAgreement"),
+    (KeyUsage::KeyCertSign, "keyCertSign"),
+    (KeyUsage::CrlSign, "cRLSign"),
+];
+
+impl fmt::Display for KeyUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (_, name) = KEY_USAGES.iter().find(|(usage, _)| usage == self).unwrap();
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for KeyUsage {
+    type Err = String;
+
+    /// Parses the names used by OpenSSL, like `digitalSignature`, in any case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KEY_USAGES
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(s))
+            .map(|(usage, _)| *usage)
+            .ok_or_else(|| format!("Unknown key usage: {}", s))
+    }
+}
+
+/// What a certificate or request says about its subject.
+#[derive(Debug, Clone, Default)]
+pub struct CertificateParams {
+    pub subject: Name,
+    /// The subject alternative names. The extension is marked critical if the
+    /// subject is empty.
+    pub alt_names: Vec<GeneralName>,
+    /// Whether the key belongs to a certificate authority.
+    pub ca: bool,
+    /// The number of intermediate CAs allowed below a CA, unlimited if `None`.
+    /// Only used if `ca` is set.
+    pub path_len: Option<u32>,
+    /// The allowed key usages. If empty, CAs get digitalSignature, keyCertSign
+    /// and cRLSign, other keys digitalSignature and keyEncipherment.
+    pub key_usage: Vec<KeyUsage>,
+}
+
+impl CertificateParams {
+    /// The extensions that describe the subject, which are requested by a
+    /// signing request and included in certificates.
+    fn extensions(&self) -> Vec<Vec<u8>> {
+        let mut extensions = Vec::new();
+        if !self.alt_names.is_empty() {
+            let names: Vec<Vec<u8>> = self.alt_names.iter().map(GeneralName::to_der).collect();
+            extensions.push(extension(
+                SUBJECT_ALT_NAME,
+                self.subject.is_empty(),
+                &der::sequence(&names),
+            ));
+        }
+
+        let mut constraints = Vec::new();
+        if self.ca {
+            constraints.push(der::boolean(true));
+            if let Some(path_len) = self.path_len {
+                constraints.push(der::integer(&BigInt::from(path_len)));
+            }
+        }
+        extensions.push(extension(
+            BASIC_CONSTRAINTS,
+            true,
+            &der::sequence(&constraints),
+        ));
+
+        let bits: Vec<usize> = match (self.key_usage.is_empty(), self.ca) {
+            (false, _) => self.key_usage.iter().map(|usage| *usage as usize).collect(),
+            (true, true) => vec![
+                KeyUsage::DigitalSignature as usize,
+                KeyUsage::KeyCertSign as usize,
+                KeyUsage::CrlSign as usize,
+            ],
+            (true, false) => vec![
+                KeyUsage::DigitalSignature as usize,
+                KeyUsage::KeyEncipherment as usize,
+            ],
+        };
+        extensions.push(extension(KEY_USAGE, true, &der::named_bits(&bits)));
+        extensions
+    }
+}
+
+/// The extensions of a certificate or request, as far as they are supported.
+#[derive(Debug, Clone, Default)]
+struct Extensions {
+    alt_names: Vec<GeneralName>,
+    /// Whether there are alternative names of types that are not supported.
+    unsupported_alt_names: bool,
+    ca: bool,
+    path_len: Option<u32>,
+    /// The allowed key usages, empty if the certificate does not restrict them.
+    key_usage: Vec<KeyUsage>,
+    subject_key_id: Option<Vec<u8>>,
+    /// Whether there is a critical extension that is not supported.
+    unsupported_critical: bool,
+}
+
+/// Reads the extensions from data that holds exactly their sequence. The authority
+/// key identifier and the extended key usage are accepted, but not checked.
+fn read_extensions(data: &[u8]) -> Result<Extensions, DerError> {
+    let mut reader = Reader::new(data);
+    let mut list = reader.read_sequence()?;
+    reader.finish()?;
+
+    let mut extensions = Extensions::default();
+    while !list.is_empty() {
+        let mut fields = list.read_sequence()?;
+        let oid = fields.read_oid()?;
+        let critical = match fields.peek_tag() {
+            Some(der::BOOLEAN) => fields.read_boolean()?,
+            _ => false,
+        };
+        let mut value = Reader::new(fields.read_octet_string()?);
+        fields.finish()?;
+
+        match oid.as_slice() {
+            SUBJECT_ALT_NAME => {
+                let mut names = value.read_sequence()?;
+                while !names.is_empty() {
+                    let (tag, content) = names.read_any()?;
+                    match GeneralName::decode(tag, content) {
+                        Some(name) => extensions.alt_names.push(name),
+                        None => extensions.unsupported_alt_names = true,
+                    }
+                }
+            }
+            BASIC_CONSTRAINTS => {
+                let mut constraints = value.read_sequence()?;
+                if constraints.peek_tag() == Some(der::BOOLEAN) {
+                    extensions.ca = constraints.read_boolean()?;
+                }
+                if constraints.peek_tag() == Some(der::INTEGER) {
+                    let path_len = constraints.read_unsigned()?;
+                    extensions.path_len = Some(u32::try_from(path_len).unwrap_or(u32::MAX));
+                }
+                constraints.finish()?;
+            }
+            KEY_USAGE => {
+                let bits = value.read_named_bits()?;
+                extensions.key_usage = KEY_USAGES
+                    .iter()
+                    .map(|(usage, _)| *usage)
+                    .filter(|usage| bits.contains(&(*usage as usize)))
+                    .collect();
+            }
+            SUBJECT_KEY_IDENTIFIER => {
+                extensions.subject_key_id = Some(value.read_octet_string()?.to_vec());
+            }
+            AUTHORITY_KEY_IDENTIFIER | EXTENDED_KEY_USAGE => {
+                value.read_sequence()?;
+            }
+            _ => {
+                extensions.unsupported_critical |= critical;
+                continue;
+            }
+        }
+        value.finish()?;
+    }
+    Ok(extensions)
+}
+
+/// The period in which a certificate is valid, in seconds since the Unix epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Validity {
+    pub not_before: i64,
+    pub not_after: i64,
+}
+
+impl Validity {
+    /// From now until the given number of days from now.
+    pub fn days_from_now(days: u32) -> Validity {
+        let now = jwt::now() as i64;
+        Validity {
+            not_before: now,
+            not_after: now + days as i64 * 86_400,
+        }
+    }
+
+    fn to_der(self) -> Vec<u8> {
+        der::sequence(&[time(self.not_before), time(self.not_after)])
+    }
+
+    fn read(reader: &mut Reader) -> Result<Validity, DerError> {
+        let mut times = reader.read_sequence()?;
+        let validity = Validity {
+            not_before: read_time(&mut times)?,
+            not_after: read_time(&mut times)?,
+        };
+        times.finish()?;
+        Ok(validity)
+    }
+}
+
+/// Converts days since the Unix epoch to a year, month and day of the
+/// proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let shifted = days + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Converts a year, month and day of the proleptic Gregorian calendar to days
+/// since the Unix epoch.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Reads a UTCTime or GeneralizedTime in the forms RFC 5280 allows, which are
+/// in UTC and have seconds, and returns seconds since the Unix epoch.
+fn read_time(reader: &mut Reader) -> Result<i64, DerError> {
+    let (tag, content) = reader.read_any()?;
+    let digits = std::str::from_utf8(content)
+        .ok()
+        .and_then(|text| text.strip_suffix('Z'))
+        .filter(|digits| digits.bytes().all(|byte| byte.is_ascii_digit()))
+        .ok_or(DerError::InvalidValue)?;
+    let number = |range: std::ops::Range<usize>| digits[range].parse::<i64>().unwrap();
+    let (year, rest) = match (tag, digits.len()) {
+        (der::UTC_TIME, 12) => match number(0..2) {
+            year if year < 50 => (2000 + year, 2),
+            year => (1900 + year, 2),
+        },
+        (der::GENERALIZED_TIME, 14) => (number(0..4), 4),
+        _ => return Err(DerError::InvalidValue),
+    };
+    let [month, day, hour, minute, second] =
+        [0, 2, 4, 6, 8].map(|offset| number(rest + offset..rest + offset + 2));
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return Err(DerError::InvalidValue);
+    }
+    if second > 59 {
+        return Err(DerError::InvalidValue);
+    }
+    Ok(days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Encodes a time as UTCTime for the years 1950 to 2049 and as GeneralizedTime
+/// otherwise, as RFC 5280 requires. Times after the year 9999 are clamped.
+fn time(seconds: i64) -> Vec<u8> {
+    let seconds = seconds.min(MAX_TIME);
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+    let second_of_day = seconds.rem_euclid(86_400);
+    let clock = format!(
+        "{:02}{:02}{:02}{:02}{:02}Z",
+        month,
+        day,
+        second_of_day / 3600,
+        second_of_day / 60 % 60,
+        second_of_day % 60
+    );
+    if (1950..2050).contains(&year) {
+        der::tlv(
+            der::UTC_TIME,
+            format!("{:02}{}", year % 100, clock).as_bytes(),
+        )
+    } else {
+        der::tlv(
+            der::GENERALIZED_TIME,
+            format!("{:04}{}", year, clock).as_bytes(),
+        )
+    }
+}
+
+fn extension(oid: &[u64], critical: bool, value: &[u8]) -> Vec<u8> {
+    let mut fields = vec![der::oid(oid)];
+    if critical {
+        fields.push(der::boolean(true));
+    }
+    fields.push(der::octet_string(value));
+    der::sequence(&fields)
+}
+
+fn signature_algorithm() -> Vec<u8> {
+    der::sequence(&[der::oid(SHA256_WITH_RSA_ENCRYPTION), der::null()])
+}
+
+/// Reads a signature algorithm and returns its hash function. The parameters
+/// must be NULL or absent.
+fn read_signature_algorithm(reader: &mut Reader) -> error::Result<HashAlgorithm> {
+    let mut algorithm = reader.read_sequence()?;
+    let oid = algorithm.read_oid()?;
+    if !algorithm.is_empty() {
+        algorithm.read_null()?;
+    }
+    algorithm.finish()?;
+    match oid.as_slice() {
+        SHA256_WITH_RSA_ENCRYPTION => Ok(HashAlgorithm::Sha256),
+        SHA384_WITH_RSA_ENCRYPTION => Ok(HashAlgorithm::Sha384),
+        SHA512_WITH_RSA_ENCRYPTION => Ok(HashAlgorithm::Sha512),
+        _ => Err(CertificateError::UnsupportedAlgorithm(dotted(&oid)).into()),
+    }
+}
+
+/// Splits a certificate or request into the signed data, the hash function of
+/// the signature and the signature.
+fn read_signed(data: &[u8]) -> error::Result<(Vec<u8>, HashAlgorithm, Vec<u8>)> {
+    let mut reader = Reader::new(data);
+    let mut signed = reader.read_sequence()?;
+    reader.finish()?;
+    // DER has a single encoding, so encoding the content again returns the signed bytes.
+    let data = der::tlv(der::SEQUENCE, signed.read(der::SEQUENCE)?);
+    let hash = read_signature_algorithm(&mut signed)?;
+    let signature = signed.read_bit_string()?.to_vec();
+    signed.finish()?;
+    Ok((data, hash, signature))
+}
+
+/// Reads the next element, which must be a sequence, and returns its encoding.
+fn read_encoded_sequence(reader: &mut Reader) -> Result<Vec<u8>, DerError> {
+    Ok(der::tlv(der::SEQUENCE, reader.read(der::SEQUENCE)?))
+}
+
+fn verify_signature(
+    key: &Key,
+    hash: HashAlgorithm,
+    data: &[u8],
+    signature: &[u8],
+) -> error::Result<bool> {
+    key.verify(
+        &SignatureScheme::Pkcs1v15 { hash },
+        &mut &data[..],
+        signature,
+    )
+}
+
+/// The key identifier of RFC 7093, section 2, method 1: the leftmost 160 bits
+/// of the SHA-256 hash of the subjectPublicKey bits.
+fn key_identifier(public_key_info: &[u8]) -> error::Result<Vec<u8>> {
+    let mut spki = Reader::new(public_key_info).read_sequence()?;
+    spki.read_sequence()?;
+    let mut hash = HashAlgorithm::Sha256.digest(spki.read_bit_string()?);
+    hash.truncate(20);
+    Ok(hash)
+}
+
+/// A random positive serial number of 127 bits, well within the 20 bytes
+/// allowed by RFC 5280.
+pub(crate) fn random_serial() -> BigInt {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes[0] = (bytes[0] & 0x7f) | 0x40;
+    BigInt::from_bytes_be(Sign::Plus, &bytes)
+}
+
+/// Returns the SubjectPublicKeyInfo of the key, which must be a private key
+/// that knows its public exponent.
+fn signing_key_info(key: &Key) -> error::Result<Vec<u8>> {
+    if !key.is_private() {
+        return Err(Error::InvalidKey(
+            "Certificates can only be signed with a private key".to_string(),
+        ));
+    }
+    key.public_key_info()
+        .ok_or_else(keys::missing_public_exponent)
+}
+
+/// Signs the encoded data with SHA-256 and wraps it with the algorithm and the
+/// signature, which is the common form of certificates and requests.
+fn sign(key: &Key, data: Vec<u8>) -> error::Result<Vec<u8>> {
+    let scheme = SignatureScheme::Pkcs1v15 {
+        hash: HashAlgorithm::Sha256,
+    };
+    let signature = key.sign(&scheme, &mut data.as_slice())?;
+    Ok(der::sequence(&[
+        data,
+        signature_algorithm(),
+        der::bit_string(&signature),
+    ]))
+}
+
+/// Encodes a version 3 TBSCertificate with the extensions of the params and
+/// the key identifiers. The issuer and the subject are encoded names.
+fn tbs_certificate(
+    serial: &BigInt,
+    issuer: &[u8],
+    validity: Validity,
+    subject: &[u8],
+    public_key_info: &[u8],
+    params: &CertificateParams,
+    authority_key_id: &[u8],
+) -> error::Result<Vec<u8>> {
+    let mut extensions = params.extensions();
+    extensions.push(extension(
+        SUBJECT_KEY_IDENTIFIER,
+        false,
+        &der::octet_string(&key_identifier(public_key_info)?),
+    ));
+    extensions.push(extension(
+        AUTHORITY_KEY_IDENTIFIER,
+        false,
+        &der::sequence(&[der::tlv(der::context(0, false), authority_key_id)]),
+    ));
+
+    Ok(der::sequence(&[
+        der::tlv(der::context(0, true), &der::integer(&BigInt::from(2))),
+        der::integer(serial),
+        signature_algorithm(),
+        issuer.to_vec(),
+        validity.to_der(),
+        subject.to_vec(),
+        public_key_info.to_vec(),
+        der::tlv(der::context(3, true), &der::sequence(&extensions)),
+    ]))
+}
+
+/// Creates a DER encoded certificate for the key, signed by the key itself,
+/// with a random serial number. The subject, which is also the issuer, must
+/// not be empty.
+pub fn self_signed(
+    key: &Key,
+    params: &CertificateParams,
+    validity: Validity,
+) -> error::Result<Vec<u8>> {
+    if params.subject.is_empty() {
+        return Err(Error::Parse(
+            "The subject of a self-signed certificate must not be empty".to_string(),
+        ));
+    }
+    let public_key_info = signing_key_info(key)?;
+    let name = params.subject.to_der();
+    let tbs_certificate = tbs_certificate(
+        &random_serial(),
+        &name,
+        validity,
+        &name,
+        &public_key_info,
+        params,
+        &key_identifier(&public_key_info)?,
+    )?;
+    sign(key, tbs_certificate)
+}
+
+/// Creates a DER encoded certificate signing request for the key, signed by
+/// the key to prove that the requester owns it.
+pub fn request(key: &Key, params: &CertificateParams) -> error::Result<Vec<u8>> {
+    let public_key_info = signing_key_info(key)?;
+    let extension_request = der::sequence(&[
+        der::oid(EXTENSION_REQUEST),
+        der::set(&[der::sequence(&params.extensions())]),
+    ]);
+    let request_info = der::sequence(&[
+        der::integer(&BigInt::from(0)),
+        params.subject.to_der(),
+        public_key_info,
+        der::tlv(der::context(0, true), &extension_request),
+    ]);
+    sign(key, request_info)
+}
+
+/// A parsed certificate.
+#[derive(Debug, Clone)]
+pub struct Certificate {
+    der: Vec<u8>,
+    tbs_certificate: Vec<u8>,
+    hash: HashAlgorithm,
+    signature: Vec<u8>,
+    serial: BigInt,
+    issuer: Vec<u8>,
+    subject: Vec<u8>,
+    subject_name: Name,
+    validity: Validity,
+    public_key_info: Vec<u8>,
+    extensions: Extensions,
+}
+
+impl Certificate {
+    /// Parses a DER encoded certificate. The signature is checked when the
+    /// certificate is verified.
+    pub fn from_der(data: &[u8]) -> error::Result<Certificate> {
+        let (tbs_certificate, hash, signature) = read_signed(data)?;
+        let mut tbs = Reader::new(&tbs_certificate).read_sequence()?;
+        if tbs.peek_tag() == Some(der::context(0, true)) {
+            let mut version = Reader::new(tbs.read(der::context(0, true))?);
+            if version.read_unsigned()? > BigInt::from(2) {
+                return Err(Error::Parse("Unsupported certificate version".to_string()));
+            }
+            version.finish()?;
+        }
+        let serial = tbs.read_integer()?;
+        if read_signature_algorithm(&mut tbs)? != hash {
+            return Err(Error::Parse(
+                "The certificate names two signature algorithms".to_string(),
+            ));
+        }
+        let issuer = read_encoded_sequence(&mut tbs)?;
+        let validity = Validity::read(&mut tbs)?;
+        let subject = read_encoded_sequence(&mut tbs)?;
+        let public_key_info = read_encoded_sequence(&mut tbs)?;
+        // The unique identifiers of version 2 certificates are skipped.
+        for number in [1, 2] {
+            if tbs.peek_tag() == Some(der::context(number, false)) {
+                tbs.read_any()?;
+            }
+        }
+        let extensions = match tbs.peek_tag() {
+            Some(tag) if tag == der::context(3, true) => read_extensions(tbs.read(tag)?)?,
+            _ => Extensions::default(),
+        };
+        tbs.finish()?;
+
+        Ok(Certificate {
+            der: data.to_vec(),
+            subject_name: read_name(&subject)?,
+            tbs_certificate,
+            hash,
+            signature,
+            serial,
+            issuer,
+            subject,
+            validity,
+            public_key_info,
+            extensions,
+        })
+    }
+
+    /// Reads the certificates of a PEM file, where several can follow each
+    /// other, or a single DER encoded certificate.
+    pub fn read_all(contents: &[u8]) -> error::Result<Vec<Certificate>> {
+        if !pem::is_pem(contents) {
+            return Ok(vec![Certificate::from_der(contents)?]);
+        }
+        pem::decode_all(&String::from_utf8_lossy(contents))?
+            .into_iter()
+            .map(|(label, data)| match label.as_str() {
+                CERTIFICATE_LABEL => Certificate::from_der(&data),
+                _ => Err(Error::Parse(format!(
+                    "Expected a certificate, found {}",
+                    label
+                ))),
+            })
+            .collect()
+    }
+
+    /// The DER encoding the certificate was parsed from.
+    pub fn as_der(&self) -> &[u8] {
+        &self.der
+    }
+
+    pub fn serial(&self) -> &BigInt {
+        &self.serial
+    }
+
+    pub fn subject(&self) -> &Name {
+        &self.subject_name
+    }
+
+    pub fn validity(&self) -> Validity {
+        self.validity
+    }
+
+    /// Whether the basic constraints mark the subject as a CA.
+    pub fn is_ca(&self) -> bool {
+        self.extensions.ca
+    }
+
+    /// The public key of the subject.
+    pub fn public_key(&self) -> error::Result<Key> {
+        Key::from_public_key_info(&self.public_key_info)
+    }
+
+    /// The subject for messages, or the serial number if the subject is empty.
+    fn describe(&self) -> String {
+        match self.subject_name.is_empty() {
+            true => format!("with serial number {}", self.serial.to_str_radix(16)),
+            false => self.subject_name.to_string(),
+        }
+    }
+
+    /// Checks the validity period at the time `now` and that all critical
+    /// extensions are supported.
+    fn check(&self, now: i64) -> Result<(), CertificateError> {
+        if self.extensions.unsupported_critical {
+            return Err(CertificateError::UnsupportedCritical(self.describe()));
+        }
+        if now < self.validity.not_before {
+            return Err(CertificateError::NotYetValid(self.describe()));
+        }
+        if now > self.validity.not_after {
+            return Err(CertificateError::Expired(self.describe()));
+        }
+        Ok(())
+    }
+
+    /// Checks that the certificate may issue certificates, with the given
+    /// number of intermediate CAs between it and the leaf certificate.
+    fn check_issuer(&self, intermediates: usize) -> Result<(), CertificateError> {
+        let usage = &self.extensions.key_usage;
+        if !self.extensions.ca || !(usage.is_empty() || usage.contains(&KeyUsage::KeyCertSign)) {
+            return Err(CertificateError::NotCa(self.describe()));
+        }
+        match self.extensions.path_len {
+            Some(path_len) if intermediates > path_len as usize => {
+                Err(CertificateError::PathLengthExceeded(self.describe()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that the certificate was signed with the key of the issuer.
+    fn is_signed_by(&self, issuer: &Certificate) -> error::Result<bool> {
+        verify_signature(
+            &issuer.public_key()?,
+            self.hash,
+            &self.tbs_certificate,
+            &self.signature,
+        )
+    }
+}
+
+/// A parsed certificate signing request, whose signature has been checked.
+#[derive(Debug, Clone)]
+pub struct Request {
+    subject: Vec<u8>,
+    subject_name: Name,
+    public_key_info: Vec<u8>,
+    extensions: Extensions,
+}
+
+impl Request {
+    /// Parses a DER encoded request and checks that it was signed with the key
+    /// it contains.
+    pub fn from_der(data: &[u8]) -> error::Result<Request> {
+        let (request_info, hash, signature) = read_signed(data)?;
+        let mut info = Reader::new(&request_info).read_sequence()?;
+        if info.read_integer()? != BigInt::from(0) {
+            return Err(Error::Parse("Unsupported request version".to_string()));
+        }
+        let subject = read_encoded_sequence(&mut info)?;
+        let public_key_info = read_encoded_sequence(&mut info)?;
+        let mut attributes = Reader::new(info.read(der::context(0, true))?);
+        info.finish()?;
+
+        let mut extensions = Extensions::default();
+        while !attributes.is_empty() {
+            let mut attribute = attributes.read_sequence()?;
+            let oid = attribute.read_oid()?;
+            let values = attribute.read(der::SET)?;
+            attribute.finish()?;
+            if oid == EXTENSION_REQUEST {
+                extensions = read_extensions(values)?;
+            }
+        }
+
+        let subject_name = read_name(&subject)?;
+        let key = Key::from_public_key_info(&public_key_info)?;
+        if !verify_signature(&key, hash, &request_info, &signature)? {
+            return Err(CertificateError::InvalidSignature(subject_name.to_string()).into());
+        }
+        Ok(Request {
+            subject,
+            subject_name,
+            public_key_info,
+            extensions,
+        })
+    }
+
+    /// Reads a PEM or DER encoded request.
+    pub fn read(contents: &[u8]) -> error::Result<Request> {
+        if !pem::is_pem(contents) {
+            return Request::from_der(contents);
+        }
+        match pem::decode(&String::from_utf8_lossy(contents))? {
+            (label, data) if label == REQUEST_LABEL || label == LEGACY_REQUEST_LABEL => {
+                Request::from_der(&data)
+            }
+            (label, _) => Err(Error::Parse(format!(
+                "Expected a certificate request, found {}",
+                label
+            ))),
+        }
+    }
+
+    pub fn subject(&self) -> &Name {
+        &self.subject_name
+    }
+}
+
+/// Issues a DER encoded leaf certificate for the request, signed with the CA
+/// key, which must belong to the CA certificate. The certificate has the
+/// subject, the alternative names and the key usages of the request, and its
+/// validity ends with that of the CA certificate at the latest.
+pub fn issue(
+    ca_key: &Key,
+    ca_certificate: &Certificate,
+    request: &Request,
+    validity: Validity,
+    serial: &BigInt,
+) -> error::Result<Vec<u8>> {
+    if signing_key_info(ca_key)? != ca_certificate.public_key_info {
+        return Err(Error::InvalidKey(
+            "The key does not belong to the CA certificate".to_string(),
+        ));
+    }
+    ca_certificate.check(validity.not_before)?;
+    ca_certificate.check_issuer(0)?;
+
+    let requested = &request.extensions;
+    let requests_ca = requested
+        .key_usage
+        .iter()
+        .any(|usage| matches!(usage, KeyUsage::KeyCertSign | KeyUsage::CrlSign));
+    if requested.ca || requests_ca {
+        return Err(CertificateError::CaRequested.into());
+    }
+    if requested.unsupported_alt_names {
+        return Err(Error::Parse(
+            "The request contains unsupported subject alternative names".to_string(),
+        ));
+    }
+    if request.subject_name.is_empty() && requested.alt_names.is_empty() {
+        return Err(Error::Parse(
+            "The request has neither a subject nor alternative names".to_string(),
+        ));
+    }
+
+    let params = CertificateParams {
+        subject: request.subject_name.clone(),
+        alt_names: requested.alt_names.clone(),
+        ca: false,
+        path_len: None,
+        key_usage: requested.key_usage.clone(),
+    };
+    let authority_key_id = match &ca_certificate.extensions.subject_key_id {
+        Some(key_id) => key_id.clone(),
+        None => key_identifier(&ca_certificate.public_key_info)?,
+    };
+    let validity = Validity {
+        not_after: validity.not_after.min(ca_certificate.validity.not_after),
+        ..validity
+    };
+    let tbs_certificate = tbs_certificate(
+        serial,
+        &ca_certificate.subject,
+        validity,
+        &request.subject,
+        &request.public_key_info,
+        &params,
+        &authority_key_id,
+    )?;
+    sign(ca_key, tbs_certificate)
+}
+
+/// Verifies a chain that starts with the certificate to verify, followed by
+/// the intermediate CAs that issued it in order, against the trust anchors at
+/// the time `now` in seconds since the Unix epoch.
+///
+/// Every certificate must be within its validity period and signed by the
+/// next one, the last by a trust anchor. Every issuer must be a CA whose key
+/// may sign certificates, and its path length constraint must allow the
+/// intermediate CAs below it. The trust anchor is checked the same way,
+/// except for its signature. Trust anchors at the end of the chain are skipped.
+pub fn verify_chain(chain: &[Certificate], anchors: &[Certificate], now: i64) -> error::Result<()> {
+    let is_anchor =
+        |certificate: &Certificate| anchors.iter().any(|anchor| anchor.der == certificate.der);
+    let Some(first) = chain.first() else {
+        return Err(Error::Parse(
+            "There is no certificate to verify".to_string(),
+        ));
+    };
+    if is_anchor(first) {
+        return Ok(first.check(now)?);
+    }
+    let mut path: Vec<&Certificate> = chain.iter().collect();
+    while path
+        .last()
+        .is_some_and(|certificate| is_anchor(certificate))
+    {
+        path.pop();
+    }
+
+    for (i, certificate) in path.iter().enumerate() {
+        certificate.check(now)?;
+        let issuer = match path.get(i + 1) {
+            Some(issuer) => *issuer,
+            None => {
+                // Several trust anchors can have the same name, but only one the right key.
+                let mut candidates = anchors
+                    .iter()
+                    .filter(|anchor| anchor.subject == certificate.issuer);
+                let first = candidates.clone().next();
+                let anchor = candidates
+                    .find(|anchor| certificate.is_signed_by(anchor).unwrap_or(false))
+                    .or(first)
+                    .ok_or_else(|| CertificateError::UnknownIssuer(certificate.describe()))?;
+                anchor.check(now)?;
+                anchor
+            }
+        };
+        if issuer.subject != certificate.issuer {
+            return Err(CertificateError::UnknownIssuer(certificate.describe()).into());
+        }
+        if !certificate.is_signed_by(issuer)? {
+            return Err(CertificateError::InvalidSignature(certificate.describe()).into());
+        }
+        issuer.check_issuer(i)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::test_crt_key_pair;
+
+    #[test]
+    fn name_is_parsed_in_written_order() {
+        let name: Name = "CN=example.com, o=Example\\, Inc.,C=DE".parse().unwrap();
+
+        assert_eq!("CN=example.com, O=Example\\, Inc., C=DE", name.to_string());
+        assert_eq!(name, name.to_string().parse().unwrap());
+        assert!("".parse::<Name>().unwrap().is_empty());
+        for invalid in ["CN", "CN=", "XX=a", "C=Germany", "CN=a\\"] {
+            assert!(invalid.parse::<Name>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn name_uses_printable_string_for_country() {
+        let name: Name = "C=DE,CN=a".parse().unwrap();
+
+        assert_eq!(
+            [
+                &[0x30, 0x19, 0x31, 0x0b, 0x30, 0x09, 0x06, 0x03, 0x55, 0x04, 0x06][..],
+                &[0x13, 0x02, b'D', b'E'],
+                &[0x31, 0x0a, 0x30, 0x08, 0x06, 0x03, 0x55, 0x04, 0x03],
+                &[0x0c, 0x01, b'a'],
+            ]
+            .concat(),
+            name.to_der()
+        );
+    }
+
+    #[test]
+    fn general_name_parses_types() {
+        assert_eq!(
+            GeneralName::Dns("example.com".to_string()),
+            "dns:example.com".parse().unwrap()
+        );
+        assert_eq!(
+            GeneralName::Ip("::1".parse().unwrap()),
+            "IP:::1".parse().unwrap()
+        );
+        assert_eq!(
+            vec![0x87, 0x04, 127, 0, 0, 1],
+            "IP:127.0.0.1".parse::<GeneralName>().unwrap().to_der()
+        );
+        for invalid in [
+            "example.com",
+            "DNS:",
+            "IP:localhost",
+            "X400:a",
+            "DNS:bücher.de",
+        ] {
+            assert!(invalid.parse::<GeneralName>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn key_usage_parses_openssl_names() {
+        assert_eq!(KeyUsage::CrlSign, "crlsign".parse().unwrap());
+        assert_eq!("keyCertSign", KeyUsage::KeyCertSign.to_string());
+        assert!("sign".parse::<KeyUsage>().is_err());
+    }
+
+    #[test]
+    fn time_switches_to_generalized_time_in_2050() {
+        assert_eq!(b"700101000000Z", &time(0)[2..]);
+        assert_eq!(b"491231235959Z", &time(2_524_607_999)[2..]);
+        assert_eq!(der::UTC_TIME, time(2_524_607_999)[0]);
+        assert_eq!(b"20500101000000Z", &time(2_524_608_000)[2..]);
+        assert_eq!(der::GENERALIZED_TIME, time(2_524_608_000)[0]);
+        assert_eq!(b"99991231235959Z", &time(i64::MAX)[2..]);
+        // leap day
+        assert_eq!(b"240229120000Z", &time(1_709_208_000)[2..]);
+    }
+
+    #[test]
+    fn self_signed_certificate_is_signed_by_its_key() {
+        let keys = test_crt_key_pair();
+        let params = CertificateParams {
+            subject: "CN=Test CA".parse().unwrap(),
+            ca: true,
+            path_len: Some(0),
+            ..CertificateParams::default()
+        };
+        let certificate = self_signed(keys.private(), &params, Validity::days_from_now(1)).unwrap();
+
+        let mut reader = Reader::new(&certificate);
+        let mut fields = reader.read_sequence().unwrap();
+        reader.finish().unwrap();
+        let tbs_certificate = der::tlv(der::SEQUENCE, fields.read(der::SEQUENCE).unwrap());
+        assert_eq!(
+            signature_algorithm(),
+            der::tlv(der::SEQUENCE, fields.read(der::SEQUENCE).unwrap())
+        );
+        let signature = fields.read_bit_string().unwrap();
+        fields.finish().unwrap();
+
+        let scheme = SignatureScheme::Pkcs1v15 {
+            hash: HashAlgorithm::Sha256,
+        };
+        assert!(keys
+            .public()
+            .verify(&scheme, &mut tbs_certificate.as_slice(), signature)
+            .unwrap());
+        let mut tbs = Reader::new(&tbs_certificate).read_sequence().unwrap();
+        assert_eq!(
+            der::integer(&BigInt::from(2)),
+            tbs.read(der::context(0, true)).unwrap()
+        );
+        assert!(tbs.read_unsigned().unwrap() > BigInt::from(0));
+    }
+
+    #[test]
+    fn self_signed_certificate_needs_subject() {
+        let validity = Validity::days_from_now(1);
+
+        assert!(matches!(
+            self_signed(
+                test_crt_key_pair().private(),
+                &CertificateParams::default(),
+                validity
+            ),
+            Err(Error::Parse(_))
+        ));
+    }
+
+    // Created with `openssl req -x509 -newkey rsa:1024 -sha384 -days 36500` and
+    // the basic constraints, key usage, extended key usage and alternative names.
+    const OPENSSL_CERTIFICATE: &str = "\
+-----BEGIN CERTIFICATE-----
+MIICojCCAgugAwIBAgIUboDB42nDO1Hfnmv7HeKXl5CRrc8wDQYJKoZIhvcNAQEM
+BQAwNjELMAkGA1UEBhMCREUxEDAOBgNVBAoMB0ZpeHR1cmUxFTATBgNVBAMMDEZp
+eHR1cmUgUm9vdDAgFw0yNjEwMTgxMjA1MzNaGA8yMTI2MDkyNDEyMDUzM1owNjEL
+MAkGA1UEBhMCREUxEDAOBgNVBAoMB0ZpeHR1cmUxFTATBgNVBAMMDEZpeHR1cmUg
+Um9vdDCBnzANBgkqhkiG9w0BAQEFAAOBjQAwgYkCgYEAuSi2l0WqePNvJ3pg4Y0N
++3tQOzVCk/03v0hwWr7T7LnHOXmuEBfBdMh1dWKNYzRqs/+w7YceYT8DYmlp0VXf
+Y7xGLaMuARfdAEKQPsGwg4oW0SkRcWkoPmPkLaHojXKA3EJqwbILEUbwvWIdAuyn
+lZU9+eyrJ99tQhaQnk9WRf0CAwEAAaOBqjCBpzAdBgNVHQ4EFgQU6TAj1OG0CNYs
+l/c7Flqqzk2JugYwHwYDVR0jBBgwFoAU6TAj1OG0CNYsl/c7Flqqzk2JugYwEgYD
+VR0TAQH/BAgwBgEB/wIBAjAOBgNVHQ8BAf8EBAMCAgQwEwYDVR0lBAwwCgYIKwYB
+BQUHAwEwLAYDVR0RBCUwI4IPZml4dHVyZS5leGFtcGxlhxAAAAAAAAAAAAAAAAAA
+AAABMA0GCSqGSIb3DQEBDAUAA4GBALPelfYPeCzhosaE3F+u++8JrJYMwVGUoHJG
+PlzQXZRz8bz7rRRAakiDs0Stg3vtB/0jGHKX4OpXmzDfiKkvPOg9Uh3UvajaoB3x
+eXMBkXCirUlRRkjE0LFrUZWzR1CWfQfJmJVQiPcSQpMU+/IDb7Vzbc8g94aL1RFV
+KsevrqCP
+-----END CERTIFICATE-----
+";
+
+    const NOW: i64 = 1_800_000_000;
+
+    fn validity() -> Validity {
+        Validity {
+            not_before: NOW - 86_400,
+            not_after: NOW + 86_400,
+        }
+    }
+
+    fn ca_params(subject: &str, path_len: Option<u32>) -> CertificateParams {
+        CertificateParams {
+            subject: subject.parse().unwrap(),
+            ca: true,
+            path_len,
+            ..CertificateParams::default()
+        }
+    }
+
+    fn ca_certificate(subject: &str, path_len: Option<u32>) -> Certificate {
+        let key = test_crt_key_pair();
+        let params = ca_params(subject, path_len);
+        Certificate::from_der(&self_signed(key.private(), &params, validity()).unwrap()).unwrap()
+    }
+
+    fn leaf_request(params: &CertificateParams) -> Request {
+        Request::from_der(&request(test_crt_key_pair().private(), params).unwrap()).unwrap()
+    }
+
+    fn issue_for(ca: &Certificate, params: &CertificateParams) -> error::Result<Certificate> {
+        let key = test_crt_key_pair();
+        let serial = BigInt::from(7);
+        let der = issue(
+            key.private(),
+            ca,
+            &leaf_request(params),
+            validity(),
+            &serial,
+        )?;
+        Certificate::from_der(&der)
+    }
+
+    fn certificate_error(result: error::Result<()>) -> CertificateError {
+        match result {
+            Err(Error::Certificate(err)) => err,
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn read_time_returns_encoded_time() {
+        for seconds in [0, 951_782_400, 2_524_607_999, 2_524_608_000, -1] {
+            assert_eq!(
+                seconds,
+                read_time(&mut Reader::new(&time(seconds))).unwrap()
+            );
+        }
+        for invalid in [
+            der::tlv(der::UTC_TIME, b"7001010000Z"),
+            der::tlv(der::UTC_TIME, b"700101000000+0100"),
+            der::tlv(der::GENERALIZED_TIME, b"700101000000Z"),
+            der::tlv(der::UTC_TIME, b"701301000000Z"),
+        ] {
+            assert!(read_time(&mut Reader::new(&invalid)).is_err());
+        }
+    }
+
+    #[test]
+    fn certificate_reads_openssl_certificate() {
+        let certificates = Certificate::read_all(OPENSSL_CERTIFICATE.as_bytes()).unwrap();
+        let certificate = &certificates[0];
+
+        assert_eq!(1, certificates.len());
+        assert_eq!(
+            "C=DE, O=Fixture, CN=Fixture Root",
+            certificate.subject().to_string()
+        );
+        assert_eq!(
+            "6e80c1e369c33b51df9e6bfb1de297979091adcf",
+            certificate.serial().to_str_radix(16)
+        );
+        assert_eq!(
+            Validity {
+                not_before: 1_792_325_133,
+                not_after: 4_945_925_133
+            },
+            certificate.validity()
+        );
+        assert!(certificate.is_ca());
+        assert_eq!(Some(2), certificate.extensions.path_len);
+        assert_eq!(
+            vec![KeyUsage::KeyCertSign],
+            certificate.extensions.key_usage
+        );
+        assert_eq!(
+            vec![
+                GeneralName::Dns("fixture.example".to_string()),
+                GeneralName::Ip("::1".parse().unwrap())
+            ],
+            certificate.extensions.alt_names
+        );
+        assert_eq!(1024, certificate.public_key().unwrap().bits());
+        verify_chain(&certificates, &certificates, 1_800_000_000).unwrap();
+        assert_eq!(
+            &Certificate::from_der(certificate.as_der()).unwrap().der,
+            &certificate.der
+        );
+    }
+
+    #[test]
+    fn request_keeps_subject_and_extensions() {
+        let params = CertificateParams {
+            subject: "CN=example.com".parse().unwrap(),
+            alt_names: vec!["DNS:example.com".parse().unwrap()],
+            key_usage: vec![KeyUsage::DigitalSignature],
+            ..CertificateParams::default()
+        };
+        let request = leaf_request(&params);
+
+        assert_eq!(&params.subject, request.subject());
+        assert_eq!(params.alt_names, request.extensions.alt_names);
+        assert_eq!(params.key_usage, request.extensions.key_usage);
+        assert!(!request.extensions.ca);
+    }
+
+    #[test]
+    fn request_rejects_invalid_signature() {
+        let mut der = request(test_crt_key_pair().private(), &ca_params("CN=a", None)).unwrap();
+        let last = der.len() - 1;
+        der[last] ^= 1;
+
+        assert!(matches!(
+            Request::from_der(&der),
+            Err(Error::Certificate(CertificateError::InvalidSignature(_)))
+        ));
+    }
+
+    #[test]
+    fn issued_certificate_verifies_against_ca() {
+        let ca = [ca_certificate("CN=Test CA", Some(0))];
+        let params = CertificateParams {
+            subject: "CN=example.com".parse().unwrap(),
+            alt_names: vec!["DNS:example.com".parse().unwrap()],
+            ..CertificateParams::default()
+        };
+        let leaf = [issue_for(&ca[0], &params).unwrap()];
+
+        assert_eq!(&BigInt::from(7), leaf[0].serial());
+        assert_eq!(ca[0].subject, leaf[0].issuer);
+        assert_eq!(params.alt_names, leaf[0].extensions.alt_names);
+        assert!(!leaf[0].is_ca());
+        verify_chain(&leaf, &ca, NOW).unwrap();
+        verify_chain(&[leaf[0].clone(), ca[0].clone()], &ca, NOW).unwrap();
+        assert_eq!(
+            CertificateError::Expired("CN=example.com".to_string()),
+            certificate_error(verify_chain(&leaf, &ca, NOW + 90_000))
+        );
+        assert_eq!(
+            CertificateError::NotYetValid("CN=example.com".to_string()),
+            certificate_error(verify_chain(&leaf, &ca, NOW - 90_000))
+        );
+        assert_eq!(
+            CertificateError::UnknownIssuer("CN=example.com".to_string()),
+            certificate_error(verify_chain(
+                &leaf,
+                &[ca_certificate("CN=Other CA", None)],
+                NOW
+            ))
+        );
+    }
+
+    #[test]
+    fn verify_chain_checks_issuers() {
+        let ca = ca_certificate("CN=Test CA", None);
+        let not_ca = Certificate::from_der(
+            &self_signed(
+                test_crt_key_pair().private(),
+                &CertificateParams {
+                    subject: "CN=Test CA".parse().unwrap(),
+                    ..CertificateParams::default()
+                },
+                validity(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let leaf = issue_for(
+            &ca,
+            &CertificateParams {
+                subject: "CN=leaf".parse().unwrap(),
+                ..CertificateParams::default()
+            },
+        )
+        .unwrap();
+
+        assert!(matches!(
+            issue_for(&not_ca, &CertificateParams::default()),
+            Err(Error::Certificate(CertificateError::NotCa(_)))
+        ));
+        assert_eq!(
+            CertificateError::NotCa("CN=Test CA".to_string()),
+            certificate_error(verify_chain(&[leaf], &[not_ca], NOW))
+        );
+        assert!(matches!(
+            issue_for(&ca, &ca_params("CN=sub", None)),
+            Err(Error::Certificate(CertificateError::CaRequested))
+        ));
+    }
+
+    #[test]
+    fn verify_chain_checks_path_length() {
+        // The intermediate CA is created as if by openssl from the root and
+        // the request, since `issue` only issues leaf certificates.
+        let key = test_crt_key_pair();
+        let intermediate_params = ca_params("CN=Intermediate", None);
+        let intermediate_request = leaf_request(&intermediate_params);
+        for (path_len, expected) in [
+            (
+                Some(0),
+                Some(CertificateError::PathLengthExceeded("CN=Root".to_string())),
+            ),
+            (Some(1), None),
+            (None, None),
+        ] {
+            let root = ca_certificate("CN=Root", path_len);
+            let tbs_certificate = tbs_certificate(
+                &BigInt::from(1),
+                &root.subject,
+                validity(),
+                &intermediate_request.subject,
+                &intermediate_request.public_key_info,
+                &intermediate_params,
+                &key_identifier(&root.public_key_info).unwrap(),
+            )
+            .unwrap();
+            let intermediate =
+                Certificate::from_der(&sign(key.private(), tbs_certificate).unwrap()).unwrap();
+            let leaf = issue_for(
+                &intermediate,
+                &CertificateParams {
+                    subject: "CN=leaf".parse().unwrap(),
+                    ..CertificateParams::default()
+                },
+            )
+            .unwrap();
+
+            let result = verify_chain(&[leaf, intermediate], &[root], NOW);
+            match expected {
+                Some(err) => assert_eq!(err, certificate_error(result)),
+                None => result.unwrap(),
+            }
+        }
+    }
+
+    #[test]
+    fn signing_needs_private_key() {
+        assert!(matches!(
+            request(test_crt_key_pair().public(), &CertificateParams::default()),
+            Err(Error::InvalidKey(_))
+        ));
+    }
+}
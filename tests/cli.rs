@@ -0,0 +1,94 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rsa"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rsa-cli-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn decrypt_reads_armored_input() {
+    let dir = temp_dir("armor");
+    std::fs::write(dir.join("message"), "FooBarBaz").unwrap();
+    assert!(run(&dir, &["generate", "--bits", "1024"]).status.success());
+
+    let encrypt = |armor: bool, out: &str| {
+        let mut args = vec!["encrypt", "-k", "key.public", "-i", "message", "-o", out];
+        if armor {
+            args.push("--armor");
+        }
+        assert!(run(&dir, &args).status.success());
+    };
+    encrypt(true, "message.asc");
+    encrypt(false, "message.bin");
+
+    let armored = std::fs::read_to_string(dir.join("message.asc")).unwrap();
+    assert!(armored.starts_with("-----BEGIN RSA MESSAGE-----\n"));
+
+    // Armored input is detected without --armor, and required with it.
+    for args in [&[][..], &["--armor"]] {
+        let output = run(
+            &dir,
+            &[&["decrypt", "-k", "key.private", "-i", "message.asc"], args].concat(),
+        );
+        assert!(output.status.success());
+        assert_eq!(b"FooBarBaz".to_vec(), output.stdout);
+    }
+    let output = run(
+        &dir,
+        &[
+            "decrypt",
+            "-k",
+            "key.private",
+            "-i",
+            "message.bin",
+            "--armor",
+        ],
+    );
+    assert_eq!(Some(4), output.status.code());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn sign_rejects_public_key() {
+    let dir = temp_dir("sign");
+    std::fs::write(dir.join("message"), "FooBarBaz").unwrap();
+    assert!(run(&dir, &["generate", "--bits", "1024"]).status.success());
+
+    let output = run(&dir, &["sign", "-k", "key.public", "-i", "message"]);
+    assert_eq!(Some(5), output.status.code());
+    assert!(output.stdout.is_empty());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn generate_checks_key_size_before_passphrase() {
+    let dir = temp_dir("generate");
+    let output = run(
+        &dir,
+        &[
+            "generate",
+            "--bits",
+            "256",
+            "--encrypt",
+            "--format",
+            "pkcs8",
+            "--passphrase-env",
+            "RSA_CLI_TEST_UNSET",
+        ],
+    );
+
+    assert_eq!(Some(8), output.status.code());
+    std::fs::remove_dir_all(&dir).unwrap();
+}